@@ -1,23 +1,37 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    net::IpAddr,
     ops::{Deref, DerefMut},
 };
 
 use lettre::{transport::smtp, AsyncSmtpTransport};
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 
 use crate::{config, Error};
 
 use self::{
+    api_key::ApiKeys,
     department::Department,
-    verify::{Captcha, VerifyCx, VerifyVariant},
+    lockout::Lockout,
+    password::PasswordHash,
+    totp::Totp,
+    verify::{Captcha, Device, Tokens, VerifyCx, VerifyVariant},
 };
 
+pub mod api_key;
 pub mod department;
+pub mod invite;
+pub mod jwt;
+pub mod lockout;
+pub mod oauth;
+pub mod password;
+pub mod role;
+pub mod totp;
 pub mod verify;
 
 /// A permission group of an account.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, utoipa::ToSchema)]
 pub enum Permission {
     /// Overpowered account permission.\
     /// Contains all permissions.
@@ -27,7 +41,7 @@ pub enum Permission {
     ///
     /// # Containing permissions
     ///
-    /// - [`Self::GetPubPost`]
+    /// - [`Self::GetPubPosts`]
     Post,
     /// Get public posts.
     GetPubPosts,
@@ -38,6 +52,65 @@ pub enum Permission {
     /// Appends or removes permissions from
     /// an account.
     SetPermissions,
+
+    /// Manage roles and assign them to accounts.
+    ManageRoles,
+
+    /// Manage other accounts.
+    ///
+    /// # Containing permissions
+    ///
+    /// - [`Self::ViewAccounts`]
+    ManageAccounts,
+    /// View information of other accounts.
+    ViewAccounts,
+
+    /// Review posts of other accounts.
+    ///
+    /// # Containing permissions
+    ///
+    /// - [`Self::GetPubPosts`]
+    Approve,
+
+    /// Manage posts of other accounts.
+    ///
+    /// # Containing permissions
+    ///
+    /// - [`Self::GetPubPosts`]
+    ManagePosts,
+
+    /// Manage display screens and posts rotating on them.
+    ManageScreens,
+
+    /// Get public posts and posts visible to staff only.
+    ///
+    /// # Containing permissions
+    ///
+    /// - [`Self::GetPubPosts`]
+    GetStaffPosts,
+}
+
+impl Permission {
+    /// Permissions directly implied by this permission.
+    ///
+    /// [`Self::Op`] implies all permissions, so it's not listed here.
+    #[inline]
+    const fn implied(self) -> &'static [Self] {
+        match self {
+            Permission::Post => &[Permission::GetPubPosts],
+            Permission::ManageAccounts => &[Permission::ViewAccounts],
+            Permission::Approve => &[Permission::GetPubPosts],
+            Permission::ManagePosts => &[Permission::GetPubPosts],
+            Permission::GetStaffPosts => &[Permission::GetPubPosts],
+            _ => &[],
+        }
+    }
+
+    /// Whether this permission implies the given permission,
+    /// including itself and transitive implications.
+    pub fn implies(self, other: Self) -> bool {
+        self == other || self == Permission::Op || self.implied().iter().any(|p| p.implies(other))
+    }
 }
 
 impl libaccount::Permission for Permission {
@@ -48,10 +121,7 @@ impl libaccount::Permission for Permission {
 
     #[inline]
     fn contains(&self, permission: &Self) -> bool {
-        matches!(
-            (self, permission),
-            (Permission::Op, _) | (Permission::Post, Permission::GetPubPosts)
-        )
+        self != permission && self.implies(*permission)
     }
 }
 
@@ -60,12 +130,18 @@ impl libaccount::Permission for Permission {
 pub enum Tag {
     Permission(Permission),
     Department(Department),
+    /// Name of a [`role::Role`].
+    Role(String),
+    /// A free-form label attached by admins.
+    Label(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TagEntry {
     Permission,
     Department,
+    Role,
+    Label,
 }
 
 impl libaccount::tag::Tag for Tag {
@@ -76,6 +152,8 @@ impl libaccount::tag::Tag for Tag {
         match self {
             Tag::Permission(_) => TagEntry::Permission,
             Tag::Department(_) => TagEntry::Department,
+            Tag::Role(_) => TagEntry::Role,
+            Tag::Label(_) => TagEntry::Label,
         }
     }
 }
@@ -107,15 +185,133 @@ impl libaccount::tag::PermissionEntry for TagEntry {
 impl libaccount::tag::UserDefinableEntry for TagEntry {
     #[inline]
     fn is_user_defineable(&self) -> bool {
-        !matches!(self, TagEntry::Permission)
+        !matches!(
+            self,
+            TagEntry::Permission | TagEntry::Role | TagEntry::Label
+        )
     }
 }
 
 /// The external data of a verified account.\
-/// Containing verify sessions.
-#[derive(Serialize, Deserialize, Debug)]
+/// Containing verify sessions, login tokens and credentials.
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Ext {
     verifies: HashMap<VerifyVariant, VerifyCx>,
+    tokens: Tokens,
+    password: PasswordHash,
+    totp: Option<Totp>,
+    lockout: Lockout,
+    /// The new email waiting for verification.
+    pending_email: Option<String>,
+    #[serde(with = "time::serde::timestamp::option")]
+    registered_at: Option<OffsetDateTime>,
+    suspension: Option<Suspension>,
+    last_login: Option<LastLogin>,
+    /// Permissions granted with an expiration time.
+    grants: Vec<Grant>,
+    /// Id of the avatar image resource.
+    avatar: Option<u64>,
+    notify: NotifyPrefs,
+    /// The time this account will be purged after signing out.
+    #[serde(with = "time::serde::timestamp::option")]
+    deletion: Option<OffsetDateTime>,
+    api_keys: ApiKeys,
+    privacy: Privacy,
+    /// Whether this account is waiting for approval by admins.
+    pending_approval: bool,
+    /// Previous password hashes, the most recent first.
+    password_history: std::collections::VecDeque<PasswordHash>,
+    /// Ips logged in from, the most recent first.
+    known_ips: std::collections::VecDeque<IpAddr>,
+    /// Ids of bookmarked posts.
+    bookmarks: HashSet<u64>,
+    /// Bytes of uploaded resources which are not deleted.
+    storage_usage: u64,
+    /// Incremented to invalidate all issued JWTs.
+    token_epoch: u64,
+}
+
+/// Visibility of optional fields in the public profile.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, utoipa::ToSchema)]
+pub struct Privacy {
+    pub show_phone: bool,
+    pub show_school_id: bool,
+}
+
+/// Preferences of non-essential emails.
+///
+/// Verification emails are always sent regardless of these.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, utoipa::ToSchema)]
+pub struct NotifyPrefs {
+    /// Email when a post is approved.
+    pub on_approval: bool,
+    /// Email when a post is rejected.
+    pub on_rejection: bool,
+    /// Email when logged in from a new device.
+    pub on_new_login: bool,
+    pub digest: Digest,
+    /// Email before an approved post goes off screen.
+    pub on_expiry: bool,
+}
+
+impl Default for NotifyPrefs {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            on_approval: true,
+            on_rejection: true,
+            on_new_login: true,
+            digest: Digest::default(),
+            on_expiry: true,
+        }
+    }
+}
+
+/// Frequency of digest emails.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Digest {
+    #[default]
+    Never,
+    Daily,
+    Weekly,
+}
+
+/// A permission granted to an account until a time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Grant {
+    pub permission: Permission,
+    #[serde(with = "time::serde::timestamp")]
+    pub until: OffsetDateTime,
+    /// The account granted this permission.
+    pub granter: u64,
+}
+
+impl Grant {
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        OffsetDateTime::now_utc() > self.until
+    }
+}
+
+/// The last successful login of an account.
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+pub struct LastLogin {
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub time: OffsetDateTime,
+    #[schema(value_type = String)]
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+}
+
+/// Suspension state of an account.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Suspension {
+    pub reason: String,
+    /// The time the suspension ends, `None` means forever.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub until: Option<OffsetDateTime>,
 }
 
 /// A verified account.
@@ -126,7 +322,19 @@ pub struct Ext {
 /// are verify sessions. (See [`VerifyVariant`])
 /// Verify sessions are stored in external data as [`Ext`].
 ///
-/// Currently, the only verify session is reset password.
+/// Currently, verify sessions are reset password and change email.
+///
+/// # Passwords
+///
+/// Passwords are hashed with Argon2id and stored in external data
+/// as [`PasswordHash`]. Legacy sha256 hashes stored in the inner
+/// libaccount account are re-hashed on the next successful login.
+///
+/// # Tokens
+///
+/// Login tokens are stored in external data as [`Tokens`]
+/// instead of the inner libaccount account, so they could be
+/// rotated without the password.
 #[derive(Debug)]
 pub struct Account {
     inner: libaccount::Account<Tag, Ext>,
@@ -154,21 +362,802 @@ impl Account {
             .await
     }
 
+    /// Invalidates the password and all tokens, then sends a
+    /// reset password email to user regardless of the cooldown.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the email send failed.
+    pub async fn force_reset_password<E>(
+        &mut self,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+    {
+        const PASSWORD_LEN: usize = 32;
+        self.set_password(password::generate(PASSWORD_LEN));
+        let ext = self.inner.ext_mut();
+        ext.tokens.clear();
+        ext.token_epoch += 1;
+        ext.verifies.remove(&VerifyVariant::ResetPassword);
+        self.req_reset_password(config, transport).await
+    }
+
     /// Resets the password with given new password.
     ///
     /// # Errors
     ///
     /// - Errors if the captcha is incorrect.
-    #[inline]
-    pub fn reset_password<T>(&mut self, captcha: Captcha, new_password: T) -> Result<(), Error>
+    /// - Errors if the new password is a recent password.
+    pub fn reset_password<T>(
+        &mut self,
+        captcha: Captcha,
+        new_password: T,
+        policy: &config::PasswordPolicy,
+    ) -> Result<(), Error>
     where
         T: AsRef<str>,
+    {
+        const VARIANT: VerifyVariant = VerifyVariant::ResetPassword;
+        // Validate the captcha before checking reuse, so recent
+        // passwords could not be probed.
+        self.inner
+            .ext()
+            .verifies
+            .get(&VARIANT)
+            .ok_or(Error::VerifySessionNotFound(VARIANT))?
+            .validate(captcha)?;
+        self.check_password_reuse(new_password.as_ref(), policy)?;
+        self.do_verify(VARIANT, captcha)?;
+        self.set_password(new_password);
+        Ok(())
+    }
+
+    /// Completes a password reset through a verification link, which
+    /// replaces the password with a generated one emailed to user,
+    /// and invalidates all tokens.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the captcha is incorrect, or was already used.
+    /// - Errors if the email send failed.
+    pub async fn reset_password_by_link<E>(
+        &mut self,
+        captcha: Captcha,
+        policy: &config::PasswordPolicy,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
     {
         self.do_verify(VerifyVariant::ResetPassword, captcha)?;
-        self.inner.set_password(new_password);
+        let password = password::generate_valid(policy, Self::GENERATED_PASSWORD_LEN);
+        self.set_password(&password);
+        let ext = self.inner.ext_mut();
+        ext.tokens.clear();
+        ext.token_epoch += 1;
+        self.send_password(&password, config, transport).await
+    }
+
+    /// Length of passwords generated for verification links.
+    const GENERATED_PASSWORD_LEN: usize = 12;
+
+    /// Emails a generated password to user, who should change
+    /// it after logging in.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the email send failed.
+    pub async fn send_password<E>(
+        &self,
+        password: &str,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+    {
+        crate::mail::send(
+            config,
+            self.inner.email().parse()?,
+            "Your SubIT Screen Management System password",
+            format!(
+                "Your email has been verified. Your password is: \n\n{password}\n\n\
+                Please change it after logging in."
+            ),
+            transport,
+        )
+        .await
+    }
+
+    /// Requests to change the email and sends an email to the new address.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the difference between the last request time
+    /// and the current time is no more than 10 minutes.
+    /// - Errors if the email send failed.
+    pub async fn req_change_email<E>(
+        &mut self,
+        new_email: lettre::Address,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+    {
+        self.req_verify_to(
+            VerifyVariant::ChangeEmail,
+            new_email.clone(),
+            config,
+            transport,
+        )
+        .await?;
+        self.inner.ext_mut().pending_email = Some(new_email.to_string());
+        Ok(())
+    }
+
+    /// The new email waiting for verification.
+    #[inline]
+    pub fn pending_email(&self) -> Option<&str> {
+        self.inner.ext().pending_email.as_deref()
+    }
+
+    /// Returns a copy of this account with its email changed to the
+    /// requested one, leaving this account untouched.
+    ///
+    /// Since the id is derived from the email, the caller should
+    /// insert the copy into the world before removing this account.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the captcha is incorrect.
+    pub fn change_email(&self, captcha: Captcha) -> Result<Self, Error> {
+        let mut this = self.copy()?;
+        this.do_verify(VerifyVariant::ChangeEmail, captcha)?;
+        let email = this
+            .inner
+            .ext_mut()
+            .pending_email
+            .take()
+            .ok_or(Error::VerifySessionNotFound(VerifyVariant::ChangeEmail))?;
+        let id = Unverified::new(email.clone())?.email_hash();
+        this.inner.set_email(email)?;
+        unsafe { this.inner.initialize_id(id) };
+        Ok(this)
+    }
+
+    /// Copies this account through its data encoding,
+    /// as the inner account is not cloneable.
+    fn copy(&self) -> std::io::Result<Self> {
+        let mut buf = Vec::new();
+        dmds::Data::encode(self, &mut buf)?;
+        <Self as dmds::Data>::decode(<Self as dmds::Data>::VERSION, &[self.id()], buf.as_slice())
+    }
+
+    /// Sets the password, hashed with Argon2id.
+    ///
+    /// The previous hash is kept for [`Self::check_password_reuse`].
+    pub fn set_password<T>(&mut self, password: T)
+    where
+        T: AsRef<str>,
+    {
+        let ext = self.inner.ext_mut();
+        let previous = std::mem::replace(&mut ext.password, PasswordHash::new(password.as_ref()));
+        if !previous.is_legacy() {
+            ext.password_history.push_front(previous);
+            ext.password_history
+                .truncate(config::PasswordPolicy::MAX_HISTORY);
+        }
+    }
+
+    /// Checks whether the given password is one of the recent passwords.
+    ///
+    /// # Errors
+    ///
+    /// - Errors with [`password::Rule::Reused`] if the password is reused.
+    pub fn check_password_reuse(
+        &self,
+        password: &str,
+        policy: &config::PasswordPolicy,
+    ) -> Result<(), Error> {
+        if policy.history == 0 {
+            return Ok(());
+        }
+        if self.password_matches(password)
+            || self
+                .inner
+                .ext()
+                .password_history
+                .iter()
+                .take(policy.history - 1)
+                .any(|hash| hash.matches(password))
+        {
+            Err(Error::PasswordPolicy(vec![password::Rule::Reused]))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the given password matches the account's password.
+    pub fn password_matches(&self, password: &str) -> bool {
+        let hash = &self.inner.ext().password;
+        if hash.is_legacy() {
+            self.inner.password_matches(password)
+        } else {
+            hash.matches(password)
+        }
+    }
+
+    /// Validates the given password, and re-hashes it with Argon2id
+    /// if the account's password hash is a legacy one.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the password is incorrect.
+    pub fn check_password(&mut self, password: &str) -> Result<(), Error> {
+        if !self.password_matches(password) {
+            return Err(Error::UsernameOrPasswordIncorrect);
+        }
+        if self.inner.ext().password.is_legacy() {
+            self.set_password(password);
+        }
         Ok(())
     }
 
+    /// Validates the TOTP code if two-factor authentication
+    /// is enabled for this account.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the code is required but not given.
+    /// - Errors if the code is incorrect.
+    pub fn check_totp(&self, code: Option<&str>) -> Result<(), Error> {
+        match self.inner.ext().totp {
+            Some(ref totp) if totp.is_confirmed() => totp.check(code.ok_or(Error::TotpRequired)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts enrolling two-factor authentication, and returns the
+    /// base32 encoded secret and the `otpauth` uri.
+    ///
+    /// The enrollment takes effect after [`Self::confirm_totp`].
+    ///
+    /// # Errors
+    ///
+    /// - Errors if two-factor authentication is already enabled.
+    pub fn enroll_totp(&mut self) -> Result<(String, String), Error> {
+        if self
+            .inner
+            .ext()
+            .totp
+            .as_ref()
+            .map_or(false, Totp::is_confirmed)
+        {
+            return Err(Error::PermissionDenied);
+        }
+        let totp = Totp::new();
+        let res = totp.secret_and_uri(self.inner.email());
+        self.inner.ext_mut().totp = Some(totp);
+        Ok(res)
+    }
+
+    /// Confirms the two-factor authentication enrollment.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if two-factor authentication is not enrolled.
+    /// - Errors if the code is incorrect.
+    #[inline]
+    pub fn confirm_totp(&mut self, code: &str) -> Result<(), Error> {
+        self.inner
+            .ext_mut()
+            .totp
+            .as_mut()
+            .ok_or(Error::TotpNotEnrolled)?
+            .confirm(code)
+    }
+
+    /// Disables two-factor authentication.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if two-factor authentication is not enrolled.
+    /// - Errors if the code is incorrect.
+    pub fn disable_totp(&mut self, code: &str) -> Result<(), Error> {
+        let totp = self
+            .inner
+            .ext()
+            .totp
+            .as_ref()
+            .ok_or(Error::TotpNotEnrolled)?;
+        if totp.is_confirmed() {
+            totp.check(code)?;
+        }
+        self.inner.ext_mut().totp = None;
+        Ok(())
+    }
+
+    /// Validates login credentials, with failed attempts tracked
+    /// for locking the account.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the account is locked.
+    /// - Errors if the password is incorrect.
+    /// - Errors if the TOTP code is required but not given, or incorrect.
+    pub fn check_login(
+        &mut self,
+        password: &str,
+        totp: Option<&str>,
+        lockout: &config::Lockout,
+    ) -> Result<(), Error> {
+        self.inner.ext_mut().lockout.check()?;
+        let result = self
+            .check_password(password)
+            .and_then(|_| self.check_totp(totp));
+        match result {
+            Ok(()) => self.inner.ext_mut().lockout.succeed(),
+            Err(Error::TotpRequired) => (),
+            Err(_) => self.inner.ext_mut().lockout.fail(lockout),
+        }
+        result
+    }
+
+    /// Logins with given password and TOTP code, and returns the new token
+    /// with its expiration time as a unix timestamp.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the account is locked.
+    /// - Errors if the password is incorrect.
+    /// - Errors if the TOTP code is required but not given, or incorrect.
+    pub fn login(
+        &mut self,
+        password: &str,
+        totp: Option<&str>,
+        lockout: &config::Lockout,
+        device: Device,
+    ) -> Result<(String, Option<i64>), Error> {
+        self.check_login(password, totp, lockout)?;
+        Ok(self.issue_token(device))
+    }
+
+    /// Issues a new token without validating credentials, and returns it
+    /// with its expiration time as a unix timestamp.
+    ///
+    /// Credentials should be validated by the caller, like [`Self::login`]
+    /// or through a trusted identity provider.
+    pub fn issue_token(&mut self, device: Device) -> (String, Option<i64>) {
+        let dur = self.token_expire_dur();
+        let (token, exp) = self.inner.ext_mut().tokens.new_token(dur, device);
+        (token, exp.map(OffsetDateTime::unix_timestamp))
+    }
+
+    /// Logouts the given token.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the token is invalid.
+    #[inline]
+    pub fn logout(&mut self, token: &str) -> Result<(), Error> {
+        self.inner.ext_mut().tokens.remove(token)
+    }
+
+    /// Exchanges the given token with a new one and returns
+    /// the new token with its expiration time as a unix timestamp.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the token is invalid.
+    pub fn refresh_token(&mut self, token: &str) -> Result<(String, Option<i64>), Error> {
+        self.expire_grants();
+        let dur = self.token_expire_dur();
+        let (token, exp) = self.inner.ext_mut().tokens.rotate(token, dur)?;
+        Ok((token, exp.map(OffsetDateTime::unix_timestamp)))
+    }
+
+    /// Checks whether this account is suspended.
+    ///
+    /// # Errors
+    ///
+    /// - Errors with the reason and end time if the account is suspended.
+    pub fn check_suspension(&self) -> Result<(), Error> {
+        match self.inner.ext().suspension {
+            Some(ref s) if s.until.map_or(true, |t| t > OffsetDateTime::now_utc()) => {
+                Err(Error::Suspended(s.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Suspends this account, or lifts the suspension if `None` is given.
+    ///
+    /// All tokens are invalidated when suspending.
+    pub fn set_suspension(&mut self, suspension: Option<Suspension>) {
+        if suspension.is_some() {
+            self.inner.ext_mut().tokens.clear();
+        }
+        self.inner.ext_mut().suspension = suspension;
+    }
+
+    /// Whether this account is waiting for approval by admins.
+    #[inline]
+    pub fn is_pending_approval(&self) -> bool {
+        self.inner.ext().pending_approval
+    }
+
+    #[inline]
+    pub fn set_pending_approval(&mut self, pending: bool) {
+        self.inner.ext_mut().pending_approval = pending
+    }
+
+    /// Checks whether this account is approved.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the account is pending approval.
+    #[inline]
+    pub fn check_approval(&self) -> Result<(), Error> {
+        if self.is_pending_approval() {
+            Err(Error::PendingApproval)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The time this account will be purged, if signed out.
+    #[inline]
+    pub fn deletion(&self) -> Option<OffsetDateTime> {
+        self.inner.ext().deletion
+    }
+
+    /// Schedules this account to be purged at the given time,
+    /// and invalidates all tokens and JWTs.
+    pub fn schedule_deletion(&mut self, at: OffsetDateTime) {
+        let ext = self.inner.ext_mut();
+        ext.tokens.clear();
+        ext.token_epoch += 1;
+        ext.deletion = Some(at);
+    }
+
+    /// Cancels the scheduled deletion.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the account is not pending deletion.
+    pub fn cancel_deletion(&mut self) -> Result<(), Error> {
+        self.inner
+            .ext_mut()
+            .deletion
+            .take()
+            .map(|_| ())
+            .ok_or(Error::NotPendingDeletion)
+    }
+
+    /// Checks whether this account is pending deletion.
+    ///
+    /// # Errors
+    ///
+    /// - Errors with the purge time if the account is pending deletion.
+    #[inline]
+    pub fn check_deletion(&self) -> Result<(), Error> {
+        self.deletion()
+            .map_or(Ok(()), |t| Err(Error::PendingDeletion(t)))
+    }
+
+    /// Registration time of this account.
+    #[inline]
+    pub fn registered_at(&self) -> Option<OffsetDateTime> {
+        self.inner.ext().registered_at
+    }
+
+    /// Visibility of fields in the public profile.
+    #[inline]
+    pub fn privacy(&self) -> Privacy {
+        self.inner.ext().privacy
+    }
+
+    #[inline]
+    pub fn set_privacy(&mut self, privacy: Privacy) {
+        self.inner.ext_mut().privacy = privacy
+    }
+
+    /// Email notification preferences.
+    #[inline]
+    pub fn notify_prefs(&self) -> NotifyPrefs {
+        self.inner.ext().notify
+    }
+
+    #[inline]
+    pub fn set_notify_prefs(&mut self, prefs: NotifyPrefs) {
+        self.inner.ext_mut().notify = prefs
+    }
+
+    /// Id of the avatar image resource.
+    #[inline]
+    pub fn avatar(&self) -> Option<u64> {
+        self.inner.ext().avatar
+    }
+
+    /// Ids of bookmarked posts.
+    #[inline]
+    pub fn bookmarks(&self) -> &HashSet<u64> {
+        &self.inner.ext().bookmarks
+    }
+
+    /// Bookmarks or unbookmarks a post.
+    #[inline]
+    pub fn set_bookmark(&mut self, post: u64, bookmarked: bool) {
+        let bookmarks = &mut self.inner.ext_mut().bookmarks;
+        if bookmarked {
+            bookmarks.insert(post);
+        } else {
+            bookmarks.remove(&post);
+        }
+    }
+
+    /// Bytes of uploaded resources which are not deleted.
+    #[inline]
+    pub fn storage_usage(&self) -> u64 {
+        self.inner.ext().storage_usage
+    }
+
+    /// Adds stored bytes to the usage.
+    #[inline]
+    pub fn charge_storage(&mut self, bytes: u64) {
+        let usage = &mut self.inner.ext_mut().storage_usage;
+        *usage = usage.saturating_add(bytes);
+    }
+
+    /// Removes deleted bytes from the usage.
+    #[inline]
+    pub fn refund_storage(&mut self, bytes: u64) {
+        let usage = &mut self.inner.ext_mut().storage_usage;
+        *usage = usage.saturating_sub(bytes);
+    }
+
+    /// Sets the avatar, returns the previous one.
+    #[inline]
+    pub fn set_avatar(&mut self, avatar: Option<u64>) -> Option<u64> {
+        std::mem::replace(&mut self.inner.ext_mut().avatar, avatar)
+    }
+
+    #[inline]
+    pub fn last_login(&self) -> Option<&LastLogin> {
+        self.inner.ext().last_login.as_ref()
+    }
+
+    /// Records a successful login at the current time.
+    ///
+    /// Returns whether the ip is not seen before.
+    pub fn record_login(&mut self, ip: IpAddr, user_agent: Option<String>) -> bool {
+        const MAX_KNOWN_IPS: usize = 32;
+        let ext = self.inner.ext_mut();
+        ext.last_login = Some(LastLogin {
+            time: OffsetDateTime::now_utc(),
+            ip,
+            user_agent,
+        });
+        let known = if let Some(i) = ext.known_ips.iter().position(|i| *i == ip) {
+            ext.known_ips.remove(i);
+            true
+        } else {
+            false
+        };
+        ext.known_ips.push_front(ip);
+        ext.known_ips.truncate(MAX_KNOWN_IPS);
+        !known
+    }
+
+    /// The last time this account was active, which is the last
+    /// login time, or the registration time if never logged in.
+    pub fn last_active(&self) -> Option<OffsetDateTime> {
+        self.last_login()
+            .map(|l| l.time)
+            .or_else(|| self.registered_at())
+    }
+
+    /// Whether this account has a permission implying the given one,
+    /// without resolving roles.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Permission)
+            .map_or(false, |set| {
+                set.iter()
+                    .filter_map(libaccount::tag::AsPermission::as_permission)
+                    .any(|p| p.implies(permission))
+            })
+            || self.grants().any(|g| g.permission.implies(permission))
+    }
+
+    /// Permissions of this account, without roles and timed grants.
+    pub fn permissions(&self) -> Vec<Permission> {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Permission)
+            .map_or(vec![], |set| {
+                set.iter()
+                    .filter_map(libaccount::tag::AsPermission::as_permission)
+                    .copied()
+                    .collect()
+            })
+    }
+
+    /// Unexpired timed grants of this account.
+    #[inline]
+    pub fn grants(&self) -> impl Iterator<Item = &Grant> {
+        self.inner.ext().grants.iter().filter(|g| !g.is_expired())
+    }
+
+    /// Grants a permission until the given time, replacing
+    /// the existing grant of the same permission.
+    pub fn grant(&mut self, grant: Grant) {
+        let grants = &mut self.inner.ext_mut().grants;
+        grants.retain(|g| g.permission != grant.permission);
+        grants.push(grant)
+    }
+
+    /// Revokes the timed grant of a permission, returns whether
+    /// the grant exists.
+    pub fn revoke_grant(&mut self, permission: Permission) -> bool {
+        let grants = &mut self.inner.ext_mut().grants;
+        let len = grants.len();
+        grants.retain(|g| g.permission != permission);
+        grants.len() != len
+    }
+
+    /// Removes expired timed grants.
+    #[inline]
+    pub fn expire_grants(&mut self) {
+        self.inner.ext_mut().grants.retain(|g| !g.is_expired())
+    }
+
+    /// Ids of roles assigned to this account.
+    pub fn roles(&self) -> Vec<u64> {
+        self.role_names()
+            .iter()
+            .map(|name| role::Role::id_of(name))
+            .collect()
+    }
+
+    /// Departments of this account.
+    pub fn departments(&self) -> Vec<&Department> {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Department)
+            .map_or(vec![], |set| {
+                set.iter()
+                    .filter_map(|t| {
+                        if let Tag::Department(d) = t {
+                            Some(d)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+    }
+
+    /// Labels attached to this account by admins.
+    pub fn labels(&self) -> Vec<String> {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Label)
+            .map_or(vec![], |set| {
+                set.iter()
+                    .filter_map(|t| {
+                        if let Tag::Label(label) = t {
+                            Some(label.to_owned())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+    }
+
+    /// Whether this account has the given label.
+    #[inline]
+    pub fn has_label(&self, label: &str) -> bool {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Label)
+            .map_or(false, |set| set.contains(&Tag::Label(label.to_owned())))
+    }
+
+    /// Replaces labels of this account.
+    pub fn set_labels(&mut self, labels: impl IntoIterator<Item = String>) {
+        if let Some(set) = self.inner.tags_mut().from_entry_mut(&TagEntry::Label) {
+            set.clear()
+        }
+        for label in labels {
+            self.inner.tags_mut().insert(Tag::Label(label));
+        }
+    }
+
+    /// Names of roles of this account.
+    pub fn role_names(&self) -> Vec<String> {
+        self.inner
+            .tags()
+            .from_entry(&TagEntry::Role)
+            .map_or(vec![], |set| {
+                set.iter()
+                    .filter_map(|t| {
+                        if let Tag::Role(name) = t {
+                            Some(name.to_owned())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+    }
+
+    /// Whether the given token is valid.
+    #[inline]
+    pub fn is_token_valid(&self, token: &str) -> bool {
+        self.inner.ext().tokens.is_valid(token)
+    }
+
+    /// Validates the given token and records its last used time.
+    #[inline]
+    pub fn touch_token(&mut self, token: &str) -> bool {
+        self.inner.ext_mut().tokens.touch(token)
+    }
+
+    /// API keys of this account.
+    #[inline]
+    pub fn api_keys(&self) -> &ApiKeys {
+        &self.inner.ext().api_keys
+    }
+
+    /// API keys of this account.
+    #[inline]
+    pub fn api_keys_mut(&mut self) -> &mut ApiKeys {
+        &mut self.inner.ext_mut().api_keys
+    }
+
+    /// Login tokens of this account.
+    #[inline]
+    pub fn tokens(&self) -> &Tokens {
+        &self.inner.ext().tokens
+    }
+
+    /// Login tokens of this account.
+    #[inline]
+    pub fn tokens_mut(&mut self) -> &mut Tokens {
+        &mut self.inner.ext_mut().tokens
+    }
+
+    /// Token epoch of this account, which JWTs are issued with.
+    #[inline]
+    pub fn token_epoch(&self) -> u64 {
+        self.inner.ext().token_epoch
+    }
+
+    /// Invalidates all JWTs issued before.
+    #[inline]
+    pub fn bump_token_epoch(&mut self) {
+        self.inner.ext_mut().token_epoch += 1;
+    }
+
+    #[inline]
+    fn token_expire_dur(&self) -> Option<time::Duration> {
+        self.inner
+            .token_expire_time()
+            .map(|dur| time::Duration::seconds(dur.get() as i64))
+    }
+
     /// Requests a verify session and sends an email to user.
     ///
     /// # Errors
@@ -176,6 +1165,7 @@ impl Account {
     /// - Errors if the difference between the last request time
     /// and the current time is no more than 10 minutes.
     /// - Errors if the email send failed.
+    #[inline]
     async fn req_verify<E>(
         &mut self,
         variant: VerifyVariant,
@@ -187,36 +1177,84 @@ impl Account {
         AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
     {
         let to = self.inner.email().parse()?;
-        let ext = self.inner.ext_mut();
-        if let Some(cx) = ext.verifies.get_mut(&variant) {
-            cx.update()?;
-        } else {
-            ext.verifies.insert(variant, VerifyCx::new());
-        }
-        ext.verifies
+        self.req_verify_to(variant, to, config, transport).await
+    }
+
+    /// Requests a verify session and sends an email to the given address.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the difference between the last request time
+    /// and the current time is no more than 10 minutes.
+    /// - Errors if the email send failed.
+    async fn req_verify_to<E>(
+        &mut self,
+        variant: VerifyVariant,
+        to: lettre::Address,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+    {
+        let id = self.id();
+        self.inner
+            .ext_mut()
+            .verifies
+            .entry(variant)
+            .or_insert_with(VerifyCx::new)
+            .send_email(config, to, Some(variant), Some(id), transport)
+            .await
+    }
+
+    /// Re-sends the captcha of an existing verify session,
+    /// which also extends its expiration time.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the verify session is not found.
+    /// - Errors if the difference between the last request time
+    /// and the current time is no more than 10 minutes.
+    /// - Errors if the email send failed.
+    pub async fn resend_verify<E>(
+        &mut self,
+        variant: VerifyVariant,
+        config: &config::Smtp,
+        transport: &AsyncSmtpTransport<E>,
+    ) -> Result<(), Error>
+    where
+        E: lettre::Executor,
+        AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+    {
+        let to = match variant {
+            VerifyVariant::ChangeEmail => self
+                .pending_email()
+                .ok_or(Error::VerifySessionNotFound(variant))?
+                .parse()?,
+            _ => self.inner.email().parse()?,
+        };
+        let id = self.id();
+        self.inner
+            .ext_mut()
+            .verifies
             .get_mut(&variant)
-            .unwrap()
-            .send_email(config, to, variant, transport)
+            .ok_or(Error::VerifySessionNotFound(variant))?
+            .send_email(config, to, Some(variant), Some(id), transport)
             .await
     }
 
     /// Validates the verify session captcha and removes the session entry
     /// if the captcha is correct, or throw an error.
     fn do_verify(&mut self, variant: VerifyVariant, captcha: Captcha) -> Result<(), Error> {
-        if self
-            .inner
+        self.inner
             .ext()
             .verifies
             .get(&variant)
             .ok_or(Error::VerifySessionNotFound(variant))?
-            .captcha()
-            == captcha
-        {
-            self.inner.ext_mut().verifies.remove(&variant);
-            Ok(())
-        } else {
-            Err(Error::CaptchaIncorrect)
-        }
+            .validate(captcha)?;
+        self.inner.ext_mut().verifies.remove(&variant);
+        Ok(())
     }
 }
 
@@ -227,9 +1265,39 @@ impl From<libaccount::Account<Tag, Ext>> for Account {
     }
 }
 
+/// [`Ext`] in the layout of data version 1, which only
+/// contains verify sessions.
+///
+/// Serialized in the layout of the current version.
+#[derive(Debug, Default)]
+struct ExtV1(Ext);
+
+impl<'de> Deserialize<'de> for ExtV1 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Layout {
+            verifies: HashMap<VerifyVariant, VerifyCx>,
+        }
+
+        Layout::deserialize(deserializer).map(|Layout { verifies }| {
+            Self(Ext {
+                verifies,
+                ..Default::default()
+            })
+        })
+    }
+}
+
+impl Serialize for ExtV1 {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
 impl dmds::Data for Account {
     const DIMS: usize = 1;
-    const VERSION: u32 = 1;
+    const VERSION: u32 = 2;
 
     #[inline]
     fn dim(&self, dim: usize) -> u64 {
@@ -240,11 +1308,22 @@ impl dmds::Data for Account {
     }
 
     fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        let map_err = |err| std::io::Error::new(std::io::ErrorKind::Other, err);
         match version {
             1 => {
+                // Transcodes the external data into the current layout,
+                // as the inner account could only be built by serde.
+                let old: libaccount::Account<Tag, ExtV1> =
+                    bincode::deserialize_from(buf.reader()).map_err(map_err)?;
+                let bytes = bincode::serialize(&old).map_err(map_err)?;
                 let mut inner: libaccount::Account<Tag, Ext> =
-                    bincode::deserialize_from(buf.reader())
-                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                    bincode::deserialize(&bytes).map_err(map_err)?;
+                unsafe { inner.initialize_id(dims[0]) };
+                Ok(Self { inner })
+            }
+            2 => {
+                let mut inner: libaccount::Account<Tag, Ext> =
+                    bincode::deserialize_from(buf.reader()).map_err(map_err)?;
                 unsafe { inner.initialize_id(dims[0]) };
                 Ok(Self { inner })
             }
@@ -299,6 +1378,71 @@ impl Unverified {
         })
     }
 
+    /// Verifies this account without captcha, for accounts provisioned
+    /// by administrators or through a trusted identity provider.
+    pub fn provision(
+        mut self,
+        name: String,
+        school_id: String,
+        password: String,
+    ) -> Result<Account, Error> {
+        let captcha = self.inner.ext_mut().update()?;
+        let email = self.inner.email().to_owned();
+        Ok(libaccount::Unverified::from(self)
+            .verify(libaccount::VerifyDescriptor {
+                email,
+                name,
+                school_id,
+                phone: None,
+                password,
+                tags: Default::default(),
+                ext_args: captcha,
+            })?
+            .into())
+    }
+
+    /// Validates the given captcha of the activation.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the captcha is expired.
+    /// - Errors if the captcha is incorrect.
+    #[inline]
+    pub fn validate_captcha(&self, captcha: Captcha) -> Result<(), Error> {
+        self.inner.ext().validate(captcha)
+    }
+
+    /// Verifies this account through a verification link, with the
+    /// email's local part as name and a generated password.
+    ///
+    /// Returns the account and its password, which should be
+    /// emailed to user through [`Account::send_password`].
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the captcha is incorrect or expired.
+    pub fn activate_by_link(
+        self,
+        captcha: Captcha,
+        policy: &config::PasswordPolicy,
+    ) -> Result<(Account, String), Error> {
+        let email = self.inner.email().to_owned();
+        let name = email.split('@').next().unwrap_or_default().to_owned();
+        let password = password::generate_valid(policy, Account::GENERATED_PASSWORD_LEN);
+        let account = libaccount::Unverified::from(self)
+            .verify(libaccount::VerifyDescriptor {
+                email,
+                name,
+                school_id: String::new(),
+                phone: None,
+                password: password.clone(),
+                tags: Default::default(),
+                ext_args: captcha,
+            })?
+            .into();
+        Ok((account, password))
+    }
+
     /// Requests to send a captcha with given configuration and `transport`.
     ///
     /// # Errors
@@ -318,7 +1462,7 @@ impl Unverified {
         let to = self.inner.email().parse()?;
         self.inner
             .ext_mut()
-            .send_email(config, to, "account activation", transport)
+            .send_email(config, to, None, None, transport)
             .await
     }
 }
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::Error;
+
+use super::Permission;
+
+/// A long-lived named key for integrations, carrying
+/// a restricted set of permissions.
+#[derive(Debug, Serialize, Deserialize)]
+struct ApiKey {
+    id: u64,
+    name: String,
+    permissions: HashSet<Permission>,
+    #[serde(with = "time::serde::timestamp")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp::option")]
+    last_used: Option<OffsetDateTime>,
+}
+
+/// Information of an API key, for listing.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: u64,
+    pub name: String,
+    pub permissions: HashSet<Permission>,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub last_used: Option<OffsetDateTime>,
+}
+
+/// API keys of a verified account.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApiKeys {
+    /// Key => Entry.
+    inner: HashMap<String, ApiKey>,
+}
+
+impl ApiKeys {
+    /// Prefix of API keys, telling them apart from login tokens.
+    pub const PREFIX: &'static str = "key_";
+    /// Length of a generated key, without the prefix.
+    const LEN: usize = 40;
+
+    /// Whether the given token is an API key.
+    #[inline]
+    pub fn is_api_key(token: &str) -> bool {
+        token.starts_with(Self::PREFIX)
+    }
+
+    /// Generates a new key with given name and permissions,
+    /// and returns its id and the key.
+    pub fn create(&mut self, name: String, permissions: HashSet<Permission>) -> (u64, String) {
+        let mut rng = rand::thread_rng();
+        let key: String = Self::PREFIX
+            .chars()
+            .chain(
+                (&mut rng)
+                    .sample_iter(&Alphanumeric)
+                    .take(Self::LEN)
+                    .map(char::from),
+            )
+            .collect();
+        let id = rng.gen();
+        self.inner.insert(
+            key.clone(),
+            ApiKey {
+                id,
+                name,
+                permissions,
+                created_at: OffsetDateTime::now_utc(),
+                last_used: None,
+            },
+        );
+        (id, key)
+    }
+
+    /// Validates the given key and records its last used time.
+    ///
+    /// Returns permissions of the key if it's valid.
+    pub fn touch(&mut self, key: &str) -> Option<&HashSet<Permission>> {
+        let k = self.inner.get_mut(key)?;
+        k.last_used = Some(OffsetDateTime::now_utc());
+        Some(&k.permissions)
+    }
+
+    /// Permissions of the given key.
+    #[inline]
+    pub fn permissions(&self, key: &str) -> Option<&HashSet<Permission>> {
+        self.inner.get(key).map(|k| &k.permissions)
+    }
+
+    /// Lists all keys without the keys themselves.
+    pub fn list(&self) -> Vec<ApiKeyInfo> {
+        self.inner
+            .values()
+            .map(|k| ApiKeyInfo {
+                id: k.id,
+                name: k.name.clone(),
+                permissions: k.permissions.clone(),
+                created_at: k.created_at,
+                last_used: k.last_used,
+            })
+            .collect()
+    }
+
+    /// Revokes the key with given id.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the key is not found.
+    pub fn revoke(&mut self, id: u64) -> Result<(), Error> {
+        let len = self.inner.len();
+        self.inner.retain(|_, k| k.id != id);
+        if self.inner.len() == len {
+            Err(Error::ApiKeyNotFound(id))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::Error;
+
+/// A single-use invite code for registering in invite-only mode.
+///
+/// # dmds Integration
+///
+/// The id is derived from the code, see [`Self::id_of`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invite {
+    #[serde(skip)]
+    id: u64,
+    code: String,
+
+    /// The account issued this invite.
+    issuer: u64,
+    #[serde(with = "time::serde::timestamp")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp")]
+    expire_at: OffsetDateTime,
+}
+
+impl Invite {
+    /// Length of generated invite codes.
+    const CODE_LEN: usize = 16;
+
+    /// Generates a new invite with given issuer and expire duration.
+    pub fn new(issuer: u64, expire: time::Duration) -> Self {
+        let code = super::password::generate(Self::CODE_LEN);
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Self::id_of(&code),
+            code,
+            issuer,
+            created_at: now,
+            expire_at: now + expire,
+        }
+    }
+
+    /// Gets the id of an invite with given code.
+    #[inline]
+    pub fn id_of(code: &str) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        code.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    #[inline]
+    pub fn issuer(&self) -> u64 {
+        self.issuer
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+
+    #[inline]
+    pub fn expire_at(&self) -> OffsetDateTime {
+        self.expire_at
+    }
+
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        OffsetDateTime::now_utc() > self.expire_at
+    }
+
+    /// Validates the given code against this invite.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the code is incorrect or the invite is expired.
+    pub fn validate(&self, code: &str) -> Result<(), Error> {
+        if self.code != code || self.is_expired() {
+            Err(Error::InviteInvalid)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl dmds::Data for Invite {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use time::{Instant, OffsetDateTime};
+
+use crate::{config, Error};
+
+use super::{Account, Permission, TagEntry};
+
+/// Claims of a JWT issued on login.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// Id of the account.
+    pub sub: u64,
+    /// Expiration time as a unix timestamp.
+    pub exp: i64,
+    /// Permissions of the account and its roles at the time of issuing.
+    pub perms: Vec<Permission>,
+    /// Timed grants of the account at the time of issuing,
+    /// with their expiration unix timestamps.
+    #[serde(default)]
+    pub grants: Vec<(Permission, i64)>,
+    /// Token epoch of the account at the time of issuing.
+    ///
+    /// The token is rejected once the epoch of the account changes.
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+impl Claims {
+    /// Creates claims of the given account, with permissions
+    /// granted by its roles and unexpired timed grants.
+    ///
+    /// Timed grants are kept with their expiration times so they
+    /// stop applying once expired, even if the token doesn't.
+    pub fn new(
+        account: &Account,
+        role_permissions: impl IntoIterator<Item = Permission>,
+        config: &config::Token,
+    ) -> Self {
+        let dur = account
+            .token_expire_time()
+            .map_or(config.jwt_max_age, |dur| dur.get());
+        Self {
+            sub: account.id(),
+            exp: (OffsetDateTime::now_utc() + time::Duration::seconds(dur as i64)).unix_timestamp(),
+            perms: account
+                .tags()
+                .from_entry(&TagEntry::Permission)
+                .map_or(vec![], |set| {
+                    set.iter()
+                        .filter_map(libaccount::tag::AsPermission::as_permission)
+                        .copied()
+                        .collect()
+                })
+                .into_iter()
+                .chain(role_permissions)
+                .collect(),
+            grants: account
+                .grants()
+                .map(|g| (g.permission, g.until.unix_timestamp()))
+                .collect(),
+            epoch: account.token_epoch(),
+        }
+    }
+
+    /// Signs the claims into a JWT with given secret.
+    #[inline]
+    pub fn encode(&self, secret: &[u8]) -> Result<String, Error> {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            self,
+            &jsonwebtoken::EncodingKey::from_secret(secret),
+        )
+        .map_err(From::from)
+    }
+
+    /// Validates the signature and expiration of a JWT
+    /// and returns its claims.
+    #[inline]
+    pub fn decode(token: &str, secret: &[u8]) -> Result<Self, Error> {
+        jsonwebtoken::decode(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map(|data| data.claims)
+        .map_err(From::from)
+    }
+
+    /// Whether the claims contain all given permissions,
+    /// ignoring expired grants.
+    pub fn contains_permissions(&self, permissions: &[Permission]) -> bool {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut granted = self.perms.iter().chain(
+            self.grants
+                .iter()
+                .filter(|(_, until)| *until >= now)
+                .map(|(p, _)| p),
+        );
+        permissions
+            .iter()
+            .all(|p| granted.clone().any(|c| c.implies(*p)))
+    }
+}
+
+/// Token epochs of accounts validated recently, cached so JWTs
+/// could be validated without loading the accounts on every request.
+///
+/// Only usable accounts are cached, so revoking JWTs, suspending
+/// and signing out take effect after the cached states expire.
+#[derive(Debug)]
+pub struct States {
+    /// Account => (Token epoch, Validation instant).
+    inner: HashMap<u64, (u64, Instant)>,
+    ttl: time::Duration,
+}
+
+impl States {
+    /// Creates an empty cache whose states expire after `ttl`.
+    #[inline]
+    pub fn new(ttl: time::Duration) -> Self {
+        Self {
+            inner: HashMap::new(),
+            ttl,
+        }
+    }
+
+    #[inline]
+    fn cleanup(&mut self) {
+        let ttl = self.ttl;
+        self.inner.retain(|_, (_, i)| i.elapsed() <= ttl);
+    }
+
+    /// Whether the claims were validated against a cached state.
+    pub fn is_valid(&mut self, claims: &Claims) -> bool {
+        self.cleanup();
+        self.inner
+            .get(&claims.sub)
+            .map_or(false, |(epoch, _)| *epoch == claims.epoch)
+    }
+
+    /// Caches the state of a usable account.
+    #[inline]
+    pub fn insert(&mut self, account: &Account) {
+        self.inner
+            .insert(account.id(), (account.token_epoch(), Instant::now()));
+    }
+}
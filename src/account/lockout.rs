@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{config, Error};
+
+/// Failed login tracking of an account.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Lockout {
+    /// Continuous failed login attempts.
+    failures: u32,
+    /// The time the account is locked until.
+    #[serde(with = "time::serde::timestamp::option")]
+    locked_until: Option<OffsetDateTime>,
+}
+
+impl Lockout {
+    /// Checks whether the account is locked, and clears
+    /// the lock if it's expired.
+    ///
+    /// # Errors
+    ///
+    /// - Errors with the unlock time if the account is locked.
+    pub fn check(&mut self) -> Result<(), Error> {
+        match self.locked_until {
+            Some(until) if until > OffsetDateTime::now_utc() => Err(Error::AccountLocked(until)),
+            Some(_) => {
+                *self = Self::default();
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records a failed login attempt, and locks the account
+    /// if the attempts reached the threshold.
+    pub fn fail(&mut self, config: &config::Lockout) {
+        self.failures += 1;
+        if config.threshold != 0 && self.failures >= config.threshold {
+            self.locked_until =
+                Some(OffsetDateTime::now_utc() + time::Duration::seconds(config.duration as i64));
+        }
+    }
+
+    /// Clears failed attempts after a successful login.
+    #[inline]
+    pub fn succeed(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
+use time::Instant;
+
+use crate::{config, Error};
+
+/// Storage of pending OAuth2 authorization states,
+/// used for preventing CSRF.
+#[derive(Debug, Default)]
+pub struct States {
+    /// State => Creation instant.
+    inner: HashMap<String, Instant>,
+}
+
+impl States {
+    /// Expire duration of a state.
+    const EXPIRE_DUR: time::Duration = time::Duration::minutes(10);
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    fn cleanup(&mut self) {
+        self.inner.retain(|_, i| i.elapsed() <= Self::EXPIRE_DUR);
+    }
+
+    /// Generates a new state.
+    pub fn generate(&mut self) -> String {
+        self.cleanup();
+        let state: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        self.inner.insert(state.clone(), Instant::now());
+        state
+    }
+
+    /// Consumes the given state.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the state is not found or expired.
+    pub fn consume(&mut self, state: &str) -> Result<(), Error> {
+        self.cleanup();
+        self.inner
+            .remove(state)
+            .map(|_| ())
+            .ok_or(Error::OAuthStateInvalid)
+    }
+}
+
+/// Builds the authorization url of the identity provider.
+pub fn authorize_url(config: &config::OAuth, state: &str) -> Result<reqwest::Url, Error> {
+    reqwest::Url::parse_with_params(
+        &config.auth_url,
+        &[
+            ("response_type", "code"),
+            ("client_id", &config.client_id),
+            ("redirect_uri", &config.redirect_url),
+            ("scope", &config.scopes.join(" ")),
+            ("state", state),
+        ],
+    )
+    .map_err(|_| Error::Unknown)
+}
+
+/// User information from the identity provider.
+#[derive(Debug, Deserialize)]
+pub struct UserInfo {
+    pub email: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Exchanges the authorization code for an access token, and
+/// fetches the user information with it.
+pub async fn exchange(
+    config: &config::OAuth,
+    client: &reqwest::Client,
+    code: &str,
+) -> Result<UserInfo, Error> {
+    #[derive(Deserialize)]
+    struct TokenRes {
+        access_token: String,
+    }
+
+    let TokenRes { access_token } = client
+        .post(&config.token_url)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_url),
+            ("client_id", &config.client_id),
+            ("client_secret", &config.client_secret),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    client
+        .get(&config.userinfo_url)
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .map_err(From::from)
+}
@@ -0,0 +1,165 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use std::fmt::Display;
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, Error};
+
+/// Versioned password hash of a verified account.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub enum PasswordHash {
+    /// Legacy sha256 hash stored in the inner libaccount account.
+    ///
+    /// Re-hashed into [`Self::Argon2id`] on the next successful login.
+    #[default]
+    Legacy,
+    /// Argon2id hash in PHC string format.
+    Argon2id(String),
+}
+
+impl PasswordHash {
+    /// Hashes the given password with Argon2id.
+    pub fn new(password: &str) -> Self {
+        let salt = SaltString::generate(&mut OsRng);
+        Self::Argon2id(
+            Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .expect("argon2 hashing with default params should not fail")
+                .to_string(),
+        )
+    }
+
+    /// Whether this is a legacy hash.
+    #[inline]
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, Self::Legacy)
+    }
+
+    /// Whether the given password matches this hash.
+    ///
+    /// Always returns `false` for [`Self::Legacy`] hashes,
+    /// which should be validated by the inner account.
+    pub fn matches(&self, password: &str) -> bool {
+        match self {
+            Self::Legacy => false,
+            Self::Argon2id(phc) => argon2::PasswordHash::new(phc).map_or(false, |hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            }),
+        }
+    }
+}
+
+/// Generates a random alphanumeric password with given length.
+pub fn generate(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// A password policy rule.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    MinLength,
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+    DenyList,
+    /// Same as a recent password.
+    Reused,
+}
+
+impl Display for Rule {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::MinLength => write!(f, "too short"),
+            Rule::Lowercase => write!(f, "no lowercase letter"),
+            Rule::Uppercase => write!(f, "no uppercase letter"),
+            Rule::Digit => write!(f, "no digit"),
+            Rule::Symbol => write!(f, "no symbol"),
+            Rule::DenyList => write!(f, "too common"),
+            Rule::Reused => write!(f, "recently used"),
+        }
+    }
+}
+
+/// Validates the given password against the policy.
+///
+/// # Errors
+///
+/// - Errors with all failed rules if the password
+/// doesn't meet the policy.
+pub fn validate(policy: &config::PasswordPolicy, password: &str) -> Result<(), Error> {
+    let mut failed = vec![];
+    if password.chars().count() < policy.min_length {
+        failed.push(Rule::MinLength);
+    }
+    macro_rules! require {
+        ($($r:ident => $v:ident, $f:expr),*$(,)?) => {
+            $(if policy.$r && !password.chars().any($f) {
+                failed.push(Rule::$v);
+            })*
+        };
+    }
+    require! {
+        require_lowercase => Lowercase, |c: char| c.is_lowercase(),
+        require_uppercase => Uppercase, |c: char| c.is_uppercase(),
+        require_digit => Digit, |c: char| c.is_ascii_digit(),
+        require_symbol => Symbol, |c: char| !c.is_alphanumeric() && !c.is_whitespace(),
+    }
+    if policy
+        .deny_list
+        .iter()
+        .any(|d| d.eq_ignore_ascii_case(password))
+    {
+        failed.push(Rule::DenyList);
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PasswordPolicy(failed))
+    }
+}
+
+/// Generates a random password with at least the given length
+/// which meets the policy.
+pub fn generate_valid(policy: &config::PasswordPolicy, len: usize) -> String {
+    const CLASSES: [&[u8]; 4] = [
+        b"abcdefghijklmnopqrstuvwxyz",
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+        b"0123456789",
+        b"!#$%&*+-=?@^_~",
+    ];
+    let required = [
+        policy.require_lowercase,
+        policy.require_uppercase,
+        policy.require_digit,
+        policy.require_symbol,
+    ];
+    let len = len.max(policy.min_length).max(CLASSES.len());
+    let mut rng = rand::thread_rng();
+    loop {
+        let mut password = generate(len).into_bytes();
+        // Put a character of each required class at a random position.
+        let mut positions: Vec<usize> = (0..len).collect();
+        for (class, _) in CLASSES.iter().zip(required).filter(|(_, r)| *r) {
+            let pos = positions.swap_remove(rng.gen_range(0..positions.len()));
+            password[pos] = class[rng.gen_range(0..class.len())];
+        }
+        let password = String::from_utf8(password).unwrap_or_default();
+        if validate(policy, &password).is_ok() {
+            return password;
+        }
+    }
+}
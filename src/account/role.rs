@@ -0,0 +1,94 @@
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Permission;
+
+/// A named permission group, assigned to accounts
+/// through [`super::Tag::Role`].
+///
+/// # dmds Integration
+///
+/// The id is derived from the name, see [`Self::id_of`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Role {
+    #[serde(skip)]
+    id: u64,
+    name: String,
+    permissions: HashSet<Permission>,
+}
+
+impl Role {
+    /// Creates a new role with given name and permissions.
+    #[inline]
+    pub fn new(name: String, permissions: HashSet<Permission>) -> Self {
+        Self {
+            id: Self::id_of(&name),
+            name,
+            permissions,
+        }
+    }
+
+    /// Gets the id of a role with given name.
+    #[inline]
+    pub fn id_of(name: &str) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Permissions granted by this role.
+    #[inline]
+    pub fn permissions(&self) -> &HashSet<Permission> {
+        &self.permissions
+    }
+
+    #[inline]
+    pub fn set_permissions(&mut self, permissions: HashSet<Permission>) {
+        self.permissions = permissions
+    }
+}
+
+impl dmds::Data for Role {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::Error;
+
+/// TOTP two-factor authentication state of an account.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Totp {
+    secret: Vec<u8>,
+    /// Whether the enrollment was confirmed with a valid code.
+    ///
+    /// Unconfirmed TOTP is not required on login.
+    confirmed: bool,
+}
+
+impl Totp {
+    const ISSUER: &'static str = "SubIT";
+    const DIGITS: usize = 6;
+    const SKEW: u8 = 1;
+    const STEP: u64 = 30;
+
+    /// Creates a new unconfirmed TOTP state with a random secret.
+    pub fn new() -> Self {
+        Self {
+            secret: Secret::generate_secret()
+                .to_bytes()
+                .expect("generated secret should be valid"),
+            confirmed: false,
+        }
+    }
+
+    #[inline]
+    fn totp(&self, account_name: &str) -> TOTP {
+        TOTP::new(
+            Algorithm::SHA1,
+            Self::DIGITS,
+            Self::SKEW,
+            Self::STEP,
+            self.secret.clone(),
+            Some(Self::ISSUER.to_owned()),
+            account_name.to_owned(),
+        )
+        .expect("generated secret should be valid")
+    }
+
+    /// Returns the base32 encoded secret and the `otpauth` uri.
+    #[inline]
+    pub fn secret_and_uri(&self, account_name: &str) -> (String, String) {
+        let totp = self.totp(account_name);
+        (totp.get_secret_base32(), totp.get_url())
+    }
+
+    /// Whether the enrollment was confirmed.
+    #[inline]
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed
+    }
+
+    /// Validates the given code.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the code is incorrect.
+    pub fn check(&self, code: &str) -> Result<(), Error> {
+        if self.totp("").check_current(code).unwrap_or(false) {
+            Ok(())
+        } else {
+            Err(Error::TotpIncorrect)
+        }
+    }
+
+    /// Confirms the enrollment with given code.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the code is incorrect.
+    #[inline]
+    pub fn confirm(&mut self, code: &str) -> Result<(), Error> {
+        self.check(code)?;
+        self.confirmed = true;
+        Ok(())
+    }
+}
+
+impl Default for Totp {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,7 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, net::IpAddr};
 
 use lettre::{transport::smtp, AsyncSmtpTransport};
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
@@ -10,10 +10,12 @@ use crate::{config, Error};
 use super::Ext;
 
 /// Verify session variant for a verified account.
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, utoipa::ToSchema)]
 pub enum VerifyVariant {
     /// Reset password, if the user forgot it.
     ResetPassword,
+    /// Change email, verified through the new address.
+    ChangeEmail,
 }
 
 impl Display for VerifyVariant {
@@ -21,6 +23,7 @@ impl Display for VerifyVariant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             VerifyVariant::ResetPassword => write!(f, "reset password"),
+            VerifyVariant::ChangeEmail => write!(f, "change email"),
         }
     }
 }
@@ -46,7 +49,10 @@ impl VerifyCx {
         }
     }
 
-    /// Re-request a captcha.
+    /// Duration a captcha stays valid after it was sent.
+    const EXPIRE_DUR: time::Duration = time::Duration::minutes(30);
+
+    /// Re-request a captcha, which also extends the expiration time.
     ///
     /// # Errors
     ///
@@ -65,7 +71,10 @@ impl VerifyCx {
         }
     }
 
-    /// Requests to send a captcha with given configuration and `transport`.
+    /// Requests to send a captcha with given configuration and `transport`,
+    /// with a verification link if configured.
+    ///
+    /// `variant` and `account` are `None` for account activation.
     ///
     /// # Errors
     ///
@@ -75,41 +84,103 @@ impl VerifyCx {
         &mut self,
         smtp_config: &config::Smtp,
         to: lettre::Address,
-        event: impl Display,
+        variant: Option<VerifyVariant>,
+        account: Option<u64>,
         transport: &AsyncSmtpTransport<E>,
     ) -> Result<(), Error>
     where
         E: lettre::Executor,
         AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
     {
-        const SENDER: &str = "SubIT";
         let captcha = self.update()?;
+        let event = variant.map_or_else(|| "account activation".to_owned(), |v| v.to_string());
+        let link = if let Some(ref link) = smtp_config.verify_link {
+            let claims = LinkClaims {
+                email: to.to_string(),
+                variant,
+                account,
+                captcha,
+                exp: (self.last_req + Self::EXPIRE_DUR).unix_timestamp(),
+            };
+            format!(
+                "\n\nOr open this link to verify: \n\n{}?token={}",
+                link.url,
+                claims.encode(link.secret.as_bytes())?
+            )
+        } else {
+            String::new()
+        };
 
-        let msg = lettre::message::Message::builder()
-            .sender(lettre::message::Mailbox {
-                email: smtp_config.address.to_owned(),
-                name: Some(SENDER.to_owned()),
-            })
-            .to(lettre::message::Mailbox {
-                name: None,
-                email: to,
-            })
-            .subject("Your SubIT Screen Management System verification code")
-            .body(format!(
-                "Your verification code for {event} is: \n\n{captcha}",
-            ))?;
-        let result = lettre::AsyncTransport::send(transport, msg).await;
-        if let Err(err) = result {
-            tracing::error!("error sending email with smtp: {err}");
-            return Err(err.into());
-        }
-        Ok(())
+        crate::mail::send(
+            smtp_config,
+            to,
+            "Your SubIT Screen Management System verification code",
+            format!("Your verification code for {event} is: \n\n{captcha}{link}"),
+            transport,
+        )
+        .await
     }
 
     #[inline]
     pub(crate) fn captcha(&self) -> Captcha {
         self.captcha
     }
+
+    /// Validates the given captcha.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the captcha is expired.
+    /// - Errors if the captcha is incorrect.
+    pub(crate) fn validate(&self, captcha: Captcha) -> Result<(), Error> {
+        if OffsetDateTime::now_utc() - self.last_req > Self::EXPIRE_DUR {
+            Err(Error::CaptchaExpired)
+        } else if self.captcha != captcha {
+            Err(Error::CaptchaIncorrect)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Claims of a signed verification link.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkClaims {
+    pub email: String,
+    /// `None` for account activation.
+    pub variant: Option<VerifyVariant>,
+    /// Id of the verifying account, `None` for account activation.
+    #[serde(default)]
+    pub account: Option<u64>,
+    pub captcha: Captcha,
+    /// Expiration time as a unix timestamp.
+    pub exp: i64,
+}
+
+impl LinkClaims {
+    /// Signs the claims into a token with given secret.
+    #[inline]
+    pub fn encode(&self, secret: &[u8]) -> Result<String, Error> {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            self,
+            &jsonwebtoken::EncodingKey::from_secret(secret),
+        )
+        .map_err(From::from)
+    }
+
+    /// Validates the signature and expiration of a token
+    /// and returns its claims.
+    #[inline]
+    pub fn decode(token: &str, secret: &[u8]) -> Result<Self, Error> {
+        jsonwebtoken::decode(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map(|data| data.claims)
+        .map_err(From::from)
+    }
 }
 
 impl Default for VerifyCx {
@@ -120,7 +191,7 @@ impl Default for VerifyCx {
 }
 
 /// A captcha.
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, utoipa::ToSchema)]
 pub struct Captcha(u32);
 
 impl Captcha {
@@ -158,16 +229,267 @@ impl libaccount::ExtVerify<super::Tag, Ext> for VerifyCx {
         self,
         args: &mut libaccount::VerifyDescriptor<super::Tag, Self::Args>,
     ) -> Result<Ext, Self::Error> {
-        // Validate the captcha.
-        if self.captcha != args.ext_args {
-            return Err(Error::CaptchaIncorrect);
-        }
+        self.validate(args.ext_args)?;
 
         args.tags.retain_user_definable();
         args.tags.initialize_permissions();
 
         Ok(Ext {
-            verifies: HashMap::new(),
+            registered_at: Some(OffsetDateTime::now_utc()),
+            ..Default::default()
         })
     }
 }
+
+/// A login token entry.
+#[derive(Debug, Serialize, Deserialize)]
+struct Token {
+    /// Public identifier of this token, for revoking
+    /// without exposing the token itself.
+    id: u64,
+    #[serde(with = "time::serde::timestamp")]
+    created_at: OffsetDateTime,
+    /// The expiration time, `None` means never expires.
+    #[serde(with = "time::serde::timestamp::option")]
+    expire_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    last_used: OffsetDateTime,
+    /// The admin account using this token for impersonation.
+    impersonator: Option<u64>,
+    device: Device,
+}
+
+/// Metadata of the device a token created on.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, utoipa::ToSchema)]
+pub struct Device {
+    /// Name of the device given by the client.
+    pub name: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub ip: Option<IpAddr>,
+}
+
+impl Token {
+    #[inline]
+    fn is_expired(&self) -> bool {
+        self.expire_at
+            .map_or(false, |exp| exp <= OffsetDateTime::now_utc())
+    }
+}
+
+/// Information of a login session, for listing.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct Session {
+    pub id: u64,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub expire_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub last_used: OffsetDateTime,
+    /// Whether this is the session of the requesting token.
+    pub current: bool,
+    /// The admin account impersonating through this session.
+    pub impersonator: Option<u64>,
+    pub device: Device,
+}
+
+/// Login tokens of a verified account.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Tokens {
+    /// Token => Entry.
+    inner: HashMap<String, Token>,
+}
+
+impl Tokens {
+    /// Length of a generated token.
+    const LEN: usize = 32;
+
+    /// Prefix of impersonation tokens.
+    pub const IMPERSONATION_PREFIX: &'static str = "imp_";
+
+    /// Generates a new token with given expire duration and device,
+    /// and returns it with its expiration time.
+    #[inline]
+    pub fn new_token(
+        &mut self,
+        expire_dur: Option<time::Duration>,
+        device: Device,
+    ) -> (String, Option<OffsetDateTime>) {
+        self.generate("", expire_dur, None, device)
+    }
+
+    /// Generates a new impersonation token used by the given admin,
+    /// and returns it with its expiration time.
+    #[inline]
+    pub fn new_impersonation_token(
+        &mut self,
+        expire_dur: time::Duration,
+        impersonator: u64,
+    ) -> (String, Option<OffsetDateTime>) {
+        self.generate(
+            Self::IMPERSONATION_PREFIX,
+            Some(expire_dur),
+            Some(impersonator),
+            Device::default(),
+        )
+    }
+
+    fn generate(
+        &mut self,
+        prefix: &str,
+        expire_dur: Option<time::Duration>,
+        impersonator: Option<u64>,
+        device: Device,
+    ) -> (String, Option<OffsetDateTime>) {
+        self.cleanup();
+        let mut rng = rand::thread_rng();
+        let token: String = prefix
+            .chars()
+            .chain(
+                (&mut rng)
+                    .sample_iter(&Alphanumeric)
+                    .take(Self::LEN)
+                    .map(char::from),
+            )
+            .collect();
+        let now = OffsetDateTime::now_utc();
+        let expire_at = expire_dur.map(|dur| now + dur);
+        self.inner.insert(
+            token.clone(),
+            Token {
+                id: rng.gen(),
+                created_at: now,
+                expire_at,
+                last_used: now,
+                impersonator,
+                device,
+            },
+        );
+        (token, expire_at)
+    }
+
+    /// Whether the given token is an impersonation token.
+    #[inline]
+    pub fn is_impersonation(token: &str) -> bool {
+        token.starts_with(Self::IMPERSONATION_PREFIX)
+    }
+
+    /// The admin account impersonating through the given token,
+    /// if the token is valid.
+    #[inline]
+    pub fn impersonator(&self, token: &str) -> Option<u64> {
+        self.inner
+            .get(token)
+            .filter(|t| !t.is_expired())
+            .and_then(|t| t.impersonator)
+    }
+
+    /// Whether the given token is valid.
+    #[inline]
+    pub fn is_valid(&self, token: &str) -> bool {
+        self.inner.get(token).map_or(false, |t| !t.is_expired())
+    }
+
+    /// Validates the given token and records its last used time.
+    ///
+    /// Returns whether the token is valid.
+    pub fn touch(&mut self, token: &str) -> bool {
+        if let Some(t) = self.inner.get_mut(token).filter(|t| !t.is_expired()) {
+            t.last_used = OffsetDateTime::now_utc();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Lists all valid sessions, marking the session of
+    /// the given token as current.
+    pub fn sessions(&self, current: &str) -> Vec<Session> {
+        self.inner
+            .iter()
+            .filter(|(_, t)| !t.is_expired())
+            .map(|(token, t)| Session {
+                id: t.id,
+                created_at: t.created_at,
+                expire_at: t.expire_at,
+                last_used: t.last_used,
+                current: token == current,
+                impersonator: t.impersonator,
+                device: t.device.clone(),
+            })
+            .collect()
+    }
+
+    /// Removes the given token.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the token is invalid.
+    pub fn remove(&mut self, token: &str) -> Result<(), Error> {
+        self.cleanup();
+        self.inner
+            .remove(token)
+            .map(|_| ())
+            .ok_or(Error::LibAccount(libaccount::Error::InvalidToken))
+    }
+
+    /// Removes all tokens.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    /// Removes the token with given session id.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the session is not found.
+    pub fn revoke(&mut self, id: u64) -> Result<(), Error> {
+        self.cleanup();
+        let len = self.inner.len();
+        self.inner.retain(|_, t| t.id != id);
+        if self.inner.len() == len {
+            Err(Error::SessionNotFound(id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Replaces the given token with a newly generated one on the
+    /// same device, and returns the new token with its expiration time.
+    ///
+    /// The old token is invalidated in the same operation.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the token is invalid.
+    /// - Errors if the token is an impersonation token.
+    pub fn rotate(
+        &mut self,
+        token: &str,
+        expire_dur: Option<time::Duration>,
+    ) -> Result<(String, Option<OffsetDateTime>), Error> {
+        if !self.is_valid(token) {
+            return Err(Error::LibAccount(libaccount::Error::InvalidToken));
+        }
+        if Self::is_impersonation(token) {
+            return Err(Error::PermissionDenied);
+        }
+        let device = self
+            .inner
+            .remove(token)
+            .map(|t| t.device)
+            .unwrap_or_default();
+        Ok(self.new_token(expire_dur, device))
+    }
+
+    /// Removes all expired tokens.
+    #[inline]
+    fn cleanup(&mut self) {
+        self.inner.retain(|_, t| !t.is_expired());
+    }
+}
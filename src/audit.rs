@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::account::Permission;
+
+/// An append-only audit log entry of a privileged action.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// 1 -> time (unix timestamp)
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(skip)]
+    id: u64,
+    #[serde(with = "time::serde::timestamp")]
+    time: OffsetDateTime,
+
+    /// The account performing the action.
+    actor: u64,
+    action: Action,
+}
+
+impl Entry {
+    /// Creates a new entry with the current time.
+    #[inline]
+    pub fn new(actor: u64, action: Action) -> Self {
+        Self {
+            id: crate::id::next(),
+            time: OffsetDateTime::now_utc(),
+            actor,
+            action,
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn time(&self) -> OffsetDateTime {
+        self.time
+    }
+
+    #[inline]
+    pub fn actor(&self) -> u64 {
+        self.actor
+    }
+
+    #[inline]
+    pub fn action(&self) -> &Action {
+        &self.action
+    }
+}
+
+/// A privileged action, containing its target and changes.
+///
+/// Variants are stored by index, so new variants should be
+/// appended to the end.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum Action {
+    /// Modified fields of an account.
+    ModifyAccount { target: u64, fields: Vec<String> },
+    /// Set permissions of an account.
+    SetPermissions {
+        target: u64,
+        permissions: Vec<Permission>,
+        /// Permissions before the change.
+        previous: Vec<Permission>,
+    },
+    /// Created or modified a role.
+    PutRole {
+        name: String,
+        permissions: Vec<Permission>,
+    },
+    /// Set roles of an account.
+    SetRoles {
+        target: u64,
+        roles: Vec<String>,
+        /// Roles before the change.
+        previous: Vec<String>,
+    },
+    /// Invalidated all tokens of an account.
+    ForceLogout { target: u64 },
+    /// Invalidated the password of an account and sent a reset email.
+    ForceResetPassword { target: u64 },
+    /// Suspended an account.
+    Suspend { target: u64, reason: String },
+    /// Lifted the suspension of an account.
+    Unsuspend { target: u64 },
+    /// Granted a permission to an account until a time.
+    GrantPermission {
+        target: u64,
+        permission: Permission,
+        #[serde(with = "time::serde::timestamp")]
+        #[schema(value_type = i64)]
+        until: time::OffsetDateTime,
+    },
+    /// Revoked a timed grant of an account.
+    RevokeGrant { target: u64, permission: Permission },
+    /// Approved a registered account.
+    ApproveAccount { target: u64 },
+    /// Rejected and removed a registered account.
+    RejectAccount { target: u64 },
+    /// Set labels of an account.
+    SetLabels { target: u64, labels: Vec<String> },
+    /// Reviewed a post.
+    ReviewPost {
+        post: u64,
+        status: crate::post::Status,
+    },
+    /// Issued an impersonation token of an account.
+    Impersonate { target: u64 },
+    /// Requested as an account with an impersonation token.
+    ImpersonatedRequest {
+        target: u64,
+        method: String,
+        path: String,
+    },
+    /// Deleted a post.
+    DeletePost { post: u64, creator: u64 },
+    /// Created or updated a post category.
+    PutCategory { name: String },
+    /// Deleted a post category.
+    DeleteCategory { name: String },
+    /// Reordered active posts, from the most important.
+    ReorderPosts { posts: Vec<u64> },
+    /// Registered a display screen.
+    RegisterScreen { screen: u64 },
+    /// Removed a display screen.
+    RemoveScreen { screen: u64 },
+    /// Resolved a report of a post.
+    ResolveReport { report: u64, post: u64 },
+    /// Registered a webhook.
+    RegisterWebhook { webhook: u64, url: String },
+    /// Removed a webhook.
+    RemoveWebhook { webhook: u64 },
+    /// Created or updated a post template.
+    PutTemplate { name: String },
+    /// Deleted a post template.
+    DeleteTemplate { name: String },
+    /// Pinned or unpinned a post.
+    PinPost { post: u64, pinned: bool },
+    /// Amended the last review decision of a post.
+    AmendReview {
+        post: u64,
+        status: crate::post::Status,
+    },
+    /// Archived or unarchived a post.
+    ArchivePost { post: u64, archived: bool },
+    /// Garbage collected unreferenced resources.
+    CollectResources { resources: usize, bytes: u64 },
+    /// Changed the email of the acting account, which moved
+    /// the account from its previous id.
+    ///
+    /// Entries before this refer to the account by the previous id.
+    ChangeEmail { previous: u64 },
+    /// Created a verified account from an import.
+    ImportAccount {
+        target: u64,
+        permissions: Vec<Permission>,
+    },
+    /// Signed out the acting account, scheduling it to be purged.
+    SignOut,
+}
+
+impl Action {
+    /// The target account of this action.
+    pub fn target(&self) -> Option<u64> {
+        match self {
+            Action::ModifyAccount { target, .. }
+            | Action::SetPermissions { target, .. }
+            | Action::SetRoles { target, .. }
+            | Action::ForceLogout { target }
+            | Action::ForceResetPassword { target }
+            | Action::Suspend { target, .. }
+            | Action::Unsuspend { target }
+            | Action::GrantPermission { target, .. }
+            | Action::RevokeGrant { target, .. }
+            | Action::SetLabels { target, .. }
+            | Action::ApproveAccount { target }
+            | Action::RejectAccount { target }
+            | Action::Impersonate { target }
+            | Action::ImpersonatedRequest { target, .. }
+            | Action::ImportAccount { target, .. } => Some(*target),
+            Action::ChangeEmail { previous } => Some(*previous),
+            Action::PutRole { .. }
+            | Action::ReviewPost { .. }
+            | Action::DeletePost { .. }
+            | Action::PutCategory { .. }
+            | Action::DeleteCategory { .. }
+            | Action::ReorderPosts { .. }
+            | Action::RegisterScreen { .. }
+            | Action::RemoveScreen { .. }
+            | Action::ResolveReport { .. }
+            | Action::RegisterWebhook { .. }
+            | Action::RemoveWebhook { .. }
+            | Action::PutTemplate { .. }
+            | Action::DeleteTemplate { .. }
+            | Action::PinPost { .. }
+            | Action::AmendReview { .. }
+            | Action::ArchivePost { .. }
+            | Action::CollectResources { .. }
+            | Action::SignOut => None,
+        }
+    }
+
+    /// Whether this action changes permissions or roles of an account.
+    #[inline]
+    pub fn is_permission_change(&self) -> bool {
+        matches!(
+            self,
+            Action::SetPermissions { .. }
+                | Action::SetRoles { .. }
+                | Action::GrantPermission { .. }
+                | Action::RevokeGrant { .. }
+                | Action::ImportAccount { .. }
+        )
+    }
+}
+
+/// [`Action`] in the layout of data version 1, before variants
+/// were inserted and changes were recorded with previous values.
+#[derive(Deserialize)]
+enum ActionV1 {
+    ModifyAccount {
+        target: u64,
+        fields: Vec<String>,
+    },
+    SetPermissions {
+        target: u64,
+        permissions: Vec<Permission>,
+    },
+    PutRole {
+        name: String,
+        permissions: Vec<Permission>,
+    },
+    SetRoles {
+        target: u64,
+        roles: Vec<String>,
+    },
+    ForceLogout {
+        target: u64,
+    },
+    Suspend {
+        target: u64,
+        reason: String,
+    },
+    Unsuspend {
+        target: u64,
+    },
+    GrantPermission {
+        target: u64,
+        permission: Permission,
+        #[serde(with = "time::serde::timestamp")]
+        until: time::OffsetDateTime,
+    },
+    RevokeGrant {
+        target: u64,
+        permission: Permission,
+    },
+}
+
+impl From<ActionV1> for Action {
+    fn from(value: ActionV1) -> Self {
+        match value {
+            ActionV1::ModifyAccount { target, fields } => Action::ModifyAccount { target, fields },
+            ActionV1::SetPermissions {
+                target,
+                permissions,
+            } => Action::SetPermissions {
+                target,
+                permissions,
+                previous: vec![],
+            },
+            ActionV1::PutRole { name, permissions } => Action::PutRole { name, permissions },
+            ActionV1::SetRoles { target, roles } => Action::SetRoles {
+                target,
+                roles,
+                previous: vec![],
+            },
+            ActionV1::ForceLogout { target } => Action::ForceLogout { target },
+            ActionV1::Suspend { target, reason } => Action::Suspend { target, reason },
+            ActionV1::Unsuspend { target } => Action::Unsuspend { target },
+            ActionV1::GrantPermission {
+                target,
+                permission,
+                until,
+            } => Action::GrantPermission {
+                target,
+                permission,
+                until,
+            },
+            ActionV1::RevokeGrant { target, permission } => {
+                Action::RevokeGrant { target, permission }
+            }
+        }
+    }
+}
+
+impl dmds::Data for Entry {
+    const DIMS: usize = 2;
+    const VERSION: u32 = 2;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            1 => self.time.unix_timestamp() as u64,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                #[derive(Deserialize)]
+                struct EntryV1 {
+                    #[serde(with = "time::serde::timestamp")]
+                    time: OffsetDateTime,
+                    actor: u64,
+                    action: ActionV1,
+                }
+
+                let v1: EntryV1 = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                Ok(Self {
+                    id: dims[0],
+                    time: v1.time,
+                    actor: v1.actor,
+                    action: v1.action.into(),
+                })
+            }
+            2 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use time::Instant;
+
+use crate::Error;
+
+/// An arithmetic challenge for telling humans and bots apart.
+#[derive(Debug, Serialize, Clone, utoipa::ToSchema)]
+pub struct Challenge {
+    pub id: u64,
+    /// The question, like `3 + 5 = ?`.
+    pub question: String,
+}
+
+/// Answer of a [`Challenge`] from the client.
+#[derive(Debug, Deserialize, Clone, Copy, utoipa::ToSchema)]
+pub struct Answer {
+    pub id: u64,
+    pub answer: i64,
+}
+
+/// Storage of issued challenges.
+#[derive(Debug, Default)]
+pub struct Challenges {
+    /// Id => (Answer, Creation instant).
+    inner: HashMap<u64, (i64, Instant)>,
+}
+
+impl Challenges {
+    /// Expire duration of a challenge.
+    const EXPIRE_DUR: time::Duration = time::Duration::minutes(5);
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    fn cleanup(&mut self) {
+        self.inner
+            .retain(|_, (_, i)| i.elapsed() <= Self::EXPIRE_DUR);
+    }
+
+    /// Issues a new challenge.
+    pub fn issue(&mut self) -> Challenge {
+        self.cleanup();
+        let mut rng = rand::thread_rng();
+        let (a, b): (i64, i64) = (rng.gen_range(1..50), rng.gen_range(1..50));
+        let (question, answer) = match rng.gen_range(0..3) {
+            0 => (format!("{a} + {b} = ?"), a + b),
+            1 => (format!("{a} - {b} = ?"), a - b),
+            _ => (format!("{a} × {b} = ?"), a * b),
+        };
+        let mut id = rng.gen();
+        while self.inner.contains_key(&id) {
+            id = rng.gen();
+        }
+        self.inner.insert(id, (answer, Instant::now()));
+        Challenge { id, question }
+    }
+
+    /// Validates the answer and consumes the challenge,
+    /// whether the answer is correct or not.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the challenge is not found, expired or
+    /// the answer is incorrect.
+    pub fn validate(&mut self, answer: Answer) -> Result<(), Error> {
+        self.cleanup();
+        match self.inner.remove(&answer.id) {
+            Some((a, _)) if a == answer.answer => Ok(()),
+            _ => Err(Error::CaptchaIncorrect),
+        }
+    }
+}
@@ -3,8 +3,218 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// HTTP server configuration.
+    #[serde(default)]
+    pub server: Server,
     /// SMTP configuration.
     pub smtp: Smtp,
+    /// Login token configuration.
+    #[serde(default)]
+    pub token: Token,
+    /// OAuth2 login configuration.
+    ///
+    /// OAuth2 login is disabled if not present.
+    #[serde(default)]
+    pub oauth: Option<OAuth>,
+    /// Rate limits of sensitive account operations.
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    /// Password strength requirements.
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    /// Account lockout after repeated failed logins.
+    #[serde(default)]
+    pub lockout: Lockout,
+    /// Registration configuration.
+    #[serde(default)]
+    pub registration: Registration,
+    /// Resource storage configuration.
+    #[serde(default)]
+    pub resource: Resource,
+    /// Account deletion configuration.
+    #[serde(default)]
+    pub deletion: Deletion,
+    /// Post reviewing configuration.
+    #[serde(default)]
+    pub review: Review,
+    /// Post expiry reminder configuration.
+    #[serde(default)]
+    pub expiry_reminder: ExpiryReminder,
+    /// Post duration limits.
+    #[serde(default)]
+    pub post_duration: PostDuration,
+    /// Atom feed of accepted posts.
+    #[serde(default)]
+    pub feed: Feed,
+}
+
+impl Config {
+    /// Environment variable of the config file path.
+    pub const PATH_ENV: &'static str = "SMS3_CONFIG";
+    /// Config file path if [`Self::PATH_ENV`] is not set.
+    pub const DEFAULT_PATH: &'static str = "./config.toml";
+
+    /// Loads the configuration from a TOML file, then applies
+    /// overrides from environment variables:
+    ///
+    /// - `SMS3_ADDR`: [`Server::addr`].
+    /// - `SMS3_DATA_PATH`: [`Server::data_path`].
+    /// - `SMS3_RESOURCE_PATH`: [`Resource::path`].
+    /// - `SMS3_NODE`: [`Server::node`].
+    ///
+    /// Relative resource paths are resolved under the data path.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if [`Token::jwt_secret`] is missing in [`TokenMode::Jwt`] mode.
+    /// - Errors if [`PasswordPolicy::history`] exceeds [`PasswordPolicy::MAX_HISTORY`].
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, crate::Error> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&raw)
+            .map_err(|err| crate::Error::Config(format!("{}: {err}", path.display())))?;
+        config.apply_env()?;
+        config.resource.path = config.server.data_path.join(&config.resource.path);
+        config.resource.partial_path = config.server.data_path.join(&config.resource.partial_path);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validates constraints between fields which could not
+    /// be expressed in types.
+    fn validate(&self) -> Result<(), crate::Error> {
+        if self.token.mode == TokenMode::Jwt
+            && self.token.jwt_secret().map_or(true, <[u8]>::is_empty)
+        {
+            return Err(crate::Error::Config(
+                "token.jwt_secret is required in jwt mode".to_owned(),
+            ));
+        }
+        if self.password_policy.history > PasswordPolicy::MAX_HISTORY {
+            return Err(crate::Error::Config(format!(
+                "password_policy.history should be no more than {}",
+                PasswordPolicy::MAX_HISTORY
+            )));
+        }
+        Ok(())
+    }
+
+    /// Applies overrides from environment variables, see [`Self::load`].
+    fn apply_env(&mut self) -> Result<(), crate::Error> {
+        fn var<T: std::str::FromStr>(key: &str) -> Result<Option<T>, crate::Error>
+        where
+            T::Err: std::fmt::Display,
+        {
+            std::env::var(key)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|err| crate::Error::Config(format!("invalid {key}: {err}")))
+                })
+                .transpose()
+        }
+
+        if let Some(addr) = var("SMS3_ADDR")? {
+            self.server.addr = addr;
+        }
+        if let Some(path) = var("SMS3_DATA_PATH")? {
+            self.server.data_path = path;
+        }
+        if let Some(path) = var("SMS3_RESOURCE_PATH")? {
+            self.resource.path = path;
+        }
+        if let Some(node) = var("SMS3_NODE")? {
+            self.server.node = node;
+        }
+        Ok(())
+    }
+
+    /// Gets the reviewer pool of a post by the first matching route.
+    pub fn route<'a>(
+        &'a self,
+        category: Option<&str>,
+        departments: &[&str],
+        labels: &[String],
+    ) -> Option<&'a str> {
+        self.review
+            .routes
+            .iter()
+            .find(|r| {
+                r.category.as_deref().map_or(true, |c| category == Some(c))
+                    && r.department
+                        .as_deref()
+                        .map_or(true, |d| departments.contains(&d))
+                    && r.label.as_ref().map_or(true, |l| labels.contains(l))
+            })
+            .map(|r| r.pool.as_str())
+    }
+}
+
+/// HTTP server configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Server {
+    /// Address the server listens on.
+    #[serde(default = "Server::default_addr")]
+    pub addr: std::net::SocketAddr,
+    /// Root directory of the databases.
+    ///
+    /// Relative paths of resources are resolved under it.
+    #[serde(default = "Server::default_data_path")]
+    pub data_path: std::path::PathBuf,
+    /// Node id of this server, see [`crate::id::set_node`].
+    #[serde(default)]
+    pub node: u16,
+    /// Serves HTTPS directly if present, for deployments
+    /// without a reverse proxy.
+    #[serde(default)]
+    pub tls: Option<Tls>,
+}
+
+/// TLS configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tls {
+    /// Path of the PEM certificate chain.
+    pub cert: std::path::PathBuf,
+    /// Path of the PEM private key.
+    pub key: std::path::PathBuf,
+    /// Seconds between reloads of the certificate and key from their
+    /// files, so renewed certificates are served without restarting.
+    ///
+    /// Never reloads if `0`.
+    #[serde(default = "Tls::default_reload_interval")]
+    pub reload_interval: u64,
+}
+
+impl Tls {
+    #[inline]
+    fn default_reload_interval() -> u64 {
+        60 * 60
+    }
+}
+
+impl Server {
+    #[inline]
+    fn default_addr() -> std::net::SocketAddr {
+        ([127, 0, 0, 1], 8080).into()
+    }
+
+    #[inline]
+    fn default_data_path() -> std::path::PathBuf {
+        "./data".into()
+    }
+}
+
+impl Default for Server {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            addr: Self::default_addr(),
+            data_path: Self::default_data_path(),
+            node: 0,
+            tls: None,
+        }
+    }
 }
 
 /// SMTP mailing configuration.
@@ -26,6 +236,24 @@ pub struct Smtp {
     ///
     /// Serialized and deserialized as `PascalCase`.
     pub auth: Vec<smtp::authentication::Mechanism>,
+
+    /// Verification links included in captcha emails.
+    ///
+    /// Only captchas are sent if not present.
+    #[serde(default)]
+    pub verify_link: Option<VerifyLink>,
+}
+
+/// Verification link configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyLink {
+    /// Public url of the verify link handler of this server.
+    pub url: String,
+    /// The client page the link handler redirects to after
+    /// completing the verification, with `email` and `status` queries.
+    pub redirect_url: String,
+    /// The secret used for signing links.
+    pub secret: String,
 }
 
 impl Smtp {
@@ -46,3 +274,731 @@ impl Smtp {
         Ok(builder.build())
     }
 }
+
+/// Login token configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Token {
+    /// The kind of tokens issued on login.
+    #[serde(default)]
+    pub mode: TokenMode,
+    /// The secret used for signing JWTs.
+    ///
+    /// Required if [`Self::mode`] is [`TokenMode::Jwt`].
+    #[serde(default)]
+    pub jwt_secret: Option<String>,
+    /// Expire duration of JWTs issued for accounts whose tokens
+    /// never expire, as seconds.
+    #[serde(default = "Token::default_jwt_max_age")]
+    pub jwt_max_age: u64,
+    /// Duration validated accounts are cached for JWTs, as seconds.
+    ///
+    /// JWTs are validated against the account epoch, suspension and
+    /// deletion without loading the account while it is cached, so
+    /// revoking JWTs, suspending or signing out an account takes up
+    /// to this long to take effect. `0` validates on every request.
+    #[serde(default = "Token::default_jwt_state_ttl")]
+    pub jwt_state_ttl: u64,
+}
+
+impl Token {
+    #[inline]
+    fn default_jwt_max_age() -> u64 {
+        60 * 60 * 24 * 30
+    }
+
+    #[inline]
+    fn default_jwt_state_ttl() -> u64 {
+        30
+    }
+
+    /// Returns the JWT secret if JWT mode is enabled.
+    #[inline]
+    pub fn jwt_secret(&self) -> Option<&[u8]> {
+        if self.mode == TokenMode::Jwt {
+            self.jwt_secret.as_deref().map(str::as_bytes)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Token {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            mode: TokenMode::default(),
+            jwt_secret: None,
+            jwt_max_age: Self::default_jwt_max_age(),
+            jwt_state_ttl: Self::default_jwt_state_ttl(),
+        }
+    }
+}
+
+/// Kind of login tokens.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMode {
+    /// Random tokens stored in the account.
+    #[default]
+    Opaque,
+    /// Signed JWTs validated against states of accounts cached
+    /// for [`Token::jwt_state_ttl`].
+    Jwt,
+}
+
+/// OAuth2 authorization-code login configuration,
+/// for signing in with the school identity provider.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuth {
+    pub client_id: String,
+    pub client_secret: String,
+
+    /// The authorization endpoint of the provider.
+    pub auth_url: String,
+    /// The token endpoint of the provider.
+    pub token_url: String,
+    /// The user information endpoint of the provider.
+    ///
+    /// The response should contain an `email` field.
+    pub userinfo_url: String,
+    /// The callback url of this server registered to the provider.
+    pub redirect_url: String,
+
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// Rate limits of sensitive account operations.
+///
+/// Each limit applies to both the client ip and the target email,
+/// except `change_email` which applies to the account.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RateLimit {
+    #[serde(default = "RateLimit::default_login")]
+    pub login: Limit,
+    #[serde(default = "RateLimit::default_register")]
+    pub register: Limit,
+    #[serde(default = "RateLimit::default_reset_password")]
+    pub reset_password: Limit,
+    #[serde(default = "RateLimit::default_change_email")]
+    pub change_email: Limit,
+}
+
+impl RateLimit {
+    #[inline]
+    fn default_login() -> Limit {
+        Limit {
+            max: 10,
+            window: 60,
+        }
+    }
+
+    #[inline]
+    fn default_register() -> Limit {
+        Limit {
+            max: 5,
+            window: 60 * 60,
+        }
+    }
+
+    #[inline]
+    fn default_reset_password() -> Limit {
+        Limit {
+            max: 5,
+            window: 60 * 60,
+        }
+    }
+
+    #[inline]
+    fn default_change_email() -> Limit {
+        Limit {
+            max: 5,
+            window: 60 * 60,
+        }
+    }
+}
+
+impl Default for RateLimit {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            login: Self::default_login(),
+            register: Self::default_register(),
+            reset_password: Self::default_reset_password(),
+            change_email: Self::default_change_email(),
+        }
+    }
+}
+
+/// A rate limit.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Limit {
+    /// Max requests in a window.
+    pub max: u32,
+    /// Duration of a window, as seconds.
+    pub window: u64,
+}
+
+/// Password strength requirements.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    #[serde(default = "PasswordPolicy::default_min_length")]
+    pub min_length: usize,
+    #[serde(default)]
+    pub require_lowercase: bool,
+    #[serde(default)]
+    pub require_uppercase: bool,
+    #[serde(default)]
+    pub require_digit: bool,
+    #[serde(default)]
+    pub require_symbol: bool,
+    /// Passwords that are not allowed, compared case-insensitively.
+    #[serde(default)]
+    pub deny_list: Vec<String>,
+    /// Count of recent passwords, including the current one,
+    /// that could not be reused.
+    ///
+    /// Zero means reusing is allowed, and it should be no more
+    /// than [`Self::MAX_HISTORY`].
+    #[serde(default = "PasswordPolicy::default_history")]
+    pub history: usize,
+}
+
+impl PasswordPolicy {
+    /// Max count of previous password hashes kept in an account.
+    pub const MAX_HISTORY: usize = 24;
+
+    #[inline]
+    fn default_min_length() -> usize {
+        8
+    }
+
+    #[inline]
+    fn default_history() -> usize {
+        5
+    }
+}
+
+impl Default for PasswordPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min_length: Self::default_min_length(),
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            deny_list: vec![],
+            history: Self::default_history(),
+        }
+    }
+}
+
+/// Account lockout configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lockout {
+    /// Continuous failed login attempts before locking the account.
+    ///
+    /// Zero means never locks.
+    #[serde(default = "Lockout::default_threshold")]
+    pub threshold: u32,
+    /// Lock duration, as seconds.
+    #[serde(default = "Lockout::default_duration")]
+    pub duration: u64,
+}
+
+impl Lockout {
+    #[inline]
+    fn default_threshold() -> u32 {
+        5
+    }
+
+    #[inline]
+    fn default_duration() -> u64 {
+        15 * 60
+    }
+}
+
+impl Default for Lockout {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            threshold: Self::default_threshold(),
+            duration: Self::default_duration(),
+        }
+    }
+}
+
+/// Registration configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Registration {
+    /// Whether registering requires an invite code issued by admins.
+    #[serde(default)]
+    pub invite_only: bool,
+    /// Whether registered accounts should be approved by admins
+    /// before logging in.
+    #[serde(default)]
+    pub require_approval: bool,
+    /// Default expire duration of invite codes, as seconds.
+    #[serde(default = "Registration::default_invite_expire")]
+    pub invite_expire: u64,
+}
+
+impl Registration {
+    #[inline]
+    fn default_invite_expire() -> u64 {
+        60 * 60 * 24 * 7
+    }
+}
+
+impl Default for Registration {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            invite_only: false,
+            require_approval: false,
+            invite_expire: Self::default_invite_expire(),
+        }
+    }
+}
+
+/// Resource storage configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resource {
+    /// Directory storing resource files, for the local storage.
+    ///
+    /// Resolved under [`Server::data_path`] if relative.
+    #[serde(default = "Resource::default_path")]
+    pub path: std::path::PathBuf,
+    /// Storage backend of resource files.
+    #[serde(default)]
+    pub storage: Storage,
+    /// Directory storing partial data of chunked uploads.
+    ///
+    /// Resolved under [`Server::data_path`] if relative.
+    #[serde(default = "Resource::default_partial_path")]
+    pub partial_path: std::path::PathBuf,
+    /// Seconds an upload session lives after its last activity.
+    #[serde(default = "Resource::default_upload_session_ttl")]
+    pub upload_session_ttl: u64,
+    /// Secret signing urls of resources, signed urls
+    /// are disabled if `None`.
+    #[serde(default)]
+    pub url_secret: Option<String>,
+    /// Expire duration of signed urls, as seconds.
+    #[serde(default = "Resource::default_url_max_age")]
+    pub url_max_age: u64,
+    /// Address of a ClamAV daemon scanning uploads, in `host:port`
+    /// or a path of the unix socket. Uploads are not scanned if `None`.
+    #[serde(default)]
+    pub clamav: Option<String>,
+    /// Max bytes of resources uploaded by an account,
+    /// unlimited if this is `0`.
+    #[serde(default = "Resource::default_quota")]
+    pub quota: u64,
+    /// Hours an unreferenced resource is kept before
+    /// being garbage collected.
+    #[serde(default = "Resource::default_orphan_grace_hours")]
+    pub orphan_grace_hours: u64,
+    /// Max size of an uploaded resource, as bytes.
+    #[serde(default = "Resource::default_max_size")]
+    pub max_size: usize,
+    /// Max size of an uploaded video, as bytes.
+    #[serde(default = "Resource::default_max_video_size")]
+    pub max_video_size: usize,
+    /// Max duration of an uploaded video, as seconds.
+    #[serde(default = "Resource::default_max_video_duration")]
+    pub max_video_duration: u64,
+    /// Limits of uploaded images.
+    #[serde(default)]
+    pub image: ImageLimits,
+    /// Compression of uploaded PNG images.
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+/// Storage backend of resource files.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Storage {
+    /// Files in [`Resource::path`].
+    #[default]
+    Local,
+    /// Objects in an S3-compatible bucket.
+    S3(S3Storage),
+}
+
+/// S3-compatible storage configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct S3Storage {
+    pub bucket: String,
+    #[serde(default = "S3Storage::default_region")]
+    pub region: String,
+    /// Endpoint of a self-hosted service, e.g. MinIO.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Uses path-style instead of virtual-hosted-style urls,
+    /// which is required by most self-hosted services.
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+impl S3Storage {
+    #[inline]
+    fn default_region() -> String {
+        "us-east-1".to_owned()
+    }
+}
+
+/// Compression of uploaded PNG images, run before the images
+/// are stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Compression {
+    /// Optimizes PNG images losslessly.
+    #[serde(default = "Compression::default_lossless")]
+    pub lossless: bool,
+    /// Optimization level from `0` to `6`, higher is slower.
+    #[serde(default = "Compression::default_level")]
+    pub level: u8,
+    /// Re-encodes opaque PNG images as JPEG in this quality
+    /// from `1` to `100` if smaller, disabled if `None`.
+    #[serde(default)]
+    pub lossy_quality: Option<u8>,
+}
+
+impl Compression {
+    #[inline]
+    fn default_lossless() -> bool {
+        true
+    }
+
+    #[inline]
+    fn default_level() -> u8 {
+        2
+    }
+}
+
+impl Default for Compression {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            lossless: Self::default_lossless(),
+            level: Self::default_level(),
+            lossy_quality: None,
+        }
+    }
+}
+
+/// Limits of uploaded images.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageLimits {
+    /// Allowed formats by extension, e.g. `png`, `jpg` and `webp`.
+    ///
+    /// All supported formats are allowed if empty.
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// Max width in pixels, unlimited if this is `0`.
+    #[serde(default)]
+    pub max_width: u32,
+    /// Max height in pixels, unlimited if this is `0`.
+    #[serde(default)]
+    pub max_height: u32,
+    /// Rotates images into their EXIF orientations while
+    /// stripping metadata, so they are not displayed sideways.
+    #[serde(default = "ImageLimits::default_bake_orientation")]
+    pub bake_orientation: bool,
+    /// Resolutions of the screens, e.g. 1080x1920 for portrait screens.
+    ///
+    /// Images are not checked if empty.
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Tolerance of aspect ratios matching the presets, as
+    /// a fraction of the ratio of the preset.
+    #[serde(default = "ImageLimits::default_aspect_tolerance")]
+    pub aspect_tolerance: f32,
+    /// Rejects images mismatching the presets instead of warning.
+    #[serde(default)]
+    pub reject_mismatches: bool,
+}
+
+/// Resolution of screens.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Preset {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageLimits {
+    #[inline]
+    fn default_bake_orientation() -> bool {
+        true
+    }
+
+    #[inline]
+    fn default_aspect_tolerance() -> f32 {
+        0.02
+    }
+}
+
+impl Default for ImageLimits {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            formats: vec![],
+            max_width: 0,
+            max_height: 0,
+            bake_orientation: Self::default_bake_orientation(),
+            presets: vec![],
+            aspect_tolerance: Self::default_aspect_tolerance(),
+            reject_mismatches: false,
+        }
+    }
+}
+
+impl Resource {
+    #[inline]
+    fn default_path() -> std::path::PathBuf {
+        "resources".into()
+    }
+
+    #[inline]
+    fn default_partial_path() -> std::path::PathBuf {
+        "uploads".into()
+    }
+
+    #[inline]
+    fn default_upload_session_ttl() -> u64 {
+        600
+    }
+
+    #[inline]
+    fn default_url_max_age() -> u64 {
+        60 * 60
+    }
+
+    #[inline]
+    fn default_quota() -> u64 {
+        500 * 1024 * 1024
+    }
+
+    #[inline]
+    fn default_orphan_grace_hours() -> u64 {
+        24
+    }
+
+    #[inline]
+    fn default_max_size() -> usize {
+        10 * 1024 * 1024
+    }
+
+    #[inline]
+    fn default_max_video_size() -> usize {
+        50 * 1024 * 1024
+    }
+
+    #[inline]
+    fn default_max_video_duration() -> u64 {
+        30
+    }
+}
+
+impl Default for Resource {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            path: Self::default_path(),
+            storage: Storage::default(),
+            partial_path: Self::default_partial_path(),
+            upload_session_ttl: Self::default_upload_session_ttl(),
+            url_secret: None,
+            url_max_age: Self::default_url_max_age(),
+            clamav: None,
+            quota: Self::default_quota(),
+            orphan_grace_hours: Self::default_orphan_grace_hours(),
+            max_size: Self::default_max_size(),
+            max_video_size: Self::default_max_video_size(),
+            max_video_duration: Self::default_max_video_duration(),
+            image: ImageLimits::default(),
+            compression: Compression::default(),
+        }
+    }
+}
+
+/// Account deletion configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Deletion {
+    /// Duration before a signed out account is purged, as seconds.
+    ///
+    /// The account could be restored during this period.
+    #[serde(default = "Deletion::default_grace_period")]
+    pub grace_period: u64,
+}
+
+impl Deletion {
+    #[inline]
+    fn default_grace_period() -> u64 {
+        60 * 60 * 24 * 30
+    }
+}
+
+impl Default for Deletion {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            grace_period: Self::default_grace_period(),
+        }
+    }
+}
+
+/// Post reviewing configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Review {
+    /// Count of distinct approvers required to approve a post.
+    #[serde(default = "Review::default_approvals")]
+    pub approvals: usize,
+    /// Routes assigning posts to reviewer pools, matched in order.
+    ///
+    /// Posts matching no routes are not assigned.
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+/// A route assigning posts to a reviewer pool.
+///
+/// All present conditions should be matched.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Route {
+    /// Name of the role of reviewers in this pool.
+    pub pool: String,
+    /// Category of the post.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// A department of the publisher.
+    #[serde(default)]
+    pub department: Option<String>,
+    /// A label of the publisher, e.g. the house.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl Review {
+    #[inline]
+    fn default_approvals() -> usize {
+        1
+    }
+}
+
+impl Default for Review {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            approvals: Self::default_approvals(),
+            routes: vec![],
+        }
+    }
+}
+
+/// Post expiry reminder configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpiryReminder {
+    /// Days before the end date of a post to remind the publisher.
+    ///
+    /// Reminders are disabled if this is `0`.
+    #[serde(default = "ExpiryReminder::default_days")]
+    pub days: u32,
+}
+
+impl ExpiryReminder {
+    #[inline]
+    fn default_days() -> u32 {
+        3
+    }
+}
+
+impl Default for ExpiryReminder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            days: Self::default_days(),
+        }
+    }
+}
+
+/// Post duration limits.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostDuration {
+    /// Max days a post could be displayed, including both
+    /// the start and end date.
+    ///
+    /// Unlimited if this is `0`.
+    #[serde(default = "PostDuration::default_max_days")]
+    pub max_days: u32,
+    /// Overrides for publishers with specific permissions.
+    ///
+    /// The largest limit of matching overrides applies.
+    #[serde(default)]
+    pub overrides: Vec<PostDurationOverride>,
+}
+
+/// A post duration limit for publishers with a permission.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostDurationOverride {
+    pub permission: crate::account::Permission,
+    /// Unlimited if this is `0`.
+    pub max_days: u32,
+}
+
+impl PostDuration {
+    #[inline]
+    fn default_max_days() -> u32 {
+        7
+    }
+}
+
+impl Default for PostDuration {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_days: Self::default_max_days(),
+            overrides: vec![],
+        }
+    }
+}
+
+/// Atom feed configuration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Feed {
+    /// Public url of this server, prefixing links in the feed.
+    #[serde(default)]
+    pub url: String,
+    #[serde(default = "Feed::default_title")]
+    pub title: String,
+    /// Max count of entries in the feed.
+    #[serde(default = "Feed::default_limit")]
+    pub limit: usize,
+}
+
+impl Feed {
+    #[inline]
+    fn default_title() -> String {
+        "SMS3 Posts".to_owned()
+    }
+
+    #[inline]
+    fn default_limit() -> usize {
+        50
+    }
+}
+
+impl Default for Feed {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            title: Self::default_title(),
+            limit: Self::default_limit(),
+        }
+    }
+}
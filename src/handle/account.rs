@@ -1,26 +1,56 @@
-use std::{collections::HashSet, num::NonZeroU64};
+use std::{collections::HashSet, net::SocketAddr, num::NonZeroU64};
 
-use axum::{extract::State, Json};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect},
+    Json,
+};
 use dmds::{IoHandle, StreamExt};
 use libaccount::{tag::AsPermission, Phone, VerifyDescriptor};
 use serde::{Deserialize, Serialize};
 use sms3_backend::{
-    account::{department::Department, verify::Captcha, Permission, Tag, TagEntry, Unverified},
+    account::{
+        api_key::ApiKeyInfo,
+        department::Department,
+        invite::Invite,
+        jwt::Claims,
+        oauth, password,
+        role::Role,
+        verify::{Captcha, Device, LinkClaims, Session, VerifyVariant},
+        Account, LastLogin, NotifyPrefs, Permission, Privacy, Tag, TagEntry, Unverified,
+    },
+    audit::Action,
+    challenge::{self, Challenge},
+    config::Config,
+    resource::{self, Variant},
     Error,
 };
+use time::OffsetDateTime;
+
+use crate::{Auth, Global, Worlds};
 
-use crate::{Auth, Global};
+pub mod manage;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct SendCaptchaReq {
+    #[schema(value_type = String)]
     pub email: lettre::Address,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/send-captcha",
+    tag = "account",
+    request_body = SendCaptchaReq,
+    responses((status = 200, description = "Success")),
+)]
 pub async fn send_captcha<Io: IoHandle>(
     State(Global {
         smtp_transport,
         worlds,
         config,
+        ..
     }): State<Global<Io>>,
     Json(SendCaptchaReq { email }): Json<SendCaptchaReq>,
 ) -> Result<(), Error> {
@@ -53,62 +83,350 @@ pub async fn send_captcha<Io: IoHandle>(
     Ok(())
 }
 
-#[derive(Deserialize)]
-pub struct RegisterReq(pub VerifyDescriptor<Tag, Captcha>);
+#[utoipa::path(
+    get,
+    path = "/api/account/challenge",
+    tag = "account",
+    responses((status = 200, body = Challenge)),
+)]
+pub async fn challenge<Io: IoHandle>(
+    State(Global { challenges, .. }): State<Global<Io>>,
+) -> Json<Challenge> {
+    Json(challenges.lock().await.issue())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RegisterReq {
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub desc: VerifyDescriptor<Tag, Captcha>,
+    pub challenge: challenge::Answer,
+    /// The invite code, required in invite-only mode.
+    #[serde(default)]
+    pub invite: Option<String>,
+}
 
+#[utoipa::path(
+    post,
+    path = "/api/account/register",
+    tag = "account",
+    request_body = RegisterReq,
+    responses((status = 200, description = "Success")),
+)]
 pub async fn register<Io: IoHandle>(
-    State(Global { worlds, .. }): State<Global<Io>>,
-    Json(RegisterReq(desc)): Json<RegisterReq>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(Global {
+        worlds,
+        config,
+        limiters,
+        challenges,
+        ..
+    }): State<Global<Io>>,
+    Json(RegisterReq {
+        desc,
+        challenge,
+        invite,
+    }): Json<RegisterReq>,
 ) -> Result<(), Error> {
-    let unverified = Unverified::new(desc.email.to_owned())?;
-    worlds
-        .account
-        .try_insert(
-            libaccount::Unverified::from(
-                worlds
-                    .unverified_account
-                    .chunk_buf_of_data_or_load(&unverified)
-                    .await
-                    .map_err(|_| Error::UnverifiedAccountNotFound)?
-                    .remove(unverified.email_hash())
-                    .await
-                    .ok_or(Error::UnverifiedAccountNotFound)?,
-            )
-            .verify(desc)?
-            .into(),
+    limiters.register.check_ip_email(addr.ip(), &desc.email)?;
+    challenges.lock().await.validate(challenge)?;
+    password::validate(&config.password_policy, &desc.password)?;
+
+    let invite = if config.registration.invite_only {
+        let code = invite.ok_or(Error::InviteRequired)?;
+        let id = Invite::id_of(&code);
+        let select = worlds.invite.select(0, id).hint(id);
+        let lazy = ga!(select, id).ok_or(Error::InviteInvalid)?;
+        lazy.get().await?.validate(&code)?;
+        // Consume the invite before inserting the account,
+        // so concurrent registrations can't share it.
+        Some(lazy.destroy().await.map_err(|_| Error::InviteInvalid)?)
+    } else {
+        None
+    };
+
+    let registered: Result<(), Error> = async {
+        let unverified = Unverified::new(desc.email.to_owned())?;
+        let mut account: Account = libaccount::Unverified::from(
+            worlds
+                .unverified_account
+                .chunk_buf_of_data_or_load(&unverified)
+                .await
+                .map_err(|_| Error::UnverifiedAccountNotFound)?
+                .remove(unverified.email_hash())
+                .await
+                .ok_or(Error::UnverifiedAccountNotFound)?,
         )
-        .await
-        .map_err(|_| Error::PermissionDenied)
+        .verify(desc)?
+        .into();
+        account.set_pending_approval(config.registration.require_approval);
+        worlds
+            .account
+            .try_insert(account)
+            .await
+            .map_err(|_| Error::PermissionDenied)
+    }
+    .await;
+
+    // Give the invite back if registration failed.
+    if let (Err(_), Some(invite)) = (&registered, invite) {
+        worlds
+            .invite
+            .try_insert(invite)
+            .await
+            .map_err(|_| Error::PermissionDenied)?;
+    }
+    registered
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct LoginReq {
+    #[schema(value_type = String)]
     pub email: lettre::Address,
     pub password: String,
+    /// The TOTP code, required if two-factor authentication is enabled.
+    #[serde(default)]
+    pub totp: Option<String>,
+    /// Name of the device, shown in the sessions listing.
+    #[serde(default)]
+    pub device_name: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct LoginRes {
     pub id: u64,
     pub token: String,
     pub expire_at: Option<i64>,
 }
 
+/// Gets the user agent from request headers.
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+}
+
+/// Sends a login alert email if the login is from a new device
+/// and the account didn't opt out.
+///
+/// Failures are logged without failing the login.
+async fn alert_new_login<E>(
+    account: &Account,
+    ip: std::net::IpAddr,
+    user_agent: Option<&str>,
+    config: &Config,
+    transport: &lettre::AsyncSmtpTransport<E>,
+) where
+    E: lettre::Executor,
+    lettre::AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = lettre::transport::smtp::Error>,
+{
+    if !account.notify_prefs().on_new_login {
+        return;
+    }
+    let Ok(to) = account.email().parse() else {
+        return;
+    };
+    let body = format!(
+        "Your account was logged in from a new device.\n\nIP: {ip}\nUser agent: {}\n\n\
+        If this wasn't you, please reset your password and revoke the session.",
+        user_agent.unwrap_or("unknown"),
+    );
+    if let Err(err) = sms3_backend::mail::send(
+        &config.smtp,
+        to,
+        "New login to your SubIT account",
+        body,
+        transport,
+    )
+    .await
+    {
+        tracing::warn!("failed to send login alert: {err}");
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/login",
+    tag = "account",
+    request_body = LoginReq,
+    responses((status = 200, body = LoginRes)),
+)]
 pub async fn login<Io: IoHandle>(
-    State(Global { worlds, .. }): State<Global<Io>>,
-    Json(LoginReq { email, password }): Json<LoginReq>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    State(Global {
+        smtp_transport,
+        worlds,
+        config,
+        limiters,
+        ..
+    }): State<Global<Io>>,
+    Json(LoginReq {
+        email,
+        password,
+        totp,
+        device_name,
+    }): Json<LoginReq>,
 ) -> Result<Json<LoginRes>, Error> {
+    limiters.login.check_ip_email(addr.ip(), email.as_ref())?;
     let unverified = Unverified::new(email.to_string())?;
     let select = sa!(worlds.account, unverified.email_hash());
     let mut lazy =
         ga!(select, unverified.email_hash()).ok_or(Error::UsernameOrPasswordIncorrect)?;
-    let (token, exp_time) = lazy.get_mut().await?.login(&password).map_err(|err| {
-        if matches!(err, libaccount::Error::PasswordIncorrect) {
-            Error::UsernameOrPasswordIncorrect
-        } else {
-            err.into()
+    let account = lazy.get_mut().await?;
+    account.check_login(&password, totp.as_deref(), &config.lockout)?;
+    account.check_approval()?;
+    account.check_deletion()?;
+    let ua = user_agent(&headers);
+    if account.record_login(addr.ip(), ua.clone()) {
+        alert_new_login(account, addr.ip(), ua.as_deref(), &config, &smtp_transport).await;
+    }
+    let device = Device {
+        name: device_name,
+        user_agent: user_agent(&headers),
+        ip: Some(addr.ip()),
+    };
+    let (token, exp_time) = issue_token(&worlds, account, &config, device).await?;
+
+    Ok(axum::Json(LoginRes {
+        id: lazy.id(),
+        token,
+        expire_at: exp_time,
+    }))
+}
+
+/// Issues a login token for the account, as a JWT if enabled.
+async fn issue_token<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    account: &mut Account,
+    config: &Config,
+    device: Device,
+) -> Result<(String, Option<i64>), Error> {
+    if let Some(secret) = config.token.jwt_secret() {
+        let role_permissions = super::role_permissions(worlds, account).await?;
+        let claims = Claims::new(account, role_permissions, &config.token);
+        Ok((claims.encode(secret)?, Some(claims.exp)))
+    } else {
+        Ok(account.issue_token(device))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/account/oauth/authorize",
+    tag = "account",
+    responses((status = 303, description = "Redirects to the authorization page of the provider")),
+)]
+pub async fn oauth_authorize<Io: IoHandle>(
+    State(Global {
+        config,
+        oauth_states,
+        ..
+    }): State<Global<Io>>,
+) -> Result<Redirect, Error> {
+    let oauth = config.oauth.as_ref().ok_or(Error::OAuthDisabled)?;
+    let state = oauth_states.lock().await.generate();
+    Ok(Redirect::to(oauth::authorize_url(oauth, &state)?.as_str()))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OAuthCallbackReq {
+    pub code: String,
+    pub state: String,
+}
+
+/// Completes the OAuth2 login, and provisions the account
+/// if there is no account linked with the email.
+#[utoipa::path(
+    get,
+    path = "/api/account/oauth/callback",
+    tag = "account",
+    params(OAuthCallbackReq),
+    responses((status = 200, body = LoginRes)),
+)]
+pub async fn oauth_callback<Io: IoHandle>(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    State(Global {
+        worlds,
+        config,
+        http_client,
+        oauth_states,
+        ..
+    }): State<Global<Io>>,
+    Query(OAuthCallbackReq { code, state }): Query<OAuthCallbackReq>,
+) -> Result<Json<LoginRes>, Error> {
+    let oauth = config.oauth.as_ref().ok_or(Error::OAuthDisabled)?;
+    oauth_states.lock().await.consume(&state)?;
+    let info = oauth::exchange(oauth, &http_client, &code).await?;
+
+    let unverified = Unverified::new(info.email)?;
+    let id = unverified.email_hash();
+    let exists = {
+        let select = sa!(worlds.account, id);
+        ga!(select, id).is_some()
+    };
+    if !exists {
+        if config.registration.invite_only {
+            return Err(Error::InviteRequired);
         }
-    })?;
+        let mut account = unverified.provision(
+            info.name.unwrap_or_default(),
+            String::new(),
+            // The user should reset the password through email
+            // before logging in with a password.
+            password::generate(32),
+        )?;
+        account.set_pending_approval(config.registration.require_approval);
+        worlds
+            .account
+            .try_insert(account)
+            .await
+            .map_err(|_| Error::PermissionDenied)?;
+    }
+
+    let select = sa!(worlds.account, id);
+    let mut lazy = ga!(select, id).ok_or(Error::TargetAccountNotFound)?;
+    let account = lazy.get_mut().await?;
+    account.check_approval()?;
+    account.check_deletion()?;
+    account.record_login(addr.ip(), user_agent(&headers));
+    let device = Device {
+        name: None,
+        user_agent: user_agent(&headers),
+        ip: Some(addr.ip()),
+    };
+    let (token, expire_at) = issue_token(&worlds, account, &config, device).await?;
+    Ok(Json(LoginRes {
+        id,
+        token,
+        expire_at,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/refresh-token",
+    tag = "account",
+    responses((status = 200, body = LoginRes)),
+    security(("token" = [])),
+)]
+pub async fn refresh_token<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+) -> Result<Json<LoginRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    let (token, exp_time) = if let Some(secret) = config.token.jwt_secret() {
+        let account = lazy.get().await?;
+        let role_permissions = super::role_permissions(&worlds, account).await?;
+        let claims = Claims::new(account, role_permissions, &config.token);
+        (claims.encode(secret)?, Some(claims.exp))
+    } else {
+        lazy.get_mut().await?.refresh_token(&auth.token)?
+    };
 
     Ok(axum::Json(LoginRes {
         id: lazy.id(),
@@ -117,16 +435,243 @@ pub async fn login<Io: IoHandle>(
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct EnrollTotpRes {
+    /// Base32 encoded secret.
+    pub secret: String,
+    /// The `otpauth` uri.
+    pub uri: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/totp/enroll",
+    tag = "account",
+    responses((status = 200, body = EnrollTotpRes)),
+    security(("token" = [])),
+)]
+pub async fn enroll_totp<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<EnrollTotpRes>, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    let (secret, uri) = lazy.get_mut().await?.enroll_totp()?;
+    Ok(Json(EnrollTotpRes { secret, uri }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TotpCodeReq {
+    pub code: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/totp/confirm",
+    tag = "account",
+    request_body = TotpCodeReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn confirm_totp<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TotpCodeReq { code }): Json<TotpCodeReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.confirm_totp(&code)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/totp/disable",
+    tag = "account",
+    request_body = TotpCodeReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn disable_totp<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TotpCodeReq { code }): Json<TotpCodeReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.disable_totp(&code)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ResendVerifyReq {
+    #[schema(value_type = String)]
+    pub email: lettre::Address,
+    /// The verify session to resend, `None` means account activation.
+    #[serde(default)]
+    pub variant: Option<VerifyVariant>,
+}
+
+/// Re-sends the captcha of an unverified account or
+/// an existing verify session.
+#[utoipa::path(
+    post,
+    path = "/api/account/resend-verify",
+    tag = "account",
+    request_body = ResendVerifyReq,
+    responses((status = 200, description = "Success")),
+)]
+pub async fn resend_verify<Io: IoHandle>(
+    State(Global {
+        smtp_transport,
+        worlds,
+        config,
+        ..
+    }): State<Global<Io>>,
+    Json(ResendVerifyReq { email, variant }): Json<ResendVerifyReq>,
+) -> Result<(), Error> {
+    let unverified = Unverified::new(email.to_string())?;
+    let id = unverified.email_hash();
+    if let Some(variant) = variant {
+        let select = sa!(worlds.account, id);
+        let mut lazy = ga!(select, id).ok_or(Error::VerifySessionNotFound(variant))?;
+        return lazy
+            .get_mut()
+            .await?
+            .resend_verify(variant, &config.smtp, &smtp_transport)
+            .await;
+    }
+
+    let select = worlds.unverified_account.select(0, id).hint(id);
+    let mut lazy = ga!(select, id).ok_or(Error::UnverifiedAccountNotFound)?;
+    lazy.get_mut()
+        .await?
+        .send_captcha(&config.smtp, &smtp_transport)
+        .await
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VerifyLinkReq {
+    pub token: String,
+}
+
+/// Opens a verification link, which consumes its verify session
+/// and completes the verification, then redirects to the client page
+/// with the email and a `status` of `Activated`, `ResetPassword` or
+/// `ChangeEmail`.
+///
+/// Activated accounts and reset passwords get a generated password
+/// emailed to user.
+#[utoipa::path(
+    get,
+    path = "/api/account/verify",
+    tag = "account",
+    params(VerifyLinkReq),
+    responses((status = 303, description = "Redirects to the client page")),
+)]
+pub async fn verify_link<Io: IoHandle>(
+    State(Global {
+        worlds,
+        config,
+        smtp_transport,
+        ..
+    }): State<Global<Io>>,
+    Query(VerifyLinkReq { token }): Query<VerifyLinkReq>,
+) -> Result<Redirect, Error> {
+    let link = config
+        .smtp
+        .verify_link
+        .as_ref()
+        .ok_or(Error::PermissionDenied)?;
+    let claims = LinkClaims::decode(&token, link.secret.as_bytes())?;
+    let status = match (claims.variant, claims.account) {
+        (None, _) => {
+            if config.registration.invite_only {
+                return Err(Error::InviteRequired);
+            }
+            let unverified = Unverified::new(claims.email.to_owned())?;
+            let id = unverified.email_hash();
+            {
+                let select = worlds.unverified_account.select(0, id).hint(id);
+                let lazy = ga!(select, id).ok_or(Error::UnverifiedAccountNotFound)?;
+                lazy.get().await?.validate_captcha(claims.captcha)?;
+            }
+            // Remove the unverified account first, so the link
+            // could only be used once.
+            let (mut account, password) = worlds
+                .unverified_account
+                .chunk_buf_of_data_or_load(&unverified)
+                .await
+                .map_err(|_| Error::UnverifiedAccountNotFound)?
+                .remove(id)
+                .await
+                .ok_or(Error::UnverifiedAccountNotFound)?
+                .activate_by_link(claims.captcha, &config.password_policy)?;
+            account.set_pending_approval(config.registration.require_approval);
+            account
+                .send_password(&password, &config.smtp, &smtp_transport)
+                .await?;
+            worlds
+                .account
+                .try_insert(account)
+                .await
+                .map_err(|_| Error::PermissionDenied)?;
+            "Activated"
+        }
+        (Some(VerifyVariant::ResetPassword), Some(id)) => {
+            let select = sa!(worlds.account, id);
+            let mut lazy = ga!(select, id).ok_or(Error::PermissionDenied)?;
+            lazy.get_mut()
+                .await?
+                .reset_password_by_link(
+                    claims.captcha,
+                    &config.password_policy,
+                    &config.smtp,
+                    &smtp_transport,
+                )
+                .await?;
+            "ResetPassword"
+        }
+        (Some(VerifyVariant::ChangeEmail), Some(id)) => {
+            move_to_pending_email(&worlds, id, claims.captcha).await?;
+            "ChangeEmail"
+        }
+        (Some(variant), None) => return Err(Error::VerifySessionNotFound(variant)),
+    };
+    let mut url = reqwest::Url::parse(&link.redirect_url).map_err(|_| Error::PermissionDenied)?;
+    url.query_pairs_mut()
+        .append_pair("email", &claims.email)
+        .append_pair("status", status);
+    Ok(Redirect::to(url.as_str()))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct SendResetPasswordCaptchaReq {
+    #[schema(value_type = String)]
     pub email: lettre::Address,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/send-reset-password-captcha",
+    tag = "account",
+    request_body = SendResetPasswordCaptchaReq,
+    responses((status = 200, description = "Success")),
+)]
 pub async fn send_reset_password_captcha<Io: IoHandle>(
     State(Global {
         smtp_transport,
         worlds,
         config,
+        ..
     }): State<Global<Io>>,
     Json(SendResetPasswordCaptchaReq { email }): Json<SendResetPasswordCaptchaReq>,
 ) -> Result<(), Error> {
@@ -140,87 +685,494 @@ pub async fn send_reset_password_captcha<Io: IoHandle>(
         .map_err(From::from)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct ResetPasswordReq {
+    #[schema(value_type = String)]
     pub email: lettre::Address,
     pub captcha: Captcha,
     pub new_password: String,
+    pub challenge: challenge::Answer,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/reset-password",
+    tag = "account",
+    request_body = ResetPasswordReq,
+    responses((status = 200, description = "Success")),
+)]
 pub async fn reset_password<Io: IoHandle>(
-    State(Global { worlds, .. }): State<Global<Io>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(Global {
+        worlds,
+        config,
+        limiters,
+        challenges,
+        ..
+    }): State<Global<Io>>,
     Json(ResetPasswordReq {
         email,
         captcha,
         new_password,
+        challenge,
     }): Json<ResetPasswordReq>,
 ) -> Result<(), Error> {
+    limiters
+        .reset_password
+        .check_ip_email(addr.ip(), email.as_ref())?;
+    challenges.lock().await.validate(challenge)?;
+    password::validate(&config.password_policy, &new_password)?;
     let unverified = Unverified::new(email.to_string())?;
     let select = sa!(worlds.account, unverified.email_hash());
     let mut lazy = ga!(select, unverified.email_hash()).ok_or(Error::PermissionDenied)?;
-    lazy.get_mut().await?.reset_password(captcha, new_password)
+    lazy.get_mut()
+        .await?
+        .reset_password(captcha, new_password, &config.password_policy)
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct SelfInfoRes {
+    #[schema(value_type = String)]
     pub email: lettre::Address,
     pub name: String,
     pub school_id: String,
+    #[schema(value_type = Option<Object>)]
     pub phone: Option<Phone>,
 
     /// Duration, as seconds.
+    #[schema(value_type = Option<u64>)]
     pub token_expire_duration: Option<NonZeroU64>,
 
     pub permissions: Vec<Permission>,
+    #[schema(value_type = Vec<String>)]
     pub departments: Vec<Department>,
+    pub labels: Vec<String>,
+
+    pub last_login: Option<LastLogin>,
+    /// Id of the avatar, served through [`avatar`].
+    pub avatar: Option<u64>,
 }
 
+impl SelfInfoRes {
+    fn new(account: &Account) -> Result<Self, Error> {
+        Ok(Self {
+            email: account.email().parse()?,
+            name: account.name().to_owned(),
+            school_id: account.school_id().to_owned(),
+            phone: account.phone(),
+            token_expire_duration: account.token_expire_time(),
+            permissions: account
+                .tags()
+                .from_entry(&TagEntry::Permission)
+                .map_or(vec![], |set| {
+                    set.into_iter()
+                        .filter_map(|t| t.as_permission())
+                        .copied()
+                        .collect()
+                }),
+            departments: account
+                .tags()
+                .from_entry(&TagEntry::Department)
+                .map_or(vec![], |set| {
+                    set.into_iter()
+                        .filter_map(|t| {
+                            if let Tag::Department(d) = t {
+                                Some(d.clone())
+                            } else {
+                                None
+                            }
+                        })
+                        .collect()
+                }),
+            labels: account.labels(),
+            last_login: account.last_login().cloned(),
+            avatar: account.avatar(),
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/account/get",
+    tag = "account",
+    responses((status = 200, body = SelfInfoRes)),
+    security(("token" = [])),
+)]
 pub async fn self_info<Io: IoHandle>(
     auth: Auth,
     State(Global { worlds, .. }): State<Global<Io>>,
 ) -> Result<Json<SelfInfoRes>, Error> {
     let select = sa!(worlds.account, auth.account);
     let lazy = va!(auth, select);
-    let account = lazy.get().await?;
-    Ok(Json(SelfInfoRes {
-        email: account.email().parse()?,
-        name: account.name().to_owned(),
-        school_id: account.school_id().to_owned(),
-        phone: account.phone(),
-        token_expire_duration: account.token_expire_time(),
-        permissions: account
-            .tags()
-            .from_entry(&TagEntry::Permission)
-            .map_or(vec![], |set| {
-                set.into_iter()
-                    .filter_map(|t| t.as_permission())
-                    .copied()
-                    .collect()
-            }),
-        departments: account
-            .tags()
-            .from_entry(&TagEntry::Department)
-            .map_or(vec![], |set| {
-                set.into_iter()
-                    .filter_map(|t| {
-                        if let Tag::Department(d) = t {
-                            Some(d.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            }),
+    SelfInfoRes::new(lazy.get().await?).map(Json)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SignOutReq {
+    pub password: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SignOutRes {
+    /// The time the account will be purged, as a unix timestamp.
+    pub purge_at: i64,
+}
+
+/// Signs out the account, which will be purged after the grace period.
+#[utoipa::path(
+    post,
+    path = "/api/account/sign-out",
+    tag = "account",
+    request_body = SignOutReq,
+    responses((status = 200, body = SignOutRes)),
+    security(("token" = [])),
+)]
+pub async fn sign_out<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+    Json(SignOutReq { password }): Json<SignOutReq>,
+) -> Result<Json<SignOutRes>, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    let account = lazy.get_mut().await?;
+    if !account.password_matches(&password) {
+        return Err(Error::UsernameOrPasswordIncorrect);
+    }
+    let purge_at =
+        OffsetDateTime::now_utc() + time::Duration::seconds(config.deletion.grace_period as i64);
+    account.schedule_deletion(purge_at);
+    super::record_audit(&worlds, auth.account, Action::SignOut).await?;
+    Ok(Json(SignOutRes {
+        purge_at: purge_at.unix_timestamp(),
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RestoreReq {
+    #[schema(value_type = String)]
+    pub email: lettre::Address,
+    pub password: String,
+    #[serde(default)]
+    pub totp: Option<String>,
+}
+
+/// Restores a signed out account during the grace period.
+#[utoipa::path(
+    post,
+    path = "/api/account/restore",
+    tag = "account",
+    request_body = RestoreReq,
+    responses((status = 200, description = "Success")),
+)]
+pub async fn restore<Io: IoHandle>(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(Global {
+        worlds,
+        config,
+        limiters,
+        ..
+    }): State<Global<Io>>,
+    Json(RestoreReq {
+        email,
+        password,
+        totp,
+    }): Json<RestoreReq>,
+) -> Result<(), Error> {
+    limiters.login.check_ip_email(addr.ip(), email.as_ref())?;
+    let unverified = Unverified::new(email.to_string())?;
+    let select = sa!(worlds.account, unverified.email_hash());
+    let mut lazy =
+        ga!(select, unverified.email_hash()).ok_or(Error::UsernameOrPasswordIncorrect)?;
+    let account = lazy.get_mut().await?;
+    account.check_login(&password, totp.as_deref(), &config.lockout)?;
+    account.cancel_deletion()
+}
+
+/// Purges accounts whose deletion grace period is over.
+///
+/// This should be run periodically in background.
+pub async fn purge_deletions<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    post_index: &tokio::sync::RwLock<sms3_backend::post::search::Index>,
+    storage: &dyn resource::storage::Storage,
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let mut purged = HashSet::new();
+    let select = worlds.account.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy
+            .get()
+            .await
+            .map_or(false, |a| a.deletion().map_or(false, |t| t <= now))
+        {
+            purged.insert(lazy.destroy().await?.id());
+        }
+    }
+    if purged.is_empty() {
+        return Ok(());
+    }
+    purge_account_refs(worlds, post_index, storage, &purged).await
+}
+
+/// Removes posts, comments and resources of purged accounts.
+///
+/// Resources also uploaded by other accounts are kept for them,
+/// and those referenced elsewhere are kept as is.
+async fn purge_account_refs<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    post_index: &tokio::sync::RwLock<sms3_backend::post::search::Index>,
+    storage: &dyn resource::storage::Storage,
+    purged: &HashSet<u64>,
+) -> Result<(), Error> {
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    let mut deleted = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if purged.contains(&lazy.get().await?.creator()) {
+            deleted.push(lazy.destroy().await?);
+        }
+    }
+    for post in deleted {
+        post_index.write().await.remove(post.id());
+        super::post::comment::delete_all(worlds, post.id()).await?;
+        for id in post
+            .images()
+            .iter()
+            .chain(post.attachments())
+            .chain(post.videos())
+        {
+            super::resource::set_used(worlds, *id, false, None).await?;
+        }
+    }
+
+    let select = worlds.comment.select(0, ..);
+    let mut iter = select.iter();
+    let mut deleted: Vec<(u64, u64)> = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if purged.contains(&lazy.get().await?.author()) {
+            let c = lazy.destroy().await?;
+            deleted.push((c.post(), c.id()));
+        }
+    }
+    for (post, comment) in deleted {
+        super::post::comment::delete_replies(worlds, post, vec![comment]).await?;
+    }
+
+    let select = worlds.resource.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        let res = lazy.get().await?;
+        if !purged.iter().any(|a| res.is_uploader(*a)) {
+            continue;
+        }
+        let res = lazy.get_mut().await?;
+        let mut kept = true;
+        for account in purged {
+            if res.is_uploader(*account) {
+                kept = res.remove_uploader(*account);
+            }
+        }
+        if kept || res.is_used() {
+            continue;
+        }
+        for key in res.keys() {
+            storage.delete(&key).await?;
+        }
+        lazy.destroy().await?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/account/notify-prefs",
+    tag = "account",
+    responses((status = 200, body = NotifyPrefs)),
+    security(("token" = [])),
+)]
+pub async fn notify_prefs<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<NotifyPrefs>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    Ok(Json(lazy.get().await?.notify_prefs()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/notify-prefs",
+    tag = "account",
+    request_body = NotifyPrefs,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn set_notify_prefs<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(prefs): Json<NotifyPrefs>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.set_notify_prefs(prefs);
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ExportRes {
+    pub id: u64,
+    pub profile: SelfInfoRes,
+    pub sessions: Vec<Session>,
+    /// Posts published by the account.
+    pub posts: Vec<super::post::PostInfoRes>,
+    /// Resources uploaded by the account.
+    pub resources: Vec<super::resource::UsageResourceRes>,
+    /// Audit entries of actions done by the account.
+    pub audit: Vec<super::admin::AuditEntryRes>,
+}
+
+/// Exports all data of the account as a JSON attachment.
+///
+/// The JSON is streamed while posts, resources and audit
+/// entries are being collected.
+#[utoipa::path(
+    get,
+    path = "/api/account/export",
+    tag = "account",
+    responses((status = 200, body = ExportRes)),
+    security(("token" = [])),
+)]
+pub async fn export<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<impl IntoResponse, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    const BUF_SIZE: usize = 64 * 1024;
+
+    let (id, profile, sessions) = {
+        let select = sa!(worlds.account, auth.account);
+        let lazy = va!(auth, select);
+        let account = lazy.get().await?;
+        (
+            lazy.id(),
+            serde_json::to_string(&SelfInfoRes::new(account)?).map_err(|_| Error::Unknown)?,
+            serde_json::to_string(&account.tokens().sessions(&auth.token))
+                .map_err(|_| Error::Unknown)?,
+        )
+    };
+
+    let (mut writer, reader) = tokio::io::duplex(BUF_SIZE);
+    tokio::spawn(async move {
+        use tokio::io::AsyncWriteExt;
+
+        let result: std::io::Result<()> = async {
+            writer
+                .write_all(
+                    format!(r#"{{"id":{id},"profile":{profile},"sessions":{sessions},"posts":["#)
+                        .as_bytes(),
+                )
+                .await?;
+            let select = worlds.post.select(0, ..);
+            let mut iter = select.iter();
+            let mut first = true;
+            while let Some(Ok(lazy)) = iter.next().await {
+                let Ok(post) = lazy.get().await else {
+                    continue;
+                };
+                if !post.is_publisher(id) {
+                    continue;
+                }
+                let json = serde_json::to_vec(&super::post::PostInfoRes::new(post))?;
+                if !std::mem::take(&mut first) {
+                    writer.write_all(b",").await?;
+                }
+                writer.write_all(&json).await?;
+            }
+
+            writer.write_all(br#"],"resources":["#).await?;
+            let select = worlds.resource.select(0, ..);
+            let mut iter = select.iter();
+            let mut first = true;
+            while let Some(Ok(lazy)) = iter.next().await {
+                let Ok(res) = lazy.get().await else {
+                    continue;
+                };
+                if res.user() != id {
+                    continue;
+                }
+                let json = serde_json::to_vec(&super::resource::UsageResourceRes {
+                    id: res.id(),
+                    variant: res.variant(),
+                    size: res.size(),
+                    used: res.is_used(),
+                    created_at: res.created_at().unix_timestamp(),
+                })?;
+                if !std::mem::take(&mut first) {
+                    writer.write_all(b",").await?;
+                }
+                writer.write_all(&json).await?;
+            }
+
+            writer.write_all(br#"],"audit":["#).await?;
+            let select = worlds.audit.select(0, ..);
+            let mut iter = select.iter();
+            let mut first = true;
+            while let Some(Ok(lazy)) = iter.next().await {
+                let Ok(entry) = lazy.get().await else {
+                    continue;
+                };
+                if entry.actor() != id {
+                    continue;
+                }
+                let json = serde_json::to_vec(&super::admin::AuditEntryRes {
+                    id: entry.id(),
+                    time: entry.time().unix_timestamp(),
+                    actor: entry.actor(),
+                    action: entry.action().clone(),
+                })?;
+                if !std::mem::take(&mut first) {
+                    writer.write_all(b",").await?;
+                }
+                writer.write_all(&json).await?;
+            }
+            writer.write_all(b"]}").await?;
+            writer.shutdown().await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!("failed to export account: {err}");
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"account.json\"",
+            ),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+    ))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct ModifyReq {
     #[serde(default)]
     pub name: Option<String>,
     #[serde(default)]
     pub school_id: Option<String>,
     #[serde(default)]
+    #[schema(value_type = Option<Object>)]
     pub phone: Option<Phone>,
 
     /// Duration, as seconds.
@@ -231,26 +1183,44 @@ pub struct ModifyReq {
     pub password: Option<ModifyPasswordPart>,
 
     #[serde(default)]
+    #[schema(value_type = Option<Vec<String>>)]
     pub departments: Option<Vec<Department>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct ModifyPasswordPart {
     pub old: String,
     pub new: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/modify",
+    tag = "account",
+    request_body = ModifyReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
 pub async fn modify<Io: IoHandle>(
     auth: Auth,
-    State(Global { worlds, .. }): State<Global<Io>>,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
     Json(mut req): Json<ModifyReq>,
 ) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
     let select = sa!(worlds.account, auth.account);
     let mut lazy = va!(auth, select);
     let account = lazy.get_mut().await?;
 
+    let mut fields = vec![];
     macro_rules! modify {
-        ($($i:ident => $m:ident),*$(,)?) => { $(if let Some(v) = req.$i.take() { account.$m(v) })* };
+        ($($i:ident => $m:ident),*$(,)?) => {
+            $(if let Some(v) = req.$i.take() {
+                account.$m(v);
+                fields.push(stringify!($i).to_owned());
+            })*
+        };
     }
     modify! {
         name => set_name,
@@ -258,16 +1228,21 @@ pub async fn modify<Io: IoHandle>(
         phone => set_phone
     }
     if let Some(dur) = req.token_expire_duration.and_then(NonZeroU64::new) {
-        account.set_token_expire_time(Some(dur.get()))
+        account.set_token_expire_time(Some(dur.get()));
+        fields.push("token_expire_duration".to_owned());
     }
     if let Some(ModifyPasswordPart { old, new }) = req.password.take() {
         if account.password_matches(&old) {
-            account.set_password(new)
+            password::validate(&config.password_policy, &new)?;
+            account.check_password_reuse(&new, &config.password_policy)?;
+            account.set_password(new);
+            fields.push("password".to_owned());
         } else {
             return Err(Error::UsernameOrPasswordIncorrect);
         }
     }
     if let Some(mut departments) = req.departments.take() {
+        fields.push("departments".to_owned());
         account
             .tags_mut()
             .from_entry_mut(&TagEntry::Department)
@@ -290,27 +1265,259 @@ pub async fn modify<Io: IoHandle>(
         }
     }
 
+    if !fields.is_empty() {
+        super::record_audit(
+            &worlds,
+            auth.account,
+            Action::ModifyAccount {
+                target: auth.account,
+                fields,
+            },
+        )
+        .await?;
+    }
     Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/logout",
+    tag = "account",
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
 pub async fn logout<Io: IoHandle>(
     auth: Auth,
     State(Global { worlds, .. }): State<Global<Io>>,
 ) -> Result<(), Error> {
     let select = sa!(worlds.account, auth.account);
     let mut lazy = va!(auth, select);
-    lazy.get_mut()
-        .await?
-        .logout(&auth.token)
-        .map_err(From::from)
+    if auth.claims.is_some() {
+        // JWTs are stateless and expire by themselves.
+        return Ok(());
+    }
+    lazy.get_mut().await?.logout(&auth.token)
 }
 
-#[derive(Deserialize)]
+#[utoipa::path(
+    get,
+    path = "/api/account/sessions",
+    tag = "account",
+    responses((status = 200, body = [Session])),
+    security(("token" = [])),
+)]
+pub async fn sessions<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<Session>>, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    Ok(Json(lazy.get().await?.tokens().sessions(&auth.token)))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RevokeSessionReq {
+    pub id: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/sessions/revoke",
+    tag = "account",
+    request_body = RevokeSessionReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn revoke_session<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RevokeSessionReq { id }): Json<RevokeSessionReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.tokens_mut().revoke(id)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReqChangeEmailReq {
+    #[schema(value_type = String)]
+    pub new_email: lettre::Address,
+    /// Current password of the account.
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/req-change-email",
+    tag = "account",
+    request_body = ReqChangeEmailReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn req_change_email<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        smtp_transport,
+        worlds,
+        config,
+        ..
+    }): State<Global<Io>>,
+    Json(ReqChangeEmailReq {
+        new_email,
+        password,
+    }): Json<ReqChangeEmailReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let new_id = Unverified::new(new_email.to_string())?.email_hash();
+    let select = sa!(worlds.account, new_id);
+    if ga!(select, new_id).is_some() {
+        return Err(Error::PermissionDenied);
+    }
+
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    let account = lazy.get_mut().await?;
+    if !account.password_matches(&password) {
+        return Err(Error::UsernameOrPasswordIncorrect);
+    }
+    account
+        .req_change_email(new_email, &config.smtp, &smtp_transport)
+        .await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ChangeEmailReq {
+    pub captcha: Captcha,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ChangeEmailRes {
+    /// The new id of the account.
+    pub id: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/change-email",
+    tag = "account",
+    request_body = ChangeEmailReq,
+    responses((status = 200, body = ChangeEmailRes)),
+    security(("token" = [])),
+)]
+pub async fn change_email<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, limiters, ..
+    }): State<Global<Io>>,
+    Json(ChangeEmailReq { captcha }): Json<ChangeEmailReq>,
+) -> Result<Json<ChangeEmailRes>, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    {
+        let select = sa!(worlds.account, auth.account);
+        va!(auth, select);
+    }
+    limiters
+        .change_email
+        .check(&format!("account:{}", auth.account))?;
+    let id = move_to_pending_email(&worlds, auth.account, captcha).await?;
+    Ok(Json(ChangeEmailRes { id }))
+}
+
+/// Moves an account to the id of its pending email verified
+/// with the captcha, and returns the new id.
+async fn move_to_pending_email<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    previous: u64,
+    captcha: Captcha,
+) -> Result<u64, Error> {
+    let select = sa!(worlds.account, previous);
+    let lazy = ga!(select, previous).ok_or(Error::PermissionDenied)?;
+    let account = lazy.get().await?;
+    let pending = account
+        .pending_email()
+        .ok_or(Error::VerifySessionNotFound(VerifyVariant::ChangeEmail))?;
+    let id = Unverified::new(pending.to_owned())?.email_hash();
+    {
+        let select = sa!(worlds.account, id);
+        if ga!(select, id).is_some() {
+            return Err(Error::PermissionDenied);
+        }
+    }
+    let moved = account.change_email(captcha)?;
+
+    // Move the account to the chunk of its new id, keeping the
+    // previous one until the new one is inserted.
+    worlds
+        .account
+        .try_insert(moved)
+        .await
+        .map_err(|_| Error::PermissionDenied)?;
+    lazy.destroy().await?;
+    replace_account_refs(worlds, previous, id).await?;
+    super::record_audit(worlds, id, Action::ChangeEmail { previous }).await?;
+    Ok(id)
+}
+
+/// Replaces references to an account whose id changed
+/// in posts, comments and resources.
+///
+/// The audit log is kept as is, and linked to the new id
+/// by an [`Action::ChangeEmail`] entry.
+async fn replace_account_refs<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    from: u64,
+    to: u64,
+) -> Result<(), Error> {
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.get().await?.involves(from) {
+            lazy.get_mut().await?.replace_account(from, to);
+        }
+    }
+
+    let select = worlds.comment.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.get().await?.author() == from {
+            lazy.get_mut().await?.set_author(to);
+        }
+    }
+
+    let select = worlds.resource.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.get().await?.is_uploader(from) {
+            lazy.get_mut().await?.replace_uploader(from, to);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct SetPermissionsReq {
     pub target_account: u64,
     pub permissions: Vec<Permission>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/account/set-permissions",
+    tag = "account",
+    request_body = SetPermissionsReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
 pub async fn set_permissions<Io: IoHandle>(
     auth: Auth,
     State(Global { worlds, .. }): State<Global<Io>>,
@@ -320,7 +1527,7 @@ pub async fn set_permissions<Io: IoHandle>(
     }): Json<SetPermissionsReq>,
 ) -> Result<(), Error> {
     let select = sa!(worlds.account, auth.account);
-    let lazy = va!(auth, select => Permission::SetPermissions);
+    let lazy = va!(auth, select, worlds => Permission::SetPermissions);
     let this = lazy.get().await?;
     let permissions: HashSet<_> = permissions.into_iter().map(From::from).collect();
     let legal_perms = permissions
@@ -330,7 +1537,8 @@ pub async fn set_permissions<Io: IoHandle>(
                 .ok_or(Error::PermissionDenied)?,
         )
         .filter_map(Tag::as_permission)
-        .copied();
+        .copied()
+        .collect::<Vec<_>>();
 
     let select_t = sa!(worlds.account, target_account);
     let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
@@ -345,12 +1553,426 @@ pub async fn set_permissions<Io: IoHandle>(
                 .map_or(true, |pt| pt.is_subset(p))
         })
     {
+        let previous = target.permissions();
         target.tags_mut().initialize_permissions();
         *target
             .tags_mut()
             .from_entry_mut(&TagEntry::Permission)
-            .unwrap() = legal_perms.into_iter().map(From::from).collect();
+            .unwrap() = legal_perms.iter().copied().map(From::from).collect();
+        super::record_audit(
+            &worlds,
+            auth.account,
+            Action::SetPermissions {
+                target: target_account,
+                permissions: legal_perms,
+                previous,
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PutRoleReq {
+    pub name: String,
+    pub permissions: HashSet<Permission>,
+}
+
+/// Creates a role, or replaces permissions of an existing role.
+#[utoipa::path(
+    post,
+    path = "/api/account/put-role",
+    tag = "account",
+    request_body = PutRoleReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn put_role<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PutRoleReq { name, permissions }): Json<PutRoleReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::ManageRoles);
+    let this = lazy.get().await?;
+
+    // Roles could only contain permissions the account could grant.
+    let required: Vec<_> = permissions.iter().copied().collect();
+    if !super::permitted(&worlds, &auth, this, &required).await? {
+        return Err(Error::PermissionDenied);
+    }
+    let action = Action::PutRole {
+        name: name.clone(),
+        permissions: permissions.iter().copied().collect(),
+    };
+    let id = Role::id_of(&name);
+    let select = worlds.role.select(0, id).hint(id);
+    if let Some(mut lazy) = ga!(select, id) {
+        let role = lazy.get_mut().await?;
+        let previous: Vec<_> = role.permissions().iter().copied().collect();
+        if !super::permitted(&worlds, &auth, this, &previous).await? {
+            return Err(Error::PermissionDenied);
+        }
+        role.set_permissions(permissions);
+    } else {
+        worlds
+            .role
+            .try_insert(Role::new(name, permissions))
+            .await
+            .map_err(|_| Error::PermissionDenied)?;
+    }
+    super::record_audit(&worlds, auth.account, action).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SetRolesReq {
+    pub target_account: u64,
+    /// Names of the roles.
+    pub roles: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/set-roles",
+    tag = "account",
+    request_body = SetRolesReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn set_roles<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(SetRolesReq {
+        target_account,
+        roles,
+    }): Json<SetRolesReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::ManageRoles);
+    let this = lazy.get().await?;
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    let previous = lazy_t.get().await?.role_names();
+
+    // Assigned and removed roles could only contain permissions
+    // the account could grant directly.
+    let mut valid = Vec::with_capacity(roles.len());
+    for name in roles
+        .iter()
+        .chain(previous.iter().filter(|r| !roles.contains(r)))
+    {
+        let id = Role::id_of(name);
+        let select = worlds.role.select(0, id).hint(id);
+        let Some(lazy_r) = ga!(select, id) else {
+            if roles.contains(name) {
+                return Err(Error::RoleNotFound(name.to_owned()));
+            }
+            // Removing a deleted role.
+            continue;
+        };
+        let permissions: Vec<_> = lazy_r.get().await?.permissions().iter().copied().collect();
+        if !super::permitted(&worlds, &auth, this, &permissions).await? {
+            return Err(Error::PermissionDenied);
+        }
+        if roles.contains(name) {
+            valid.push(name.to_owned());
+        }
+    }
+
+    let target = lazy_t.get_mut().await?;
+    if let Some(set) = target.tags_mut().from_entry_mut(&TagEntry::Role) {
+        set.clear()
+    }
+    for name in valid.iter() {
+        target.tags_mut().insert(Tag::Role(name.to_owned()));
+    }
+    super::record_audit(
+        &worlds,
+        auth.account,
+        Action::SetRoles {
+            target: target_account,
+            roles: valid,
+            previous,
+        },
+    )
+    .await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SetAvatarReq {
+    /// Id of an uploaded image resource, `None` removes the avatar.
+    pub resource: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/set-avatar",
+    tag = "account",
+    request_body = SetAvatarReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn set_avatar<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(SetAvatarReq { resource }): Json<SetAvatarReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    if let Some(id) = resource {
+        super::resource::check_owners(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[id],
+            |user| user == auth.account,
+            Permission::ManageAccounts,
+        )
+        .await?;
+        super::resource::set_used(&worlds, id, true, Some(Variant::Image)).await?;
+    }
+    let previous = lazy.get_mut().await?.set_avatar(resource);
+    if let Some(id) = previous {
+        super::resource::set_used(&worlds, id, false, None).await?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AvatarReq {
+    /// Side length of the avatar in pixels.
+    #[serde(default)]
+    pub size: Option<u32>,
+}
+
+/// Serves the avatar of an account as a square PNG.
+///
+/// The size is rounded up to one of [`resource::image::AVATAR_SIDES`],
+/// and generated avatars are cached in the storage.
+#[utoipa::path(
+    get,
+    path = "/api/account/avatar/{id}",
+    tag = "account",
+    params(("id" = u64, Path), AvatarReq),
+    responses((status = 200, description = "Avatar thumbnail", content_type = "image/png")),
+)]
+pub async fn avatar<Io: IoHandle>(
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Path(id): Path<u64>,
+    Query(AvatarReq { size }): Query<AvatarReq>,
+) -> Result<impl IntoResponse, Error> {
+    const DEFAULT_SIZE: u32 = 128;
+
+    let select = sa!(worlds.account, id);
+    let lazy = ga!(select, id).ok_or(Error::TargetAccountNotFound)?;
+    let avatar = lazy
+        .get()
+        .await?
+        .avatar()
+        .ok_or(Error::ResourceNotFound(id))?;
+    let sides = resource::image::AVATAR_SIDES;
+    let size = size.unwrap_or(DEFAULT_SIZE);
+    let side = sides
+        .into_iter()
+        .find(|s| *s >= size)
+        .unwrap_or(sides[sides.len() - 1]);
+    let key = resource::image::avatar_key(avatar, side);
+    if let Some(png) = storage.get(&key).await? {
+        return Ok(([(header::CONTENT_TYPE, "image/png")], png));
     }
 
+    let data = storage
+        .get(&resource::key_of(avatar))
+        .await?
+        .ok_or(Error::ResourceNotFound(avatar))?;
+    let png = tokio::task::spawn_blocking(move || resource::image::thumbnail(&data, side))
+        .await
+        .map_err(|_| Error::Unknown)??;
+    storage.put(&key, &png).await?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateApiKeyReq {
+    pub name: String,
+    /// Permissions of the key, which should be held by the account.
+    pub permissions: HashSet<Permission>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CreateApiKeyRes {
+    pub id: u64,
+    /// The key, which is only shown once.
+    pub key: String,
+}
+
+/// Creates an API key, which is authorized as `{account}:{key}`
+/// just like login tokens.
+#[utoipa::path(
+    post,
+    path = "/api/account/api-keys",
+    tag = "account",
+    request_body = CreateApiKeyReq,
+    responses((status = 200, body = CreateApiKeyRes)),
+    security(("token" = [])),
+)]
+pub async fn create_api_key<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(CreateApiKeyReq { name, permissions }): Json<CreateApiKeyReq>,
+) -> Result<Json<CreateApiKeyRes>, Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    let account = lazy.get().await?;
+    if !permissions.iter().all(|p| account.has_permission(*p)) {
+        let granted = super::role_permissions(&worlds, account).await?;
+        if !permissions
+            .iter()
+            .all(|p| account.has_permission(*p) || granted.iter().any(|g| g.implies(*p)))
+        {
+            return Err(Error::PermissionDenied);
+        }
+    }
+
+    let (id, key) = lazy
+        .get_mut()
+        .await?
+        .api_keys_mut()
+        .create(name, permissions);
+    Ok(Json(CreateApiKeyRes { id, key }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/account/api-keys",
+    tag = "account",
+    responses((status = 200, body = [ApiKeyInfo])),
+    security(("token" = [])),
+)]
+pub async fn api_keys<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<ApiKeyInfo>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    Ok(Json(lazy.get().await?.api_keys().list()))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RevokeApiKeyReq {
+    pub id: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/revoke-api-key",
+    tag = "account",
+    request_body = RevokeApiKeyReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn revoke_api_key<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RevokeApiKeyReq { id }): Json<RevokeApiKeyReq>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.api_keys_mut().revoke(id)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProfileRes {
+    pub name: String,
+    /// Labels of the account, e.g. its house.
+    pub house: Vec<String>,
+    /// Departments of the account.
+    #[schema(value_type = Vec<String>)]
+    pub organization: Vec<Department>,
+    pub avatar: Option<u64>,
+    /// Present if the account made it public.
+    #[schema(value_type = Option<Object>)]
+    pub phone: Option<Phone>,
+    /// Present if the account made it public.
+    pub school_id: Option<String>,
+}
+
+/// Gets the public profile of an account.
+///
+/// The name, house and organization are always shown, while the
+/// phone and school id are shown as the privacy of the account.
+#[utoipa::path(
+    get,
+    path = "/api/account/profile/{id}",
+    tag = "account",
+    params(("id" = u64, Path)),
+    responses((status = 200, body = ProfileRes)),
+    security(("token" = [])),
+)]
+pub async fn profile<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Path(id): Path<u64>,
+) -> Result<Json<ProfileRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let select_t = sa!(worlds.account, id);
+    let lazy_t = ga!(select_t, id).ok_or(Error::TargetAccountNotFound)?;
+    let account = lazy_t.get().await?;
+    let privacy = account.privacy();
+    let SelfInfoRes {
+        name,
+        school_id,
+        phone,
+        departments,
+        labels,
+        avatar,
+        ..
+    } = SelfInfoRes::new(account)?;
+    Ok(Json(ProfileRes {
+        name,
+        house: labels,
+        organization: departments,
+        avatar,
+        phone: phone.filter(|_| privacy.show_phone),
+        school_id: Some(school_id).filter(|_| privacy.show_school_id),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/set-privacy",
+    tag = "account",
+    request_body = Privacy,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn set_privacy<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(privacy): Json<Privacy>,
+) -> Result<(), Error> {
+    if auth.is_api_key() {
+        return Err(Error::PermissionDenied);
+    }
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    lazy.get_mut().await?.set_privacy(privacy);
     Ok(())
 }
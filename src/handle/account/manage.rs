@@ -0,0 +1,968 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::{
+        department::Department, invite::Invite, password, Grant, Permission, Suspension, Tag,
+        TagEntry, Unverified,
+    },
+    audit::Action,
+    Error,
+};
+use time::OffsetDateTime;
+
+use crate::{handle::admin::AuditEntryRes, Auth, Global};
+
+use super::SelfInfoRes;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TargetAccountReq {
+    pub target_account: u64,
+}
+
+/// Invalidates all tokens of the target account,
+/// including issued JWTs.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/force-logout",
+    tag = "account",
+    request_body = TargetAccountReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn force_logout<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TargetAccountReq { target_account }): Json<TargetAccountReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    let target = lazy_t.get_mut().await?;
+    target.tokens_mut().clear();
+    target.bump_token_epoch();
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::ForceLogout {
+            target: target_account,
+        },
+    )
+    .await
+}
+
+/// Invalidates the password and all tokens of the target account,
+/// and emails the user a reset password captcha.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/reset-password",
+    tag = "account",
+    request_body = TargetAccountReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn reset_password<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        config,
+        smtp_transport,
+        ..
+    }): State<Global<Io>>,
+    Json(TargetAccountReq { target_account }): Json<TargetAccountReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    lazy_t
+        .get_mut()
+        .await?
+        .force_reset_password(&config.smtp, &smtp_transport)
+        .await?;
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::ForceResetPassword {
+            target: target_account,
+        },
+    )
+    .await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ImpersonateRes {
+    pub token: String,
+    pub expire_at: Option<i64>,
+}
+
+/// Issues a short-lived token of the target account, with every
+/// request authorized by it recorded in the audit log.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/impersonate",
+    tag = "account",
+    request_body = TargetAccountReq,
+    responses((status = 200, body = ImpersonateRes)),
+    security(("token" = [])),
+)]
+pub async fn impersonate<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TargetAccountReq { target_account }): Json<TargetAccountReq>,
+) -> Result<Json<ImpersonateRes>, Error> {
+    const EXPIRE_DUR: time::Duration = time::Duration::minutes(15);
+
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    let (token, expire_at) = lazy_t
+        .get_mut()
+        .await?
+        .tokens_mut()
+        .new_impersonation_token(EXPIRE_DUR, auth.account);
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::Impersonate {
+            target: target_account,
+        },
+    )
+    .await?;
+    Ok(Json(ImpersonateRes {
+        token,
+        expire_at: expire_at.map(OffsetDateTime::unix_timestamp),
+    }))
+}
+
+#[derive(Deserialize)]
+struct ImportRow {
+    email: String,
+    name: String,
+    school_id: String,
+    /// Label of the house, e.g. for house-scoped posts.
+    #[serde(default)]
+    house: String,
+    /// Permissions separated by `;`.
+    #[serde(default)]
+    permissions: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ImportRowRes {
+    /// Line number of the row, where the header is line 1.
+    pub line: u64,
+    pub email: Option<String>,
+    pub id: Option<u64>,
+    /// The generated initial password.
+    pub password: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Creates verified accounts from a CSV with header
+/// `email,name,school_id,house,permissions`.
+///
+/// Initial passwords are generated to meet the password policy.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/import",
+    tag = "account",
+    request_body(content = String, content_type = "text/csv"),
+    responses((status = 200, body = [ImportRowRes])),
+    security(("token" = [])),
+)]
+pub async fn import<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+    body: String,
+) -> Result<Json<Vec<ImportRowRes>>, Error> {
+    const PASSWORD_LEN: usize = 12;
+
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::ManageAccounts);
+    let this = lazy.get().await?;
+
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let mut results = vec![];
+    for (i, record) in reader.deserialize::<ImportRow>().enumerate() {
+        let mut res = ImportRowRes {
+            line: i as u64 + 2,
+            email: None,
+            id: None,
+            password: None,
+            error: None,
+        };
+        let result: Result<(), Error> = async {
+            let row = record?;
+            res.email = Some(row.email.clone());
+
+            let mut permissions = vec![];
+            for p in row
+                .permissions
+                .split(';')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+            {
+                let p = Permission::deserialize(serde::de::IntoDeserializer::<
+                    serde::de::value::Error,
+                >::into_deserializer(p))
+                .map_err(|_| Error::PermissionDenied)?;
+                if !crate::handle::permitted(&worlds, &auth, this, &[p]).await? {
+                    return Err(Error::PermissionDenied);
+                }
+                permissions.push(p);
+            }
+
+            let unverified = Unverified::new(row.email)?;
+            let id = unverified.email_hash();
+            let password = password::generate_valid(&config.password_policy, PASSWORD_LEN);
+            let mut account = unverified.provision(row.name, row.school_id, password.clone())?;
+            account.tags_mut().initialize_permissions();
+            if let Some(set) = account.tags_mut().from_entry_mut(&TagEntry::Permission) {
+                set.clear()
+            }
+            for p in permissions.iter().copied() {
+                account.tags_mut().insert(Tag::Permission(p));
+            }
+            let house = row.house.trim();
+            if !house.is_empty() {
+                account.set_labels([house.to_owned()]);
+            }
+            worlds
+                .account
+                .try_insert(account)
+                .await
+                .map_err(|_| Error::PermissionDenied)?;
+            crate::handle::record_audit(
+                &worlds,
+                auth.account,
+                Action::ImportAccount {
+                    target: id,
+                    permissions,
+                },
+            )
+            .await?;
+            res.id = Some(id);
+            res.password = Some(password);
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            res.error = Some(err.to_string());
+        }
+        results.push(res);
+    }
+    Ok(Json(results))
+}
+
+#[derive(Deserialize, Default, utoipa::ToSchema)]
+pub struct SearchReq {
+    /// Substring of the name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Substring of the email.
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub department: Option<Department>,
+    #[serde(default)]
+    pub permission: Option<Permission>,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AccountRes {
+    pub id: u64,
+    #[serde(flatten)]
+    pub info: SelfInfoRes,
+}
+
+/// Searches accounts matching all given filters.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/search",
+    tag = "account",
+    request_body = SearchReq,
+    responses((status = 200, body = [AccountRes])),
+    security(("token" = [])),
+)]
+pub async fn search<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(req): Json<SearchReq>,
+) -> Result<Json<Vec<AccountRes>>, Error> {
+    const MAX_RESULTS: usize = 100;
+
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ViewAccounts);
+
+    let name = req.name.map(|n| n.to_lowercase());
+    let email = req.email.map(|e| e.to_lowercase());
+    let department = req.department.map(Tag::Department);
+
+    let select = worlds.account.select(0, ..);
+    let mut iter = select.iter();
+    let mut results = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(account) = lazy.get().await else {
+            continue;
+        };
+        if name
+            .as_ref()
+            .map_or(true, |n| account.name().to_lowercase().contains(n))
+            && email
+                .as_ref()
+                .map_or(true, |e| account.email().to_lowercase().contains(e))
+            && department.as_ref().map_or(true, |d| {
+                account
+                    .tags()
+                    .from_entry(&TagEntry::Department)
+                    .map_or(false, |set| set.contains(d))
+            })
+            && req.permission.map_or(true, |p| account.has_permission(p))
+            && req.label.as_ref().map_or(true, |l| account.has_label(l))
+        {
+            results.push(AccountRes {
+                id: lazy.id(),
+                info: SelfInfoRes::new(account)?,
+            });
+            if results.len() >= MAX_RESULTS {
+                break;
+            }
+        }
+    }
+    Ok(Json(results))
+}
+
+/// Sort key of account listing.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListSort {
+    #[default]
+    RegisteredAt,
+    Name,
+    SchoolId,
+}
+
+/// Value of a [`ListSort`] key.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum SortValue {
+    Time(i64),
+    Text(String),
+}
+
+/// Cursor of account listing, pointing to the last returned account.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+pub struct ListCursor {
+    pub value: SortValue,
+    pub id: u64,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ListReq {
+    #[serde(default)]
+    pub sort: ListSort,
+    #[serde(default)]
+    pub desc: bool,
+    /// Only returns accounts after this cursor.
+    #[serde(default)]
+    pub after: Option<ListCursor>,
+    #[serde(default = "ListReq::default_limit")]
+    pub limit: usize,
+}
+
+impl ListReq {
+    const MAX_LIMIT: usize = 100;
+
+    #[inline]
+    fn default_limit() -> usize {
+        20
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ListRes {
+    pub accounts: Vec<AccountRes>,
+    /// Cursor for the next page, `None` if there are no more accounts.
+    pub next: Option<ListCursor>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/list",
+    tag = "account",
+    request_body = ListReq,
+    responses((status = 200, body = ListRes)),
+    security(("token" = [])),
+)]
+pub async fn list<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(ListReq {
+        sort,
+        desc,
+        after,
+        limit,
+    }): Json<ListReq>,
+) -> Result<Json<ListRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ViewAccounts);
+    let limit = limit.clamp(1, ListReq::MAX_LIMIT);
+
+    let select = worlds.account.select(0, ..);
+    let mut iter = select.iter();
+    let mut accounts = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(account) = lazy.get().await else {
+            continue;
+        };
+        let cursor = ListCursor {
+            value: match sort {
+                ListSort::RegisteredAt => {
+                    SortValue::Time(account.registered_at().map_or(0, |t| t.unix_timestamp()))
+                }
+                ListSort::Name => SortValue::Text(account.name().to_owned()),
+                ListSort::SchoolId => SortValue::Text(account.school_id().to_owned()),
+            },
+            id: lazy.id(),
+        };
+        if after
+            .as_ref()
+            .map_or(true, |a| if desc { cursor < *a } else { cursor > *a })
+        {
+            accounts.push((
+                cursor,
+                AccountRes {
+                    id: lazy.id(),
+                    info: SelfInfoRes::new(account)?,
+                },
+            ));
+        }
+    }
+    accounts.sort_unstable_by(|a, b| if desc { b.0.cmp(&a.0) } else { a.0.cmp(&b.0) });
+    accounts.truncate(limit + 1);
+    let next = if accounts.len() > limit {
+        accounts.pop();
+        accounts.last().map(|(c, _)| c.clone())
+    } else {
+        None
+    };
+    Ok(Json(ListRes {
+        accounts: accounts.into_iter().map(|(_, a)| a).collect(),
+        next,
+    }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SuspendReq {
+    pub target_account: u64,
+    pub reason: String,
+    /// End time of the suspension as a unix timestamp,
+    /// `None` means forever.
+    #[serde(default)]
+    pub until: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/suspend",
+    tag = "account",
+    request_body = SuspendReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn suspend<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(SuspendReq {
+        target_account,
+        reason,
+        until,
+    }): Json<SuspendReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+    let until = until
+        .map(OffsetDateTime::from_unix_timestamp)
+        .transpose()
+        .map_err(|_| Error::InvalidTimestamp)?;
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    lazy_t.get_mut().await?.set_suspension(Some(Suspension {
+        reason: reason.clone(),
+        until,
+    }));
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::Suspend {
+            target: target_account,
+            reason,
+        },
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/unsuspend",
+    tag = "account",
+    request_body = TargetAccountReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn unsuspend<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TargetAccountReq { target_account }): Json<TargetAccountReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    lazy_t.get_mut().await?.set_suspension(None);
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::Unsuspend {
+            target: target_account,
+        },
+    )
+    .await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct IssueInvitesReq {
+    /// Count of invites to issue.
+    pub count: usize,
+    /// Expire duration of the invites as seconds,
+    /// or the configured default if not present.
+    #[serde(default)]
+    pub expire: Option<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct InviteRes {
+    pub code: String,
+    pub issuer: u64,
+    pub created_at: i64,
+    pub expire_at: i64,
+}
+
+impl InviteRes {
+    fn new(invite: &Invite) -> Self {
+        Self {
+            code: invite.code().to_owned(),
+            issuer: invite.issuer(),
+            created_at: invite.created_at().unix_timestamp(),
+            expire_at: invite.expire_at().unix_timestamp(),
+        }
+    }
+}
+
+/// Issues single-use invite codes for registering.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/invites",
+    tag = "account",
+    request_body = IssueInvitesReq,
+    responses((status = 200, body = [InviteRes])),
+    security(("token" = [])),
+)]
+pub async fn issue_invites<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+    Json(IssueInvitesReq { count, expire }): Json<IssueInvitesReq>,
+) -> Result<Json<Vec<InviteRes>>, Error> {
+    const MAX_COUNT: usize = 100;
+
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let expire =
+        time::Duration::seconds(expire.unwrap_or(config.registration.invite_expire) as i64);
+    let mut res = Vec::with_capacity(count.min(MAX_COUNT));
+    for _ in 0..count.min(MAX_COUNT) {
+        let invite = Invite::new(auth.account, expire);
+        res.push(InviteRes::new(&invite));
+        worlds.invite.insert(invite).await?;
+    }
+    Ok(Json(res))
+}
+
+/// Lists all unexpired invites.
+#[utoipa::path(
+    get,
+    path = "/api/account/manage/invites",
+    tag = "account",
+    responses((status = 200, body = [InviteRes])),
+    security(("token" = [])),
+)]
+pub async fn invites<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<InviteRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select = worlds.invite.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let invite = lazy.get().await?;
+        if !invite.is_expired() {
+            res.push(InviteRes::new(invite));
+        }
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RevokeInviteReq {
+    pub code: String,
+}
+
+/// Revokes an invite before it's used.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/revoke-invite",
+    tag = "account",
+    request_body = RevokeInviteReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn revoke_invite<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RevokeInviteReq { code }): Json<RevokeInviteReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let id = Invite::id_of(&code);
+    let select = worlds.invite.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or(Error::InviteInvalid)?;
+    lazy.destroy().await?;
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DormantReq {
+    /// Accounts without logging in for these days are dormant.
+    pub days: u32,
+}
+
+/// Lists dormant accounts, sorted by last active time ascending.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/dormant",
+    tag = "account",
+    request_body = DormantReq,
+    responses((status = 200, body = [AccountRes])),
+    security(("token" = [])),
+)]
+pub async fn dormant<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(DormantReq { days }): Json<DormantReq>,
+) -> Result<Json<Vec<AccountRes>>, Error> {
+    const MAX_RESULTS: usize = 100;
+
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ViewAccounts);
+    let cutoff = OffsetDateTime::now_utc() - time::Duration::days(days as i64);
+
+    let select = worlds.account.select(0, ..);
+    let mut iter = select.iter();
+    let mut results = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(account) = lazy.get().await else {
+            continue;
+        };
+        let last_active = account.last_active();
+        if last_active.map_or(true, |t| t < cutoff) {
+            results.push((
+                last_active,
+                AccountRes {
+                    id: lazy.id(),
+                    info: SelfInfoRes::new(account)?,
+                },
+            ));
+        }
+    }
+    results.sort_by_key(|(t, _)| *t);
+    Ok(Json(
+        results
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, r)| r)
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct GrantReq {
+    pub target_account: u64,
+    pub permission: Permission,
+    /// Expiration time of the grant as a unix timestamp.
+    pub until: i64,
+}
+
+/// Grants a permission to the target account until a time.
+///
+/// The granter should have the permission itself.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/grant",
+    tag = "account",
+    request_body = GrantReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn grant<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(GrantReq {
+        target_account,
+        permission,
+        until,
+    }): Json<GrantReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::SetPermissions, permission);
+    let until = OffsetDateTime::from_unix_timestamp(until).map_err(|_| Error::InvalidTimestamp)?;
+    if until <= OffsetDateTime::now_utc() {
+        return Err(Error::InvalidTimestamp);
+    }
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    lazy_t.get_mut().await?.grant(Grant {
+        permission,
+        until,
+        granter: auth.account,
+    });
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::GrantPermission {
+            target: target_account,
+            permission,
+            until,
+        },
+    )
+    .await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RevokeGrantReq {
+    pub target_account: u64,
+    pub permission: Permission,
+}
+
+/// Revokes a timed grant of the target account.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/revoke-grant",
+    tag = "account",
+    request_body = RevokeGrantReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn revoke_grant<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RevokeGrantReq {
+        target_account,
+        permission,
+    }): Json<RevokeGrantReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::SetPermissions);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    if !lazy_t.get_mut().await?.revoke_grant(permission) {
+        return Ok(());
+    }
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::RevokeGrant {
+            target: target_account,
+            permission,
+        },
+    )
+    .await
+}
+
+/// Lists permission and role changes of the target account,
+/// sorted by time descending.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/permission-history",
+    tag = "account",
+    request_body = TargetAccountReq,
+    responses((status = 200, body = [AuditEntryRes])),
+    security(("token" = [])),
+)]
+pub async fn permission_history<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(TargetAccountReq { target_account }): Json<TargetAccountReq>,
+) -> Result<Json<Vec<AuditEntryRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select = worlds.audit.select(0, ..);
+    let mut iter = select.iter();
+    let mut entries = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(entry) = lazy.get().await else {
+            continue;
+        };
+        let action = entry.action();
+        if action.is_permission_change() && action.target() == Some(target_account) {
+            entries.push(AuditEntryRes {
+                id: entry.id(),
+                time: entry.time().unix_timestamp(),
+                actor: entry.actor(),
+                action: action.clone(),
+            });
+        }
+    }
+    entries.sort_unstable_by(|a, b| b.time.cmp(&a.time).then(b.id.cmp(&a.id)));
+    Ok(Json(entries))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SetLabelsReq {
+    pub target_account: u64,
+    pub labels: Vec<String>,
+}
+
+/// Replaces labels of the target account.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/set-labels",
+    tag = "account",
+    request_body = SetLabelsReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn set_labels<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(SetLabelsReq {
+        target_account,
+        labels,
+    }): Json<SetLabelsReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    lazy_t.get_mut().await?.set_labels(labels.iter().cloned());
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::SetLabels {
+            target: target_account,
+            labels,
+        },
+    )
+    .await
+}
+
+/// Lists accounts pending approval, sorted by registration time.
+#[utoipa::path(
+    get,
+    path = "/api/account/manage/approval-queue",
+    tag = "account",
+    responses((status = 200, body = [AccountRes])),
+    security(("token" = [])),
+)]
+pub async fn approval_queue<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<AccountRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select = worlds.account.select(0, ..);
+    let mut iter = select.iter();
+    let mut results = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(account) = lazy.get().await else {
+            continue;
+        };
+        if account.is_pending_approval() {
+            results.push((
+                account.registered_at(),
+                AccountRes {
+                    id: lazy.id(),
+                    info: SelfInfoRes::new(account)?,
+                },
+            ));
+        }
+    }
+    results.sort_by_key(|(t, _)| *t);
+    Ok(Json(results.into_iter().map(|(_, r)| r).collect()))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ApproveReq {
+    pub target_account: u64,
+    /// Whether to approve the account, or reject and remove it.
+    pub approve: bool,
+}
+
+/// Approves or rejects an account pending approval.
+#[utoipa::path(
+    post,
+    path = "/api/account/manage/approve",
+    tag = "account",
+    request_body = ApproveReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn approve<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(ApproveReq {
+        target_account,
+        approve,
+    }): Json<ApproveReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageAccounts);
+
+    let select_t = sa!(worlds.account, target_account);
+    let mut lazy_t = ga!(select_t, target_account).ok_or(Error::TargetAccountNotFound)?;
+    if !lazy_t.get().await?.is_pending_approval() {
+        return Err(Error::TargetAccountNotFound);
+    }
+    let action = if approve {
+        lazy_t.get_mut().await?.set_pending_approval(false);
+        Action::ApproveAccount {
+            target: target_account,
+        }
+    } else {
+        lazy_t.destroy().await?;
+        Action::RejectAccount {
+            target: target_account,
+        }
+    };
+    crate::handle::record_audit(&worlds, auth.account, action).await
+}
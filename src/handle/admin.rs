@@ -0,0 +1,143 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::Permission,
+    audit::{Action, Entry},
+    Error,
+};
+
+use crate::{Auth, Global};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AuditReq {
+    /// Only returns entries before this cursor.
+    #[serde(default)]
+    pub before: Option<AuditCursor>,
+    #[serde(default = "AuditReq::default_limit")]
+    pub limit: usize,
+}
+
+impl AuditReq {
+    const MAX_LIMIT: usize = 200;
+
+    #[inline]
+    fn default_limit() -> usize {
+        50
+    }
+}
+
+/// Cursor of audit entries, ordered by time and id descending.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+pub struct AuditCursor {
+    pub time: i64,
+    pub id: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AuditEntryRes {
+    pub id: u64,
+    pub time: i64,
+    pub actor: u64,
+    pub action: Action,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AuditRes {
+    pub entries: Vec<AuditEntryRes>,
+    /// Cursor for the next page, `None` if there are no more entries.
+    pub next: Option<AuditCursor>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/audit",
+    tag = "admin",
+    request_body = AuditReq,
+    responses((status = 200, body = AuditRes)),
+    security(("token" = [])),
+)]
+pub async fn audit<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(AuditReq { before, limit }): Json<AuditReq>,
+) -> Result<Json<AuditRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let limit = limit.clamp(1, AuditReq::MAX_LIMIT);
+    let end = before.map_or(u64::MAX, |c| c.time.max(0) as u64);
+    let select = worlds.audit.select(1, ..=end);
+    let mut iter = select.iter();
+    let mut entries = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if let Ok(entry) = lazy.get().await {
+            let cursor = cursor_of(entry);
+            if before.map_or(true, |b| cursor < b) {
+                entries.push(AuditEntryRes {
+                    id: cursor.id,
+                    time: cursor.time,
+                    actor: entry.actor(),
+                    action: entry.action().clone(),
+                });
+            }
+        }
+    }
+    entries.sort_unstable_by(|a, b| cursor_of_res(b).cmp(&cursor_of_res(a)));
+    entries.truncate(limit + 1);
+    let next = (entries.len() > limit).then(|| {
+        entries.pop();
+        cursor_of_res(&entries[limit - 1])
+    });
+    Ok(Json(AuditRes { entries, next }))
+}
+
+#[inline]
+fn cursor_of(entry: &Entry) -> AuditCursor {
+    AuditCursor {
+        time: entry.time().unix_timestamp(),
+        id: entry.id(),
+    }
+}
+
+#[inline]
+fn cursor_of_res(entry: &AuditEntryRes) -> AuditCursor {
+    AuditCursor {
+        time: entry.time,
+        id: entry.id,
+    }
+}
+
+/// Garbage collects unreferenced resources immediately,
+/// see [`super::resource::collect_orphans`].
+#[utoipa::path(
+    post,
+    path = "/api/admin/collect-resources",
+    tag = "admin",
+    responses((status = 200, body = super::resource::CollectRes)),
+    security(("token" = [])),
+)]
+pub async fn collect_resources<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        config,
+        storage,
+        ..
+    }): State<Global<Io>>,
+) -> Result<Json<super::resource::CollectRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let collected = super::resource::collect_orphans(&worlds, &config, &*storage).await?;
+    super::record_audit(
+        &worlds,
+        auth.account,
+        Action::CollectResources {
+            resources: collected.resources,
+            bytes: collected.bytes,
+        },
+    )
+    .await?;
+    Ok(Json(collected))
+}
@@ -0,0 +1,309 @@
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// OpenAPI specification of all the handlers.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "SMS3 Backend",
+        description = "Errors are returned as JSON objects with an `error` message \
+            and the `request_id` of the failed request."
+    ),
+    paths(
+        super::account::send_captcha,
+        super::account::challenge,
+        super::account::register,
+        super::account::login,
+        super::account::oauth_authorize,
+        super::account::oauth_callback,
+        super::account::refresh_token,
+        super::account::enroll_totp,
+        super::account::confirm_totp,
+        super::account::disable_totp,
+        super::account::resend_verify,
+        super::account::verify_link,
+        super::account::send_reset_password_captcha,
+        super::account::reset_password,
+        super::account::self_info,
+        super::account::sign_out,
+        super::account::restore,
+        super::account::notify_prefs,
+        super::account::set_notify_prefs,
+        super::account::export,
+        super::account::modify,
+        super::account::logout,
+        super::account::sessions,
+        super::account::revoke_session,
+        super::account::req_change_email,
+        super::account::change_email,
+        super::account::set_permissions,
+        super::account::put_role,
+        super::account::set_roles,
+        super::account::set_avatar,
+        super::account::avatar,
+        super::account::api_keys,
+        super::account::create_api_key,
+        super::account::revoke_api_key,
+        super::account::profile,
+        super::account::set_privacy,
+        super::account::manage::force_logout,
+        super::account::manage::reset_password,
+        super::account::manage::impersonate,
+        super::account::manage::import,
+        super::account::manage::search,
+        super::account::manage::list,
+        super::account::manage::suspend,
+        super::account::manage::unsuspend,
+        super::account::manage::invites,
+        super::account::manage::issue_invites,
+        super::account::manage::revoke_invite,
+        super::account::manage::dormant,
+        super::account::manage::grant,
+        super::account::manage::revoke_grant,
+        super::account::manage::permission_history,
+        super::account::manage::set_labels,
+        super::account::manage::approval_queue,
+        super::account::manage::approve,
+        super::admin::audit,
+        super::admin::collect_resources,
+        super::post::create,
+        super::post::get_posts,
+        super::post::get_posts_info,
+        super::post::resource,
+        super::post::public_image,
+        super::post::get_image,
+        super::post::image_info,
+        super::post::sign_url,
+        super::post::export,
+        super::post::edit,
+        super::post::clone,
+        super::post::submit,
+        super::post::assigned,
+        super::post::approve_post,
+        super::post::approve_batch,
+        super::post::amend_review,
+        super::post::delete,
+        super::post::reorder,
+        super::post::history,
+        super::post::stats,
+        super::post::stats_summary,
+        super::post::bookmark,
+        super::post::bookmarks,
+        super::post::pin,
+        super::post::archive,
+        super::post::calendar,
+        super::post::feed,
+        super::post::category::get_categories,
+        super::post::category::put,
+        super::post::category::delete,
+        super::post::comment::create,
+        super::post::comment::get_comments,
+        super::post::comment::delete,
+        super::post::report::report,
+        super::post::report::reports,
+        super::post::report::resolve,
+        super::post::template::get_templates,
+        super::post::template::put,
+        super::post::template::delete,
+        super::resource::new_session,
+        super::resource::upload,
+        super::resource::upload_batch,
+        super::resource::progress,
+        super::resource::upload_chunk,
+        super::resource::finalize,
+        super::resource::usage,
+        super::resource::free,
+        super::post::signed_resource,
+        super::screen::register,
+        super::screen::screens,
+        super::screen::assign,
+        super::screen::remove,
+        super::screen::playlist,
+        super::screen::report,
+        super::webhook::webhooks,
+        super::webhook::register,
+        super::webhook::remove,
+    ),
+    components(schemas(
+        sms3_backend::account::Permission,
+        sms3_backend::account::Privacy,
+        sms3_backend::account::NotifyPrefs,
+        sms3_backend::account::Digest,
+        sms3_backend::account::LastLogin,
+        sms3_backend::account::api_key::ApiKeyInfo,
+        sms3_backend::account::verify::VerifyVariant,
+        sms3_backend::account::verify::Captcha,
+        sms3_backend::account::verify::Device,
+        sms3_backend::account::verify::Session,
+        sms3_backend::audit::Action,
+        sms3_backend::challenge::Challenge,
+        sms3_backend::challenge::Answer,
+        super::account::SendCaptchaReq,
+        super::account::RegisterReq,
+        super::account::LoginReq,
+        super::account::LoginRes,
+        super::account::EnrollTotpRes,
+        super::account::TotpCodeReq,
+        super::account::ResendVerifyReq,
+        super::account::SendResetPasswordCaptchaReq,
+        super::account::ResetPasswordReq,
+        super::account::SelfInfoRes,
+        super::account::SignOutReq,
+        super::account::SignOutRes,
+        super::account::RestoreReq,
+        super::account::ExportRes,
+        super::account::ModifyReq,
+        super::account::ModifyPasswordPart,
+        super::account::RevokeSessionReq,
+        super::account::ReqChangeEmailReq,
+        super::account::ChangeEmailReq,
+        super::account::ChangeEmailRes,
+        super::account::SetPermissionsReq,
+        super::account::PutRoleReq,
+        super::account::SetRolesReq,
+        super::account::SetAvatarReq,
+        super::account::CreateApiKeyReq,
+        super::account::CreateApiKeyRes,
+        super::account::RevokeApiKeyReq,
+        super::account::ProfileRes,
+        super::account::manage::TargetAccountReq,
+        super::account::manage::ImpersonateRes,
+        super::account::manage::ImportRowRes,
+        super::account::manage::SearchReq,
+        super::account::manage::AccountRes,
+        super::account::manage::ListSort,
+        super::account::manage::SortValue,
+        super::account::manage::ListCursor,
+        super::account::manage::ListReq,
+        super::account::manage::ListRes,
+        super::account::manage::SuspendReq,
+        super::account::manage::IssueInvitesReq,
+        super::account::manage::InviteRes,
+        super::account::manage::RevokeInviteReq,
+        super::account::manage::DormantReq,
+        super::account::manage::GrantReq,
+        super::account::manage::RevokeGrantReq,
+        super::account::manage::SetLabelsReq,
+        super::account::manage::ApproveReq,
+        super::admin::AuditReq,
+        super::admin::AuditCursor,
+        super::admin::AuditEntryRes,
+        super::admin::AuditRes,
+        super::post::CreatePostReq,
+        super::post::PostIdRes,
+        super::post::SearchQuery,
+        super::post::GetPostsFilter,
+        super::post::PostSort,
+        super::post::PostCursor,
+        super::post::GetPostsDescriptor,
+        super::post::GetPostsRes,
+        super::post::GetPostsInfoReq,
+        super::post::PostInfoRes,
+        super::post::WindowRes,
+        super::post::ImageInfoReq,
+        super::post::ImageInfoRes,
+        super::post::SignUrlReq,
+        super::post::SignUrlRes,
+        super::post::ExportPostReq,
+        super::post::EditPostReq,
+        super::post::ClonePostReq,
+        super::post::SubmitPostReq,
+        super::post::ReviewPostReq,
+        super::post::ReviewPostRes,
+        super::post::BatchReviewPostsReq,
+        super::post::BatchReviewPostRes,
+        super::post::AmendReviewReq,
+        super::post::DeletePostReq,
+        super::post::ReorderPostsReq,
+        super::post::PostHistoryReq,
+        super::post::PostHistoryRes,
+        super::post::PostStatsReq,
+        super::post::PostStatsRes,
+        super::post::StatsSummaryReq,
+        super::post::StatsSummaryRes,
+        super::post::StatusCount,
+        super::post::GroupCount,
+        super::post::WeekCount,
+        super::post::BookmarkReq,
+        super::post::PinPostReq,
+        super::post::ArchivePostReq,
+        super::post::category::PutCategoryReq,
+        super::post::category::CategoryRes,
+        super::post::category::DeleteCategoryReq,
+        super::post::comment::CreateCommentReq,
+        super::post::comment::CommentIdRes,
+        super::post::comment::GetCommentsReq,
+        super::post::comment::CommentRes,
+        super::post::comment::DeleteCommentReq,
+        super::post::report::ReportPostReq,
+        super::post::report::ReportRes,
+        super::post::report::ResolveReportReq,
+        super::post::template::PutTemplateReq,
+        super::post::template::TemplateRes,
+        super::post::template::DeleteTemplateReq,
+        super::resource::NewSessionReq,
+        super::resource::ResourceIdRes,
+        super::resource::BatchFileRes,
+        super::resource::UploadProgressRes,
+        super::resource::FinalizeReq,
+        super::resource::CollectRes,
+        super::resource::UsageResourceRes,
+        super::resource::UsageRes,
+        super::screen::RegisterScreenReq,
+        super::screen::RegisterScreenRes,
+        super::screen::ScreenRes,
+        super::screen::AssignPostsReq,
+        super::screen::RemoveScreenReq,
+        super::screen::PlaylistItemRes,
+        super::screen::ReportReq,
+        super::screen::PostReport,
+        super::webhook::RegisterWebhookReq,
+        super::webhook::RegisterWebhookRes,
+        super::webhook::WebhookRes,
+        super::webhook::RemoveWebhookReq,
+        sms3_backend::post::Scope,
+        sms3_backend::post::State,
+        sms3_backend::post::Status,
+        sms3_backend::post::Recurrence,
+        sms3_backend::post::Revision,
+        sms3_backend::post::Change,
+        sms3_backend::post::DescriptionFormat,
+        sms3_backend::post::report::Reason,
+        sms3_backend::post::search::Field,
+        sms3_backend::post::template::Field,
+        sms3_backend::resource::Variant,
+        sms3_backend::resource::image::Size,
+        sms3_backend::webhook::EventKind,
+        sms3_backend::webhook::Delivery,
+    )),
+    modifiers(&Token),
+    tags(
+        (name = "account", description = "Accounts and their management"),
+        (name = "admin", description = "Administration"),
+        (name = "post", description = "Posts, and their comments, categories, reports and templates"),
+        (name = "resource", description = "Resource uploads"),
+        (name = "screen", description = "Display devices"),
+        (name = "webhook", description = "Webhooks of post events"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Adds the `Authorization` header in `{account}:{token}` syntax
+/// as the security scheme.
+struct Token;
+
+impl Modify for Token {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "Authorization",
+                    "`{account}:{token}`, where the token is a login token, a JWT or an API key.",
+                ))),
+            )
+        }
+    }
+}
@@ -0,0 +1,2807 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::{Account, Permission},
+    audit::Action,
+    config::Config,
+    post::{self, search, Change, DescriptionFormat, Post, Recurrence, Scope, Status},
+    resource::{self, image, storage::Storage, Variant},
+    webhook::{Event, EventKind},
+    Error,
+};
+use time::{Date, OffsetDateTime};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt};
+
+use crate::{Auth, Global, Worlds};
+
+/// Selects a post.
+macro_rules! sp {
+    ($w:expr, $id:expr) => {
+        $w.post.select(0, $id).hint($id)
+    };
+}
+
+pub mod category;
+pub mod comment;
+pub mod report;
+pub mod template;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreatePostReq {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub description_format: DescriptionFormat,
+    /// Ids of uploaded image resources.
+    pub images: Vec<u64>,
+    /// Ids of uploaded PDF resources.
+    #[serde(default)]
+    pub attachments: Vec<u64>,
+    /// Ids of uploaded video resources.
+    #[serde(default)]
+    pub videos: Vec<u64>,
+    pub start: Date,
+    /// Could be omitted if the template has default days.
+    #[serde(default)]
+    pub end: Option<Date>,
+    /// Name of the category.
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Accounts sharing edit rights with the creator.
+    #[serde(default)]
+    pub co_publishers: HashSet<u64>,
+    #[serde(default)]
+    pub scope: Scope,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Creates the post as a draft instead of submitting it.
+    #[serde(default)]
+    pub draft: bool,
+    /// Name of the template filling the title pattern
+    /// and defaults of the post.
+    #[serde(default)]
+    pub from_template: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PostIdRes {
+    pub id: u64,
+}
+
+/// Creates a pending or draft post.
+#[utoipa::path(
+    post,
+    path = "/api/post/create",
+    tag = "post",
+    request_body = CreatePostReq,
+    responses((status = 200, body = PostIdRes)),
+    security(("token" = [])),
+)]
+pub async fn create<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        post_index,
+        config,
+        http_client,
+        ..
+    }): State<Global<Io>>,
+    Json(CreatePostReq {
+        title,
+        description,
+        description_format,
+        images,
+        attachments,
+        videos,
+        start,
+        end,
+        category,
+        tags,
+        co_publishers,
+        scope,
+        recurrence,
+        draft,
+        from_template,
+    }): Json<CreatePostReq>,
+) -> Result<Json<PostIdRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::Post);
+    let template = if let Some(ref name) = from_template {
+        Some(template::get(&worlds, name).await?)
+    } else {
+        None
+    };
+    let end = end
+        .or_else(|| {
+            let days = template.as_ref()?.days()?;
+            start.checked_add(time::Duration::days(days as i64 - 1))
+        })
+        .ok_or(Error::InvalidDateRange)?;
+    let category = category.or_else(|| template.as_ref()?.category().cloned());
+    let title = template.as_ref().map_or(title, |t| t.title(&title));
+    validate_dates(&worlds, &config, &auth, lazy.get().await?, &(start..=end)).await?;
+    if let Some(ref category) = category {
+        category::validate(&worlds, category).await?;
+    }
+    if recurrence
+        .as_ref()
+        .map_or(false, |r| !r.is_valid(&(start..=end)))
+    {
+        return Err(Error::InvalidRecurrence);
+    }
+
+    validate_co_publishers(&worlds, auth.account, &co_publishers).await?;
+
+    let mut post = Post::new(title, description, images, start..=end, auth.account, draft);
+    post.set_attachments(attachments);
+    post.set_videos(videos);
+    post.set_description_format(description_format);
+    post.set_category(category);
+    post.set_tags(tags);
+    post.set_co_publishers(co_publishers);
+    post.set_scope(scope);
+    post.set_recurrence(recurrence);
+    if let Some(field) = template.and_then(|t| t.missing(&post)) {
+        return Err(Error::TemplateFieldRequired(field.name()));
+    }
+    let resources = resources_of(&post);
+    let ids: Vec<_> = resources.iter().map(|(id, _)| *id).collect();
+    super::resource::check_owners(
+        &worlds,
+        &auth,
+        lazy.get().await?,
+        &ids,
+        |user| post.is_publisher(user),
+        Permission::ManagePosts,
+    )
+    .await?;
+    super::resource::acquire_all(&worlds, &resources).await?;
+
+    post.materialize(OffsetDateTime::now_utc().date());
+    post.set_pool(route(&config, lazy.get().await?, &post));
+    let id = post.id();
+    let (title, description) = (post.title().to_owned(), post.description().to_owned());
+    if let Err(err) = worlds.post.insert(post).await {
+        super::resource::release_all(&worlds, &ids).await?;
+        return Err(err.into());
+    }
+    post_index.write().await.insert(id, &title, &description);
+    super::webhook::dispatch(worlds, http_client, Event::new(EventKind::Created, id));
+    Ok(Json(PostIdRes { id }))
+}
+
+/// Resources attached to the post, with their variants.
+fn resources_of(post: &Post) -> Vec<(u64, Option<Variant>)> {
+    let mut resources = vec![];
+    for (ids, variant) in [
+        (post.images(), Variant::Image),
+        (post.attachments(), Variant::Pdf),
+        (post.videos(), Variant::Video),
+    ] {
+        resources.extend(ids.iter().map(|id| (*id, Some(variant))));
+    }
+    resources
+}
+
+/// A full-text query of posts, given as a plain query matching
+/// all terms in title or description, or with options.
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum SearchQuery {
+    Plain(String),
+    Detailed {
+        query: String,
+        /// Matches any term instead of all terms.
+        #[serde(default)]
+        any: bool,
+        #[serde(default)]
+        field: search::Field,
+    },
+}
+
+impl SearchQuery {
+    fn search(&self, index: &search::Index) -> HashSet<u64> {
+        match self {
+            SearchQuery::Plain(query) => index.search(query, search::Field::All, false),
+            SearchQuery::Detailed { query, any, field } => index.search(query, *field, *any),
+        }
+    }
+}
+
+/// A filter of posts.
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GetPostsFilter {
+    Creator(u64),
+    /// Posts created or co-published by the account.
+    Publisher(u64),
+    Status(Status),
+    /// Posts on screen at the date.
+    OnDate(Date),
+    /// Posts on screen at any date of the inclusive range,
+    /// including posts already running at the start.
+    Overlaps {
+        start: Date,
+        end: Date,
+    },
+    /// Posts matching the full-text query.
+    Search(SearchQuery),
+    /// Posts in the category.
+    Category(String),
+    /// Posts with the tag.
+    Tag(String),
+}
+
+impl GetPostsFilter {
+    fn matches(&self, post: &Post) -> bool {
+        match self {
+            GetPostsFilter::Creator(c) => post.creator() == *c,
+            GetPostsFilter::Publisher(p) => post.is_publisher(*p),
+            GetPostsFilter::Status(s) => post.state().status() == *s,
+            GetPostsFilter::OnDate(d) => post.on_screen(*d),
+            GetPostsFilter::Overlaps { start, end } => post.overlaps(&(*start..=*end)),
+            // Resolved through the index before scanning.
+            GetPostsFilter::Search(_) => true,
+            GetPostsFilter::Category(c) => post.category() == Some(c),
+            GetPostsFilter::Tag(t) => post.tags().contains(t),
+        }
+    }
+}
+
+/// Sort key of posts.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PostSort {
+    #[default]
+    CreatedAt,
+    StartDate,
+    /// Time of the last status change.
+    StatusChanged,
+    /// Display priority given by admins, sorts with `desc`
+    /// to get the most important posts first.
+    Priority,
+}
+
+impl PostSort {
+    /// The sort value of a post as a unix timestamp.
+    fn value_of(self, post: &Post) -> i64 {
+        match self {
+            PostSort::CreatedAt => post.created_at().unix_timestamp(),
+            PostSort::StartDate => post
+                .dates()
+                .start()
+                .midnight()
+                .assume_utc()
+                .unix_timestamp(),
+            PostSort::StatusChanged => post.state().time().unix_timestamp(),
+            PostSort::Priority => post.priority(),
+        }
+    }
+}
+
+/// Cursor of posts, ordered by the sort value and id
+/// after pinned posts.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+pub struct PostCursor {
+    #[serde(default)]
+    pub pinned: bool,
+    pub value: i64,
+    pub id: u64,
+}
+
+impl PostCursor {
+    /// Order of cursors in results, pinned posts first
+    /// regardless of the sort direction.
+    fn order(&self, other: &Self, desc: bool) -> std::cmp::Ordering {
+        other.pinned.cmp(&self.pinned).then_with(|| {
+            let ord = (self.value, self.id).cmp(&(other.value, other.id));
+            if desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct GetPostsDescriptor {
+    /// Filters that all should be matched.
+    #[serde(default)]
+    pub filters: Vec<GetPostsFilter>,
+    #[serde(default)]
+    pub sort: PostSort,
+    /// Sorts in descending order.
+    #[serde(default)]
+    pub desc: bool,
+    /// Only returns posts after this cursor in the sort order.
+    #[serde(default)]
+    pub after: Option<PostCursor>,
+    #[serde(default = "GetPostsDescriptor::default_limit")]
+    pub limit: usize,
+}
+
+impl GetPostsDescriptor {
+    const MAX_LIMIT: usize = 200;
+
+    #[inline]
+    fn default_limit() -> usize {
+        50
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GetPostsRes {
+    pub posts: Vec<u64>,
+    /// Cursor for the next page, `None` if there are no more posts.
+    pub next: Option<PostCursor>,
+}
+
+/// Visibility of posts to the requesting account.
+struct Visibility {
+    account: u64,
+    all: bool,
+    public: bool,
+    staff: bool,
+    labels: Vec<String>,
+    departments: Vec<String>,
+}
+
+impl Visibility {
+    async fn new<Io: IoHandle>(
+        worlds: &Worlds<Io>,
+        auth: &Auth,
+        account: &Account,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            account: auth.account,
+            all: super::permitted(worlds, auth, account, &[Permission::Approve]).await?,
+            public: super::permitted(worlds, auth, account, &[Permission::GetPubPosts]).await?,
+            staff: super::permitted(worlds, auth, account, &[Permission::GetStaffPosts]).await?,
+            labels: account.labels(),
+            departments: account
+                .departments()
+                .iter()
+                .map(|d| d.as_ref().to_owned())
+                .collect(),
+        })
+    }
+
+    /// Whether the post is visible.
+    fn can_see(&self, post: &Post) -> bool {
+        post.is_publisher(self.account)
+            || (self.all && !post.is_draft())
+            || (self.public && post.is_active() && self.in_scope(post.scope()))
+    }
+
+    /// Whether the account is in the audience.
+    fn in_scope(&self, scope: &Scope) -> bool {
+        match scope {
+            Scope::Everyone => true,
+            Scope::House(house) => self.labels.contains(house),
+            Scope::Department(department) => self.departments.contains(department),
+            Scope::Staff => self.staff,
+        }
+    }
+}
+
+/// Gets ids of posts matching all filters, paginated by cursor.
+#[utoipa::path(
+    post,
+    path = "/api/post/get",
+    tag = "post",
+    request_body = GetPostsDescriptor,
+    responses((status = 200, body = GetPostsRes)),
+    security(("token" = [])),
+)]
+pub async fn get_posts<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, post_index, ..
+    }): State<Global<Io>>,
+    Json(GetPostsDescriptor {
+        filters,
+        sort,
+        desc,
+        after,
+        limit,
+    }): Json<GetPostsDescriptor>,
+) -> Result<Json<GetPostsRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+
+    let limit = limit.clamp(1, GetPostsDescriptor::MAX_LIMIT);
+    let now = OffsetDateTime::now_utc();
+    let searched = {
+        let index = post_index.read().await;
+        filters.iter().fold(None, |acc: Option<HashSet<u64>>, f| {
+            if let GetPostsFilter::Search(query) = f {
+                let ids = query.search(&index);
+                Some(acc.map_or(ids.clone(), |a| a.intersection(&ids).copied().collect()))
+            } else {
+                acc
+            }
+        })
+    };
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    let mut cursors = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        if searched.as_ref().map_or(false, |s| !s.contains(&post.id()))
+            || !visibility.can_see(post)
+            || !filters.iter().all(|f| f.matches(post))
+        {
+            continue;
+        }
+        let cursor = PostCursor {
+            pinned: post.is_pinned(now),
+            value: sort.value_of(post),
+            id: post.id(),
+        };
+        if after.map_or(true, |a| cursor.order(&a, desc).is_gt()) {
+            cursors.push(cursor);
+        }
+    }
+    cursors.sort_unstable_by(|a, b| a.order(b, desc));
+    cursors.truncate(limit + 1);
+    let next = (cursors.len() > limit).then(|| {
+        cursors.pop();
+        cursors[limit - 1]
+    });
+    Ok(Json(GetPostsRes {
+        posts: cursors.into_iter().map(|c| c.id).collect(),
+        next,
+    }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct GetPostsInfoReq {
+    pub posts: Vec<u64>,
+    /// Includes whether the posts are bookmarked.
+    #[serde(default)]
+    pub bookmarks: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PostInfoRes {
+    pub id: u64,
+    pub title: String,
+    pub description: String,
+    pub description_format: DescriptionFormat,
+    /// Sanitized HTML of the description if it's in markdown.
+    pub description_html: Option<String>,
+    pub images: Vec<u64>,
+    pub attachments: Vec<u64>,
+    pub videos: Vec<u64>,
+    pub start: Date,
+    pub end: Date,
+    pub creator: u64,
+    pub co_publishers: Vec<u64>,
+    pub scope: Scope,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub recurrence: Option<Recurrence>,
+    /// Upcoming display windows of the recurrence.
+    pub windows: Vec<WindowRes>,
+    pub states: Vec<post::State>,
+    /// Whether the post is pinned currently.
+    pub pinned: bool,
+    /// Whether the post is bookmarked by the requesting account,
+    /// only present if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmarked: Option<bool>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WindowRes {
+    pub start: Date,
+    pub end: Date,
+}
+
+impl PostInfoRes {
+    pub fn new(post: &Post) -> Self {
+        Self {
+            id: post.id(),
+            title: post.title().to_owned(),
+            description: post.description().to_owned(),
+            description_format: post.description_format(),
+            description_html: post.description_html(),
+            images: post.images().to_vec(),
+            attachments: post.attachments().to_vec(),
+            videos: post.videos().to_vec(),
+            start: *post.dates().start(),
+            end: *post.dates().end(),
+            creator: post.creator(),
+            co_publishers: post.co_publishers().iter().copied().collect(),
+            scope: post.scope().clone(),
+            category: post.category().cloned(),
+            tags: post.tags().to_vec(),
+            recurrence: post.recurrence().cloned(),
+            windows: post
+                .windows()
+                .iter()
+                .map(|w| WindowRes {
+                    start: *w.start(),
+                    end: *w.end(),
+                })
+                .collect(),
+            states: post.states().to_vec(),
+            pinned: post.is_pinned(OffsetDateTime::now_utc()),
+            bookmarked: None,
+        }
+    }
+}
+
+/// Gets information of posts, skipping invisible or missing ones.
+#[utoipa::path(
+    post,
+    path = "/api/post/info",
+    tag = "post",
+    request_body = GetPostsInfoReq,
+    responses((status = 200, body = [PostInfoRes])),
+    security(("token" = [])),
+)]
+pub async fn get_posts_info<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(GetPostsInfoReq { posts, bookmarks }): Json<GetPostsInfoReq>,
+) -> Result<Json<Vec<PostInfoRes>>, Error> {
+    const MAX_POSTS: usize = 200;
+
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    let account = lazy.get().await?;
+    let visibility = Visibility::new(&worlds, &auth, account).await?;
+
+    let mut res = Vec::with_capacity(posts.len().min(MAX_POSTS));
+    for id in posts.into_iter().take(MAX_POSTS) {
+        let select = sp!(worlds, id);
+        if let Some(lazy) = ga!(select, id) {
+            let post = lazy.get().await?;
+            if visibility.can_see(post) {
+                let mut info = PostInfoRes::new(post);
+                if bookmarks {
+                    info.bookmarked = Some(account.bookmarks().contains(&id));
+                }
+                res.push(info);
+            }
+        }
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResourceQuery {
+    /// Size of the thumbnail, only for images.
+    #[serde(default)]
+    pub size: Option<image::Size>,
+}
+
+/// Downloads an image, attachment or video of a post
+/// visible to the requesting account.
+#[utoipa::path(
+    get,
+    path = "/api/post/resource/{post}/{id}",
+    tag = "post",
+    params(("post" = u64, Path), ("id" = u64, Path), ResourceQuery),
+    responses(
+        (status = 200, description = "Resource content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the resource"),
+        (status = 304, description = "Not modified since the given `ETag`"),
+    ),
+    security(("token" = [])),
+)]
+pub async fn resource<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Path((post, id)): Path<(u64, u64)>,
+    Query(ResourceQuery { size }): Query<ResourceQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy_post.get().await?;
+    if !visibility.can_see(post) {
+        return Err(Error::PostNotFound(post.id()));
+    }
+    let variant = if post.images().contains(&id) {
+        Variant::Image
+    } else if post.attachments().contains(&id) {
+        Variant::Pdf
+    } else if post.videos().contains(&id) {
+        Variant::Video
+    } else {
+        return Err(Error::ResourceNotFound(id));
+    };
+    read_resource(&worlds, &*storage, id, variant, size, &headers, false).await
+}
+
+/// Downloads an image of an approved or active post visible to everyone,
+/// without authorization.
+///
+/// This is linked by the feed of accepted posts.
+#[utoipa::path(
+    get,
+    path = "/api/post/public-image/{post}/{id}",
+    tag = "post",
+    params(("post" = u64, Path), ("id" = u64, Path), ResourceQuery),
+    responses(
+        (status = 200, description = "Resource content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the resource"),
+        (status = 304, description = "Not modified since the given `ETag`"),
+    ),
+)]
+pub async fn public_image<Io: IoHandle>(
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Path((post, id)): Path<(u64, u64)>,
+    Query(ResourceQuery { size }): Query<ResourceQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, Error> {
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy_post.get().await?;
+    if !matches!(post.state().status(), Status::Approved | Status::Active)
+        || *post.scope() != Scope::Everyone
+    {
+        return Err(Error::PostNotFound(post.id()));
+    }
+    if !post.images().contains(&id) {
+        return Err(Error::ResourceNotFound(id));
+    }
+    read_resource(&worlds, &*storage, id, Variant::Image, size, &headers, true).await
+}
+
+/// Downloads an image resource referenced by posts by its content hash,
+/// so that images shared or reordered across posts are cached once.
+///
+/// The image should be uploaded by the requesting account, or
+/// referenced by a post visible to it.
+#[utoipa::path(
+    get,
+    path = "/api/post/image/{id}",
+    tag = "post",
+    params(("id" = u64, Path), ResourceQuery),
+    responses(
+        (status = 200, description = "Resource content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the resource"),
+        (status = 304, description = "Not modified since the given `ETag`"),
+    ),
+    security(("token" = [])),
+)]
+pub async fn get_image<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Path(id): Path<u64>,
+    Query(ResourceQuery { size }): Query<ResourceQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = worlds.resource.select(0, id).hint(id);
+    let lazy_res = ga!(select, id).ok_or(Error::ResourceNotFound(id))?;
+    let res = lazy_res.get().await?;
+    if res.variant() != Variant::Image || !res.is_used() {
+        return Err(Error::ResourceNotFound(id));
+    }
+    if !res.is_uploader(auth.account) {
+        let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+        let select = worlds.post.select(0, ..);
+        let mut iter = select.iter();
+        let mut visible = false;
+        while let Some(Ok(lazy)) = iter.next().await {
+            if let Ok(post) = lazy.get().await {
+                if post.images().contains(&id) && visibility.can_see(post) {
+                    visible = true;
+                    break;
+                }
+            }
+        }
+        if !visible {
+            return Err(Error::ResourceNotFound(id));
+        }
+    }
+    read_resource(
+        &worlds,
+        &*storage,
+        id,
+        Variant::Image,
+        size,
+        &headers,
+        false,
+    )
+    .await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ImageInfoReq {
+    /// Content hash of the image.
+    pub id: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ImageInfoRes {
+    pub id: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the stored file as bytes.
+    pub size: u64,
+    /// Content type, e.g. `image/png`.
+    pub format: String,
+    pub uploader: u64,
+    pub uploaded_at: i64,
+    /// Count of references, e.g. posts sharing the image.
+    pub refs: u32,
+}
+
+/// Gets metadata of an image referenced by posts or uploaded by
+/// the requesting account, see [`get_image`].
+#[utoipa::path(
+    post,
+    path = "/api/post/image-info",
+    tag = "post",
+    request_body = ImageInfoReq,
+    responses((status = 200, body = ImageInfoRes)),
+    security(("token" = [])),
+)]
+pub async fn image_info<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Json(ImageInfoReq { id }): Json<ImageInfoReq>,
+) -> Result<Json<ImageInfoRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let select = worlds.resource.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or(Error::ResourceNotFound(id))?;
+    let res = lazy.get().await?;
+    if res.variant() != Variant::Image || !(res.is_used() || res.is_uploader(auth.account)) {
+        return Err(Error::ResourceNotFound(id));
+    }
+    // Dimensions are not recorded for images uploaded before.
+    let (width, height) = match res.dimensions() {
+        Some(dimensions) => dimensions,
+        None => image::dimensions(
+            &storage
+                .get(&res.key())
+                .await?
+                .ok_or(Error::ResourceNotFound(id))?,
+        )?,
+    };
+    Ok(Json(ImageInfoRes {
+        id,
+        width,
+        height,
+        size: res.size(),
+        format: res.content_type().to_owned(),
+        uploader: res.user(),
+        uploaded_at: res.created_at().unix_timestamp(),
+        refs: res.refs(),
+    }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SignUrlReq {
+    pub post: u64,
+    /// Id of an image, attachment or video of the post.
+    pub resource: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SignUrlRes {
+    /// Path of the signed url, relative to the server.
+    pub url: String,
+    /// Expiration timestamp of the url.
+    pub expires: i64,
+}
+
+/// Signs an expiring url of a resource of a post visible to the
+/// requesting account, so that it could be fetched by clients
+/// without account tokens, e.g. screens.
+#[utoipa::path(
+    post,
+    path = "/api/post/sign-url",
+    tag = "post",
+    request_body = SignUrlReq,
+    responses((status = 200, body = SignUrlRes)),
+    security(("token" = [])),
+)]
+pub async fn sign_url<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+    Json(SignUrlReq { post, resource: id }): Json<SignUrlReq>,
+) -> Result<Json<SignUrlRes>, Error> {
+    let secret = config
+        .resource
+        .url_secret
+        .as_deref()
+        .ok_or(Error::SignedUrlDisabled)?;
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy_post.get().await?;
+    if !visibility.can_see(post) {
+        return Err(Error::PostNotFound(post.id()));
+    }
+    if !post.images().contains(&id)
+        && !post.attachments().contains(&id)
+        && !post.videos().contains(&id)
+    {
+        return Err(Error::ResourceNotFound(id));
+    }
+    let expires = OffsetDateTime::now_utc().unix_timestamp() + config.resource.url_max_age as i64;
+    let signature = resource::signed::sign(secret, id, expires);
+    Ok(Json(SignUrlRes {
+        url: format!("/api/resource/signed/{id}?expires={expires}&signature={signature}"),
+        expires,
+    }))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SignedQuery {
+    pub expires: i64,
+    pub signature: String,
+    /// Size of the thumbnail, only for images.
+    #[serde(default)]
+    pub size: Option<image::Size>,
+}
+
+/// Verifies signatures of requests to signed urls, which
+/// are in `{prefix}/{id}?expires={expires}&signature={signature}`.
+pub async fn verify_signature<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Result<Response, Error> {
+    let secret = config
+        .resource
+        .url_secret
+        .as_deref()
+        .ok_or(Error::SignedUrlDisabled)?;
+    let id = req
+        .uri()
+        .path()
+        .rsplit('/')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or(Error::InvalidUrlSignature)?;
+    let Query(SignedQuery {
+        expires, signature, ..
+    }) = Query::<SignedQuery>::try_from_uri(req.uri()).map_err(|_| Error::InvalidUrlSignature)?;
+    if !resource::signed::verify(secret, id, expires, &signature) {
+        return Err(Error::InvalidUrlSignature);
+    }
+    Ok(next.run(req).await)
+}
+
+/// Downloads a resource through a signed url, which should
+/// be guarded by [`verify_signature`].
+#[utoipa::path(
+    get,
+    path = "/api/resource/signed/{id}",
+    tag = "post",
+    params(("id" = u64, Path), SignedQuery),
+    responses(
+        (status = 200, description = "Resource content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the resource"),
+        (status = 304, description = "Not modified since the given `ETag`"),
+    ),
+)]
+pub async fn signed_resource<Io: IoHandle>(
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Path(id): Path<u64>,
+    Query(SignedQuery { size, .. }): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let select = worlds.resource.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or(Error::ResourceNotFound(id))?;
+    let res = lazy.get().await?;
+    res.check_scan()?;
+    let variant = res.variant();
+    read_resource(&worlds, &*storage, id, variant, size, &headers, false).await
+}
+
+/// Whether the client accepts the media type.
+#[inline]
+fn accepts(headers: &HeaderMap, media_type: &str) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| v.contains(media_type))
+}
+
+/// Streams a resource file with its content type and length.
+///
+/// For images, the thumbnail in given size, or the AVIF or WebP copy
+/// if the client accepts it, is read if generated. AVIF is preferred.
+///
+/// The content type is the one recorded while uploading, or sniffed
+/// from the data for thumbnails, copies and older resources.
+///
+/// Files are addressed by content hashes and never change, so they
+/// are cached by clients with their keys as entity tags.
+///
+/// A single byte range could be requested with the `Range` header,
+/// for resuming downloads and previewing large media.
+async fn read_resource<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    storage: &dyn Storage,
+    id: u64,
+    variant: Variant,
+    size: Option<image::Size>,
+    headers: &HeaderMap,
+    public: bool,
+) -> Result<Response, Error> {
+    let mut alternative = None;
+    if variant == Variant::Image {
+        let keys = match size {
+            Some(size) => vec![size.key(id)],
+            None => [
+                (cfg!(feature = "avif"), "image/avif", image::avif_key(id)),
+                (cfg!(feature = "webp"), "image/webp", image::webp_key(id)),
+            ]
+            .into_iter()
+            .filter(|(enabled, media_type, _)| *enabled && accepts(headers, media_type))
+            .map(|(_, _, key)| key)
+            .collect(),
+        };
+        for key in keys {
+            if let Some(object) = storage.open(&key).await? {
+                alternative = Some((key, object, None));
+                break;
+            }
+        }
+    }
+    let (key, object, recorded) = match alternative {
+        Some(alternative) => alternative,
+        None => {
+            let key = resource::key_of(id);
+            let object = storage
+                .open(&key)
+                .await?
+                .ok_or(Error::ResourceNotFound(id))?;
+            let select = worlds.resource.select(0, id).hint(id);
+            let recorded = match ga!(select, id) {
+                Some(lazy) => Some(lazy.get().await?.content_type().to_owned()),
+                None => None,
+            };
+            (key, object, recorded.filter(|t| !t.is_empty()))
+        }
+    };
+
+    let etag = format!("\"{key}\"");
+    let mut res_headers = HeaderMap::new();
+    res_headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).map_err(|_| Error::Unknown)?,
+    );
+    res_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(if public {
+            "public, max-age=31536000, immutable"
+        } else {
+            "private, max-age=31536000, immutable"
+        }),
+    );
+    res_headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    res_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    let matched = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| {
+            v.split(',')
+                .map(|t| t.trim().trim_start_matches("W/"))
+                .any(|t| t == etag || t == "*")
+        });
+    if matched {
+        return Ok((StatusCode::NOT_MODIFIED, res_headers).into_response());
+    }
+
+    // The file is streamed, with its head buffered for sniffing.
+    let mut reader = tokio::io::BufReader::new(object.reader);
+    let content_type = match recorded {
+        Some(content_type) => content_type,
+        None => resource::sniff(reader.fill_buf().await?)
+            .map_or("application/octet-stream", |(_, t)| t)
+            .to_owned(),
+    };
+    res_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type).map_err(|_| Error::Unknown)?,
+    );
+
+    // Ranges are ignored if the file is changed from the one
+    // known by the client.
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| {
+            headers
+                .get(header::IF_RANGE)
+                .map_or(true, |v| v.to_str().map_or(false, |v| v.trim() == etag))
+        });
+    let len = object.len;
+    let (start, end) = match range.map_or(Ok(None), |range| parse_range(range, len)) {
+        Ok(Some(range)) => range,
+        Ok(None) => {
+            res_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+            return Ok((
+                res_headers,
+                Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+            )
+                .into_response());
+        }
+        Err(()) => {
+            res_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{len}")).map_err(|_| Error::Unknown)?,
+            );
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, res_headers).into_response());
+        }
+    };
+    reader.seek(std::io::SeekFrom::Start(start)).await?;
+    res_headers.insert(
+        header::CONTENT_RANGE,
+        HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")).map_err(|_| Error::Unknown)?,
+    );
+    res_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(end - start + 1));
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        res_headers,
+        Body::from_stream(tokio_util::io::ReaderStream::new(
+            reader.take(end - start + 1),
+        )),
+    )
+        .into_response())
+}
+
+/// Parses a single byte range of a file in given length
+/// into inclusive bounds.
+///
+/// Returns `Ok(None)` for malformed or multiple ranges, which
+/// should be ignored, and `Err(())` for unsatisfiable ranges.
+fn parse_range(value: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some((start, end)) = value
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return Ok(None);
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let Ok(suffix) = suffix.parse::<u64>() else {
+                return Ok(None);
+            };
+            if suffix == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return Ok(None);
+            };
+            let end = if end.is_empty() {
+                len.saturating_sub(1)
+            } else {
+                let Ok(end) = end.parse::<u64>() else {
+                    return Ok(None);
+                };
+                if end < start {
+                    return Ok(None);
+                }
+                end.min(len.saturating_sub(1))
+            };
+            (start, end)
+        }
+    };
+    if len == 0 || start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ExportPostReq {
+    pub post: u64,
+}
+
+/// Exports a post as a zip of its resources and a `post.json`
+/// metadata file, for the publisher and admins.
+///
+/// The zip is streamed while being written.
+#[utoipa::path(
+    post,
+    path = "/api/post/export",
+    tag = "post",
+    request_body = ExportPostReq,
+    responses(
+        (status = 200, description = "Zip archive of the post", content_type = "application/zip"),
+    ),
+    security(("token" = [])),
+)]
+pub async fn export<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+    Json(ExportPostReq { post }): Json<ExportPostReq>,
+) -> Result<impl IntoResponse, Error> {
+    const BUF_SIZE: usize = 64 * 1024;
+
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy_post.get().await?;
+    if !post.is_publisher(auth.account)
+        && !super::permitted(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[Permission::ManagePosts],
+        )
+        .await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+
+    let info = PostInfoRes::new(post);
+    let metadata = serde_json::to_vec_pretty(&info).map_err(|_| Error::Unknown)?;
+    let files: Vec<(String, u64)> = post
+        .images()
+        .iter()
+        .map(|id| (format!("images/{id}"), *id))
+        .chain(
+            post.attachments()
+                .iter()
+                .map(|id| (format!("attachments/{id}.pdf"), *id)),
+        )
+        .chain(post.videos().iter().map(|id| (format!("videos/{id}"), *id)))
+        .collect();
+    let (writer, reader) = tokio::io::duplex(BUF_SIZE);
+    tokio::spawn(async move {
+        let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
+            use tokio_util::compat::FuturesAsyncWriteCompatExt;
+
+            let mut zip = async_zip::tokio::write::ZipFileWriter::with_tokio(writer);
+            zip.write_entry_whole(
+                async_zip::ZipEntryBuilder::new(
+                    "post.json".to_owned().into(),
+                    async_zip::Compression::Deflate,
+                ),
+                &metadata,
+            )
+            .await?;
+            for (name, id) in files {
+                let Ok(Some(mut object)) = storage.open(&resource::key_of(id)).await else {
+                    continue;
+                };
+                let mut entry = zip
+                    .write_entry_stream(async_zip::ZipEntryBuilder::new(
+                        name.into(),
+                        async_zip::Compression::Stored,
+                    ))
+                    .await?
+                    .compat_write();
+                tokio::io::copy(&mut object.reader, &mut entry).await?;
+                entry.into_inner().close().await?;
+            }
+            zip.close().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!("failed to export post: {err}");
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"post-{}.zip\"", info.id),
+            ),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+    ))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct EditPostReq {
+    pub post: u64,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub description_format: Option<DescriptionFormat>,
+    #[serde(default)]
+    pub images: Option<Vec<u64>>,
+    #[serde(default)]
+    pub attachments: Option<Vec<u64>>,
+    #[serde(default)]
+    pub videos: Option<Vec<u64>>,
+    #[serde(default)]
+    pub start: Option<Date>,
+    #[serde(default)]
+    pub end: Option<Date>,
+    /// Name of the category, or an empty string for uncategorized.
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// Co-publishers of the post, only editable by the creator.
+    #[serde(default)]
+    pub co_publishers: Option<HashSet<u64>>,
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    /// The recurrence rule, or `null` for not recurring.
+    #[serde(default, deserialize_with = "some")]
+    #[schema(value_type = Option<Recurrence>)]
+    pub recurrence: Option<Option<Recurrence>>,
+}
+
+/// Deserializes a present field as `Some`, so `null` could
+/// be told apart from a missing field.
+#[inline]
+fn some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Edits a post created or co-published by the requesting account, which resets
+/// it to pending for reviewing again unless it's a draft.
+#[utoipa::path(
+    post,
+    path = "/api/post/edit",
+    tag = "post",
+    request_body = EditPostReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn edit<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        post_index,
+        config,
+        ..
+    }): State<Global<Io>>,
+    Json(req): Json<EditPostReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy_account = va!(auth, select, worlds => Permission::Post);
+
+    let select = sp!(worlds, req.post);
+    let mut lazy = ga!(select, req.post).ok_or(Error::PostNotFound(req.post))?;
+    let post = lazy.get().await?;
+    if !post.is_publisher(auth.account)
+        || (req.co_publishers.is_some() && post.creator() != auth.account)
+    {
+        return Err(Error::PermissionDenied);
+    }
+    let start = req.start.unwrap_or(*post.dates().start());
+    let end = req.end.unwrap_or(*post.dates().end());
+    validate_dates(
+        &worlds,
+        &config,
+        &auth,
+        lazy_account.get().await?,
+        &(start..=end),
+    )
+    .await?;
+    let recurrence = req.recurrence.unwrap_or_else(|| post.recurrence().cloned());
+    if recurrence
+        .as_ref()
+        .map_or(false, |r| !r.is_valid(&(start..=end)))
+    {
+        return Err(Error::InvalidRecurrence);
+    }
+    let category = match req.category {
+        Some(category) if category.is_empty() => Some(None),
+        Some(category) => {
+            category::validate(&worlds, &category).await?;
+            Some(Some(category))
+        }
+        None => None,
+    };
+    if let Some(ref co_publishers) = req.co_publishers {
+        validate_co_publishers(&worlds, auth.account, co_publishers).await?;
+    }
+
+    // Validate and reference newly attached resources
+    // before changing anything.
+    let mut added = vec![];
+    let mut removed = vec![];
+    for (new, old, variant) in [
+        (&req.images, post.images(), Variant::Image),
+        (&req.attachments, post.attachments(), Variant::Pdf),
+        (&req.videos, post.videos(), Variant::Video),
+    ] {
+        if let Some(new) = new {
+            added.extend(
+                new.iter()
+                    .filter(|id| !old.contains(id))
+                    .map(|id| (*id, Some(variant))),
+            );
+            removed.extend(old.iter().filter(|id| !new.contains(id)).copied());
+        }
+    }
+    let ids: Vec<_> = added.iter().map(|(id, _)| *id).collect();
+    super::resource::check_owners(
+        &worlds,
+        &auth,
+        lazy_account.get().await?,
+        &ids,
+        |user| post.is_publisher(user),
+        Permission::ManagePosts,
+    )
+    .await?;
+    super::resource::acquire_all(&worlds, &added).await?;
+
+    let post = lazy.get_mut().await?;
+    let mut changes = vec![];
+    if let Some(category) = category.filter(|c| c.as_ref() != post.category()) {
+        changes.push(Change::Category {
+            from: post.category().cloned(),
+            to: category.clone(),
+        });
+        post.set_category(category);
+    }
+    if let Some(tags) = req.tags.filter(|t| t != post.tags()) {
+        changes.push(Change::Tags {
+            from: post.tags().to_vec(),
+            to: tags.clone(),
+        });
+        post.set_tags(tags);
+    }
+    if let Some(co_publishers) = req.co_publishers.filter(|c| c != post.co_publishers()) {
+        let sorted = |set: &HashSet<u64>| {
+            let mut ids: Vec<_> = set.iter().copied().collect();
+            ids.sort_unstable();
+            ids
+        };
+        changes.push(Change::CoPublishers {
+            from: sorted(post.co_publishers()),
+            to: sorted(&co_publishers),
+        });
+        post.set_co_publishers(co_publishers);
+    }
+    if let Some(scope) = req.scope.filter(|s| s != post.scope()) {
+        changes.push(Change::Scope {
+            from: post.scope().clone(),
+            to: scope.clone(),
+        });
+        post.set_scope(scope);
+    }
+    if let Some(images) = req.images.filter(|i| i != post.images()) {
+        changes.push(Change::Images {
+            to: images.clone(),
+            from: post.set_images(images),
+        });
+    }
+    if let Some(attachments) = req.attachments.filter(|a| a != post.attachments()) {
+        changes.push(Change::Attachments {
+            to: attachments.clone(),
+            from: post.set_attachments(attachments),
+        });
+    }
+    if let Some(videos) = req.videos.filter(|v| v != post.videos()) {
+        changes.push(Change::Videos {
+            to: videos.clone(),
+            from: post.set_videos(videos),
+        });
+    }
+    if let Some(title) = req.title.filter(|t| t != post.title()) {
+        changes.push(Change::Title {
+            from: post.title().to_owned(),
+            to: title.clone(),
+        });
+        post.set_title(title);
+    }
+    if let Some(description) = req.description.filter(|d| d != post.description()) {
+        changes.push(Change::Description {
+            from: post.description().to_owned(),
+            to: description.clone(),
+        });
+        post.set_description(description);
+    }
+    if let Some(format) = req.description_format {
+        post.set_description_format(format);
+    }
+    if *post.dates() != (start..=end) {
+        changes.push(Change::Dates {
+            from: post.dates().clone(),
+            to: start..=end,
+        });
+        post.set_dates(start..=end);
+    }
+    if recurrence.as_ref() != post.recurrence() {
+        changes.push(Change::Recurrence {
+            from: post.recurrence().cloned(),
+            to: recurrence.clone(),
+        });
+    }
+    post.push_revision(auth.account, changes);
+    post.set_recurrence(recurrence);
+    post.materialize(OffsetDateTime::now_utc().date());
+    if !post.is_draft() {
+        post.set_pool(route(&config, lazy_account.get().await?, post));
+        post.push_state(Status::Pending, auth.account, "edited".to_owned());
+    }
+    post_index
+        .write()
+        .await
+        .insert(post.id(), post.title(), post.description());
+    super::resource::release_all(&worlds, &removed).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ClonePostReq {
+    pub post: u64,
+}
+
+/// Copies content and resources of a visible post into
+/// a new draft of the requesting account.
+#[utoipa::path(
+    post,
+    path = "/api/post/clone",
+    tag = "post",
+    request_body = ClonePostReq,
+    responses((status = 200, body = PostIdRes)),
+    security(("token" = [])),
+)]
+pub async fn clone<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, post_index, ..
+    }): State<Global<Io>>,
+    Json(ClonePostReq { post }): Json<ClonePostReq>,
+) -> Result<Json<PostIdRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::Post);
+    let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let source = lazy_post.get().await?;
+    if !visibility.can_see(source) {
+        return Err(Error::PostNotFound(post));
+    }
+
+    let resources = resources_of(source);
+    super::resource::acquire_all(&worlds, &resources).await?;
+    let mut post = Post::new(
+        source.title().to_owned(),
+        source.description().to_owned(),
+        source.images().to_vec(),
+        source.dates().clone(),
+        auth.account,
+        true,
+    );
+    post.set_description_format(source.description_format());
+    post.set_attachments(source.attachments().to_vec());
+    post.set_videos(source.videos().to_vec());
+    post.set_category(source.category().cloned());
+    post.set_tags(source.tags().to_vec());
+    post.set_recurrence(source.recurrence().cloned());
+    post.materialize(OffsetDateTime::now_utc().date());
+    let id = post.id();
+    let (title, description) = (post.title().to_owned(), post.description().to_owned());
+    if let Err(err) = worlds.post.insert(post).await {
+        let ids: Vec<_> = resources.iter().map(|(id, _)| *id).collect();
+        super::resource::release_all(&worlds, &ids).await?;
+        return Err(err.into());
+    }
+    post_index.write().await.insert(id, &title, &description);
+    Ok(Json(PostIdRes { id }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SubmitPostReq {
+    pub post: u64,
+}
+
+/// Submits a draft post of the requesting account for reviewing,
+/// or resubmits a post returned for changes.
+#[utoipa::path(
+    post,
+    path = "/api/post/submit",
+    tag = "post",
+    request_body = SubmitPostReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn submit<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+    Json(SubmitPostReq { post }): Json<SubmitPostReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy_account = va!(auth, select, worlds => Permission::Post);
+
+    let select = sp!(worlds, post);
+    let mut lazy = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy.get_mut().await?;
+    if !post.is_publisher(auth.account)
+        || !matches!(
+            post.state().status(),
+            Status::Draft | Status::ChangesRequested
+        )
+    {
+        return Err(Error::PermissionDenied);
+    }
+    post.set_pool(route(&config, lazy_account.get().await?, post));
+    post.push_state(Status::Pending, auth.account, "submitted".to_owned());
+    Ok(())
+}
+
+/// Validates that co-publishers are existing accounts
+/// other than the creator.
+async fn validate_co_publishers<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    creator: u64,
+    co_publishers: &HashSet<u64>,
+) -> Result<(), Error> {
+    for id in co_publishers.iter().copied() {
+        let select = sa!(worlds.account, id);
+        if id == creator || ga!(select, id).is_none() {
+            return Err(Error::TargetAccountNotFound);
+        }
+    }
+    Ok(())
+}
+
+/// Validates the date range of a post against the duration
+/// limit of the publisher.
+async fn validate_dates<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    config: &Config,
+    auth: &Auth,
+    publisher: &Account,
+    dates: &RangeInclusive<Date>,
+) -> Result<(), Error> {
+    let limit = &config.post_duration;
+    let mut max_days = limit.max_days;
+    for o in limit.overrides.iter() {
+        if max_days == 0 {
+            break;
+        }
+        if super::permitted(worlds, auth, publisher, &[o.permission]).await? {
+            max_days = extend_max_days(max_days, o.max_days);
+        }
+    }
+    check_duration(dates, max_days)
+}
+
+/// Applies a matching override to the max days, where `0` is unlimited.
+#[inline]
+fn extend_max_days(max_days: u32, by: u32) -> u32 {
+    if max_days == 0 || by == 0 {
+        0
+    } else {
+        max_days.max(by)
+    }
+}
+
+/// Validates the time range against the max days, where `0` is unlimited.
+fn check_duration(dates: &RangeInclusive<Date>, max_days: u32) -> Result<(), Error> {
+    if dates.start() > dates.end() {
+        return Err(Error::InvalidDateRange);
+    }
+    if max_days > 0 && (*dates.end() - *dates.start()).whole_days() >= max_days as i64 {
+        return Err(Error::PostTooLong(max_days));
+    }
+    Ok(())
+}
+
+/// Gets the reviewer pool of a post by the publisher.
+fn route(config: &Config, publisher: &Account, post: &Post) -> Option<String> {
+    let departments = publisher.departments();
+    let departments: Vec<&str> = departments.iter().map(|d| d.as_ref()).collect();
+    config
+        .route(
+            post.category().map(String::as_str),
+            &departments,
+            &publisher.labels(),
+        )
+        .map(ToOwned::to_owned)
+}
+
+/// Gets ids of pending posts assigned to reviewer pools
+/// of the requesting account.
+#[utoipa::path(
+    get,
+    path = "/api/post/assigned",
+    tag = "post",
+    responses((status = 200, body = [u64])),
+    security(("token" = [])),
+)]
+pub async fn assigned<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<u64>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::Approve);
+    let pools = lazy.get().await?.role_names();
+
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let post = lazy.get().await?;
+        if post.state().status() == Status::Pending
+            && post.pool().map_or(false, |p| pools.iter().any(|r| r == p))
+        {
+            res.push(post.id());
+        }
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReviewPostReq {
+    pub post: u64,
+    /// Should be [`Status::Approved`], [`Status::Rejected`]
+    /// or [`Status::ChangesRequested`].
+    pub status: Status,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReviewPostRes {
+    /// Current status of the post.
+    pub status: Status,
+    /// Count of distinct approvers of the current review.
+    pub approvals: usize,
+}
+
+/// Approves or rejects a post.
+///
+/// A pending post is approved only if enough distinct approvers
+/// approved it, while a single rejection rejects it.
+#[utoipa::path(
+    post,
+    path = "/api/post/approve",
+    tag = "post",
+    request_body = ReviewPostReq,
+    responses((status = 200, body = ReviewPostRes)),
+    security(("token" = [])),
+)]
+pub async fn approve_post<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        config,
+        http_client,
+        ..
+    }): State<Global<Io>>,
+    Json(ReviewPostReq {
+        post,
+        status,
+        message,
+    }): Json<ReviewPostReq>,
+) -> Result<Json<ReviewPostRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Approve);
+    review(
+        &worlds,
+        &config,
+        &http_client,
+        auth.account,
+        post,
+        status,
+        message,
+    )
+    .await
+    .map(Json)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BatchReviewPostsReq {
+    pub posts: Vec<u64>,
+    /// Should be [`Status::Approved`], [`Status::Rejected`]
+    /// or [`Status::ChangesRequested`].
+    pub status: Status,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Result of reviewing a post in a batch.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BatchReviewPostRes {
+    pub post: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ReviewPostRes>,
+    /// Error of reviewing the post if failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Approves or rejects posts with one decision.
+///
+/// Each post is reviewed independently, so failures
+/// are reported per post without failing the batch.
+#[utoipa::path(
+    post,
+    path = "/api/post/approve-batch",
+    tag = "post",
+    request_body = BatchReviewPostsReq,
+    responses((status = 200, body = [BatchReviewPostRes])),
+    security(("token" = [])),
+)]
+pub async fn approve_batch<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        config,
+        http_client,
+        ..
+    }): State<Global<Io>>,
+    Json(BatchReviewPostsReq {
+        posts,
+        status,
+        message,
+    }): Json<BatchReviewPostsReq>,
+) -> Result<Json<Vec<BatchReviewPostRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Approve);
+
+    let mut res = Vec::with_capacity(posts.len());
+    for post in posts {
+        let result = review(
+            &worlds,
+            &config,
+            &http_client,
+            auth.account,
+            post,
+            status,
+            message.clone(),
+        )
+        .await;
+        res.push(match result {
+            Ok(result) => BatchReviewPostRes {
+                post,
+                result: Some(result),
+                error: None,
+            },
+            Err(err) => BatchReviewPostRes {
+                post,
+                result: None,
+                error: Some(err.to_string()),
+            },
+        });
+    }
+    Ok(Json(res))
+}
+
+/// Reviews a post as the reviewer and notifies webhooks
+/// if the post is approved or rejected.
+async fn review<Io: IoHandle>(
+    worlds: &Arc<Worlds<Io>>,
+    config: &Config,
+    http_client: &reqwest::Client,
+    reviewer: u64,
+    post: u64,
+    status: Status,
+    message: String,
+) -> Result<ReviewPostRes, Error> {
+    let select = sp!(worlds, post);
+    let mut lazy = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let p = lazy.get_mut().await?;
+    match status {
+        // Publishers could not approve their own posts.
+        Status::Approved if p.is_publisher(reviewer) => return Err(Error::PermissionDenied),
+        Status::Approved if p.state().status() == Status::Pending => {
+            p.approve(reviewer, config.review.approvals, message);
+        }
+        Status::Rejected if !p.is_draft() => p.push_state(status, reviewer, message),
+        Status::ChangesRequested if p.state().status() == Status::Pending => {
+            p.push_state(status, reviewer, message)
+        }
+        _ => return Err(Error::PermissionDenied),
+    }
+    let res = ReviewPostRes {
+        status: p.state().status(),
+        approvals: p.approvals().len(),
+    };
+    super::record_audit(worlds, reviewer, Action::ReviewPost { post, status }).await?;
+    let kind = match res.status {
+        Status::Approved => Some(EventKind::Approved),
+        Status::Rejected => Some(EventKind::Rejected),
+        Status::ChangesRequested => Some(EventKind::ChangesRequested),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        super::webhook::dispatch(worlds.clone(), http_client.clone(), Event::new(kind, post));
+    }
+    Ok(res)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AmendReviewReq {
+    pub post: u64,
+    /// Reverses the decision if present, should be
+    /// [`Status::Approved`] or [`Status::Rejected`].
+    #[serde(default)]
+    pub status: Option<Status>,
+    pub message: String,
+}
+
+/// Amends the message or reverses the last decision of a reviewed post
+/// by appending a correcting state.
+///
+/// Only the original reviewer or an [`Permission::Op`] could amend
+/// the decision, and only an [`Permission::Op`] could reverse a
+/// rejection into an approval, which bypasses the approval quorum.
+/// Publishers could not amend their own posts to approved.
+#[utoipa::path(
+    post,
+    path = "/api/post/amend-review",
+    tag = "post",
+    request_body = AmendReviewReq,
+    responses((status = 200, body = ReviewPostRes)),
+    security(("token" = [])),
+)]
+pub async fn amend_review<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        http_client,
+        ..
+    }): State<Global<Io>>,
+    Json(AmendReviewReq {
+        post,
+        status,
+        message,
+    }): Json<AmendReviewReq>,
+) -> Result<Json<ReviewPostRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select, worlds => Permission::Approve);
+
+    let select = sp!(worlds, post);
+    let mut lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let p = lazy_post.get_mut().await?;
+    if !matches!(
+        p.state().status(),
+        Status::Approved | Status::Rejected | Status::Active
+    ) {
+        return Err(Error::PermissionDenied);
+    }
+    let decision = p.last_decision().ok_or(Error::PermissionDenied)?;
+    let (reviewer, decided) = (decision.operator(), decision.status());
+    let status = status.unwrap_or(decided);
+    if !matches!(status, Status::Approved | Status::Rejected)
+        || (status == Status::Approved && p.is_publisher(auth.account))
+    {
+        return Err(Error::PermissionDenied);
+    }
+    let requires_op =
+        reviewer != auth.account || (decided, status) == (Status::Rejected, Status::Approved);
+    if requires_op
+        && !super::permitted(&worlds, &auth, lazy.get().await?, &[Permission::Op]).await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+
+    p.push_state(status, auth.account, message);
+    p.transition(OffsetDateTime::now_utc().date());
+    let res = ReviewPostRes {
+        status: p.state().status(),
+        approvals: p.approvals().len(),
+    };
+    super::record_audit(&worlds, auth.account, Action::AmendReview { post, status }).await?;
+    if status != decided {
+        let kind = if status == Status::Approved {
+            EventKind::Approved
+        } else {
+            EventKind::Rejected
+        };
+        super::webhook::dispatch(worlds, http_client, Event::new(kind, post));
+    }
+    Ok(Json(res))
+}
+
+/// Builds the search index from all posts.
+pub async fn build_index<Io: IoHandle>(worlds: &Worlds<Io>) -> Result<search::Index, Error> {
+    let mut index = search::Index::new();
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        let post = lazy.get().await?;
+        index.insert(post.id(), post.title(), post.description());
+    }
+    Ok(index)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DeletePostReq {
+    pub post: u64,
+}
+
+/// Deletes a post and releases its resources.
+///
+/// Posts of other accounts could be deleted with
+/// [`Permission::ManagePosts`].
+#[utoipa::path(
+    post,
+    path = "/api/post/delete",
+    tag = "post",
+    request_body = DeletePostReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn delete<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, post_index, ..
+    }): State<Global<Io>>,
+    Json(DeletePostReq { post }): Json<DeletePostReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let creator = lazy_post.get().await?.creator();
+    if creator != auth.account
+        && !super::permitted(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[Permission::ManagePosts],
+        )
+        .await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+
+    let deleted = lazy_post.destroy().await?;
+    post_index.write().await.remove(post);
+    comment::delete_all(&worlds, post).await?;
+    for id in deleted
+        .images()
+        .iter()
+        .chain(deleted.attachments())
+        .chain(deleted.videos())
+    {
+        super::resource::set_used(&worlds, *id, false, None).await?;
+    }
+    super::record_audit(&worlds, auth.account, Action::DeletePost { post, creator }).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReorderPostsReq {
+    /// Ids of active posts, from the most important.
+    pub posts: Vec<u64>,
+}
+
+/// Reorders currently active posts by setting their priorities.
+///
+/// Active posts not in the list are reset to the lowest priority.
+#[utoipa::path(
+    post,
+    path = "/api/post/reorder",
+    tag = "post",
+    request_body = ReorderPostsReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn reorder<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(ReorderPostsReq { posts }): Json<ReorderPostsReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+
+    let select = worlds.post.select(0, ..);
+    let mut active = vec![];
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy.get().await?.is_active() {
+            active.push(lazy);
+        }
+    }
+    if let Some(id) = posts
+        .iter()
+        .find(|id| !active.iter().any(|l| l.id() == **id))
+    {
+        return Err(Error::PostNotFound(*id));
+    }
+    for mut lazy in active {
+        let priority = posts
+            .iter()
+            .position(|id| *id == lazy.id())
+            .map_or(0, |i| (posts.len() - i) as i64);
+        lazy.get_mut().await?.set_priority(priority);
+    }
+    super::record_audit(&worlds, auth.account, Action::ReorderPosts { posts }).await
+}
+
+/// Publishes approved posts whose on-screen time range has
+/// started, and archives posts whose range has ended.
+///
+/// This should be run periodically in background.
+pub async fn schedule<Io: IoHandle>(
+    worlds: &Arc<Worlds<Io>>,
+    http_client: &reqwest::Client,
+) -> Result<(), Error> {
+    let today = OffsetDateTime::now_utc().date();
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        if matches!(post.state().status(), Status::Approved | Status::Active)
+            && lazy.get_mut().await?.transition(today) == Some(Status::Archived)
+        {
+            super::webhook::dispatch(
+                worlds.clone(),
+                http_client.clone(),
+                Event::new(EventKind::Archived, lazy.id()),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PostHistoryReq {
+    pub post: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PostHistoryRes {
+    pub revisions: Vec<post::Revision>,
+    /// Time of the last review of the requesting account.
+    #[serde(with = "time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub last_reviewed: Option<OffsetDateTime>,
+}
+
+/// Gets revisions of a post, for the publisher and reviewers.
+#[utoipa::path(
+    post,
+    path = "/api/post/history",
+    tag = "post",
+    request_body = PostHistoryReq,
+    responses((status = 200, body = PostHistoryRes)),
+    security(("token" = [])),
+)]
+pub async fn history<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PostHistoryReq { post }): Json<PostHistoryReq>,
+) -> Result<Json<PostHistoryRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let post = lazy_post.get().await?;
+    if !post.is_publisher(auth.account)
+        && (post.is_draft()
+            || !super::permitted(&worlds, &auth, lazy.get().await?, &[Permission::Approve]).await?)
+    {
+        return Err(Error::PermissionDenied);
+    }
+    Ok(Json(PostHistoryRes {
+        revisions: post.revisions().to_vec(),
+        last_reviewed: post
+            .states()
+            .iter()
+            .rev()
+            .find(|s| {
+                s.operator() == auth.account
+                    && matches!(
+                        s.status(),
+                        Status::Approved | Status::Rejected | Status::ChangesRequested
+                    )
+            })
+            .map(post::State::time),
+    }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PostStatsReq {
+    pub post: u64,
+    #[serde(default)]
+    pub from: Option<Date>,
+    #[serde(default)]
+    pub to: Option<Date>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PostStatsRes {
+    pub date: Date,
+    pub impressions: u64,
+    pub plays: u64,
+}
+
+/// Gets daily display counts of a post in date order,
+/// for the publisher and admins.
+#[utoipa::path(
+    post,
+    path = "/api/post/stats",
+    tag = "post",
+    request_body = PostStatsReq,
+    responses((status = 200, body = [PostStatsRes])),
+    security(("token" = [])),
+)]
+pub async fn stats<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PostStatsReq { post, from, to }): Json<PostStatsReq>,
+) -> Result<Json<Vec<PostStatsRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    if !lazy_post.get().await?.is_publisher(auth.account)
+        && !super::permitted(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[Permission::ManagePosts],
+        )
+        .await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+    if from.zip(to).map_or(false, |(f, t)| f > t) {
+        return Err(Error::InvalidDateRange);
+    }
+
+    let select = worlds.stats.select(1, post);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let stats = lazy.get().await?;
+        let date = stats.date();
+        if stats.post() == post
+            && from.map_or(true, |f| date >= f)
+            && to.map_or(true, |t| date <= t)
+        {
+            res.push(PostStatsRes {
+                date,
+                impressions: stats.impressions(),
+                plays: stats.plays(),
+            });
+        }
+    }
+    res.sort_unstable_by_key(|s| s.date);
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct StatsSummaryReq {
+    /// Only counts posts created at or after this date.
+    #[serde(default)]
+    pub from: Option<Date>,
+    /// Only counts posts created at or before this date.
+    #[serde(default)]
+    pub to: Option<Date>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StatsSummaryRes {
+    pub total: usize,
+    pub statuses: Vec<StatusCount>,
+    /// Average seconds from submission to approval of approved posts,
+    /// `None` if no posts were approved.
+    pub approval_latency: Option<f64>,
+    /// Counts by departments of publishers.
+    pub departments: Vec<GroupCount>,
+    /// Counts by labels of publishers, e.g. houses.
+    pub labels: Vec<GroupCount>,
+    /// Counts by ISO weeks of creation, in time order.
+    pub weeks: Vec<WeekCount>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StatusCount {
+    pub status: Status,
+    pub count: usize,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GroupCount {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WeekCount {
+    pub year: i32,
+    pub week: u8,
+    pub count: usize,
+}
+
+/// Summarizes posts for reporting throughput, excluding drafts.
+#[utoipa::path(
+    post,
+    path = "/api/post/stats/summary",
+    tag = "post",
+    request_body = StatsSummaryReq,
+    responses((status = 200, body = StatsSummaryRes)),
+    security(("token" = [])),
+)]
+pub async fn stats_summary<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(StatsSummaryReq { from, to }): Json<StatsSummaryReq>,
+) -> Result<Json<StatsSummaryRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+    if from.zip(to).map_or(false, |(f, t)| f > t) {
+        return Err(Error::InvalidDateRange);
+    }
+
+    let mut total = 0;
+    let mut statuses: Vec<StatusCount> = vec![];
+    let (mut latency_sum, mut approved) = (0i64, 0usize);
+    let mut departments: BTreeMap<String, usize> = BTreeMap::new();
+    let mut labels: BTreeMap<String, usize> = BTreeMap::new();
+    let mut weeks: BTreeMap<(i32, u8), usize> = BTreeMap::new();
+    let mut publishers: HashMap<u64, (Vec<String>, Vec<String>)> = HashMap::new();
+
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        let created = post.created_at().date();
+        if post.is_draft()
+            || from.map_or(false, |f| created < f)
+            || to.map_or(false, |t| created > t)
+        {
+            continue;
+        }
+        total += 1;
+        let status = post.state().status();
+        if let Some(c) = statuses.iter_mut().find(|c| c.status == status) {
+            c.count += 1;
+        } else {
+            statuses.push(StatusCount { status, count: 1 });
+        }
+        if let Some(latency) = approval_latency(post) {
+            latency_sum += latency;
+            approved += 1;
+        }
+        let (year, week, _) = created.to_iso_week_date();
+        *weeks.entry((year, week)).or_default() += 1;
+
+        let creator = post.creator();
+        if !publishers.contains_key(&creator) {
+            let select = sa!(worlds.account, creator);
+            let groups = if let Some(lazy) = ga!(select, creator) {
+                let account = lazy.get().await?;
+                (
+                    account
+                        .departments()
+                        .iter()
+                        .map(|d| d.as_ref().to_owned())
+                        .collect(),
+                    account.labels(),
+                )
+            } else {
+                Default::default()
+            };
+            publishers.insert(creator, groups);
+        }
+        let (ds, ls) = &publishers[&creator];
+        for d in ds {
+            *departments.entry(d.clone()).or_default() += 1;
+        }
+        for l in ls {
+            *labels.entry(l.clone()).or_default() += 1;
+        }
+    }
+
+    let groups = |map: BTreeMap<String, usize>| -> Vec<GroupCount> {
+        map.into_iter()
+            .map(|(name, count)| GroupCount { name, count })
+            .collect()
+    };
+    Ok(Json(StatsSummaryRes {
+        total,
+        statuses,
+        approval_latency: (approved > 0).then(|| latency_sum as f64 / approved as f64),
+        departments: groups(departments),
+        labels: groups(labels),
+        weeks: weeks
+            .into_iter()
+            .map(|((year, week), count)| WeekCount { year, week, count })
+            .collect(),
+    }))
+}
+
+/// Seconds from the last submission to the first approval of a post.
+fn approval_latency(post: &Post) -> Option<i64> {
+    let states = post.states();
+    let approved = states.iter().position(|s| s.status() == Status::Approved)?;
+    let submitted = states[..approved]
+        .iter()
+        .rev()
+        .find(|s| s.status() == Status::Pending)?;
+    Some((states[approved].time() - submitted.time()).whole_seconds())
+}
+
+/// Emails publishers of approved or active posts which go off screen
+/// within [`sms3_backend::config::ExpiryReminder::days`].
+///
+/// This should be run periodically in background.
+pub async fn remind_expiries<Io, E>(
+    worlds: &Worlds<Io>,
+    config: &Config,
+    transport: &lettre::AsyncSmtpTransport<E>,
+) -> Result<(), Error>
+where
+    Io: IoHandle,
+    E: lettre::Executor,
+    lettre::AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = lettre::transport::smtp::Error>,
+{
+    let days = config.expiry_reminder.days;
+    if days == 0 {
+        return Ok(());
+    }
+    let today = OffsetDateTime::now_utc().date();
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        let last = post.last_date();
+        if post.is_reminded()
+            || !matches!(post.state().status(), Status::Approved | Status::Active)
+            || last < today
+            || (last - today).whole_days() > days as i64
+        {
+            continue;
+        }
+
+        let select = sa!(worlds.account, post.creator());
+        if let Some(account) = ga!(select, post.creator()) {
+            let account = account.get().await?;
+            if let (true, Ok(to)) = (account.notify_prefs().on_expiry, account.email().parse()) {
+                let body = format!(
+                    "Your post \"{}\" will go off screen after {last}.\n\n\
+                    You could extend or replace it before then.",
+                    post.title(),
+                );
+                if let Err(err) = sms3_backend::mail::send(
+                    &config.smtp,
+                    to,
+                    "Your SubIT post is expiring",
+                    body,
+                    transport,
+                )
+                .await
+                {
+                    tracing::warn!("failed to send expiry reminder: {err}");
+                    continue;
+                }
+            }
+        }
+        lazy.get_mut().await?.set_reminded();
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BookmarkReq {
+    pub post: u64,
+    pub bookmarked: bool,
+}
+
+/// Bookmarks or unbookmarks a visible post.
+#[utoipa::path(
+    post,
+    path = "/api/post/bookmark",
+    tag = "post",
+    request_body = BookmarkReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn bookmark<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(BookmarkReq { post, bookmarked }): Json<BookmarkReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let mut lazy = va!(auth, select);
+    if bookmarked {
+        let visibility = Visibility::new(&worlds, &auth, lazy.get().await?).await?;
+        let select = sp!(worlds, post);
+        let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+        if !visibility.can_see(lazy_post.get().await?) {
+            return Err(Error::PostNotFound(post));
+        }
+    }
+    lazy.get_mut().await?.set_bookmark(post, bookmarked);
+    Ok(())
+}
+
+/// Gets ids of bookmarked posts.
+#[utoipa::path(
+    get,
+    path = "/api/post/bookmarks",
+    tag = "post",
+    responses((status = 200, body = [u64])),
+    security(("token" = [])),
+)]
+pub async fn bookmarks<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<u64>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    Ok(Json(
+        lazy.get().await?.bookmarks().iter().copied().collect(),
+    ))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PinPostReq {
+    pub post: u64,
+    /// Pins the post if `true`, unpins it otherwise.
+    pub pinned: bool,
+    /// Expiry of the pin, pinned until unpinned if not present.
+    #[serde(default, with = "time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub until: Option<OffsetDateTime>,
+}
+
+/// Pins or unpins an approved or active post, forcing it to the top
+/// of screens and post lists while pinned.
+#[utoipa::path(
+    post,
+    path = "/api/post/pin",
+    tag = "post",
+    request_body = PinPostReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn pin<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PinPostReq {
+        post,
+        pinned,
+        until,
+    }): Json<PinPostReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+    if until.map_or(false, |u| u <= OffsetDateTime::now_utc()) {
+        return Err(Error::InvalidTimestamp);
+    }
+
+    let select = sp!(worlds, post);
+    let mut lazy = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    let p = lazy.get_mut().await?;
+    if pinned && !matches!(p.state().status(), Status::Approved | Status::Active) {
+        return Err(Error::PermissionDenied);
+    }
+    p.set_pin(pinned.then_some(post::Pin {
+        operator: auth.account,
+        until,
+    }));
+    super::record_audit(&worlds, auth.account, Action::PinPost { post, pinned }).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ArchivePostReq {
+    pub post: u64,
+    /// Archives the post if `true`, unarchives it otherwise.
+    pub archived: bool,
+}
+
+/// Archives an approved or active post before its end date, or
+/// unarchives a post that is not past its last date.
+///
+/// Posts of other accounts could be archived with
+/// [`Permission::ManagePosts`].
+#[utoipa::path(
+    post,
+    path = "/api/post/archive",
+    tag = "post",
+    request_body = ArchivePostReq,
+    responses((status = 200, body = ReviewPostRes)),
+    security(("token" = [])),
+)]
+pub async fn archive<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        http_client,
+        ..
+    }): State<Global<Io>>,
+    Json(ArchivePostReq { post, archived }): Json<ArchivePostReq>,
+) -> Result<Json<ReviewPostRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let mut lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    if !lazy_post.get().await?.is_publisher(auth.account)
+        && !super::permitted(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[Permission::ManagePosts],
+        )
+        .await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    let p = lazy_post.get_mut().await?;
+    match p.state().status() {
+        Status::Approved | Status::Active if archived => {
+            p.push_state(Status::Archived, auth.account, "archived".to_owned())
+        }
+        Status::Archived if !archived && p.last_date() >= today => {
+            p.push_state(Status::Approved, auth.account, "unarchived".to_owned());
+            p.transition(today);
+        }
+        _ => return Err(Error::PermissionDenied),
+    }
+    let res = ReviewPostRes {
+        status: p.state().status(),
+        approvals: p.approvals().len(),
+    };
+    super::record_audit(
+        &worlds,
+        auth.account,
+        Action::ArchivePost { post, archived },
+    )
+    .await?;
+    if archived {
+        super::webhook::dispatch(worlds, http_client, Event::new(EventKind::Archived, post));
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CalendarQuery {
+    /// Only includes posts in the category.
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// Gets an iCalendar feed of display ranges and recurrence windows
+/// of approved and active posts visible to everyone.
+///
+/// This requires no authorization, so calendar apps could subscribe to it.
+#[utoipa::path(
+    get,
+    path = "/api/post/calendar.ics",
+    tag = "post",
+    params(CalendarQuery),
+    responses(
+        (status = 200, description = "ICS calendar", content_type = "text/calendar"),
+    ),
+)]
+pub async fn calendar<Io: IoHandle>(
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Query(CalendarQuery { category }): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    let mut posts = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        if matches!(post.state().status(), Status::Approved | Status::Active)
+            && *post.scope() == Scope::Everyone
+            && category
+                .as_ref()
+                .map_or(true, |c| post.category() == Some(c))
+        {
+            posts.push(lazy);
+        }
+    }
+
+    let mut events = vec![];
+    for lazy in posts.iter() {
+        let post = lazy.get().await?;
+        for (i, dates) in std::iter::once(post.dates())
+            .chain(post.windows())
+            .enumerate()
+        {
+            events.push(post::ical::Event {
+                uid: format!("{}-{i}@sms3", post.id()),
+                summary: post.title(),
+                description: post.description(),
+                start: *dates.start(),
+                end: *dates.end(),
+                stamp: post.state().time(),
+            });
+        }
+    }
+    let name = category.as_deref().unwrap_or("Posts");
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        post::ical::calendar(name, events),
+    ))
+}
+
+/// Gets an Atom feed of recently accepted posts visible to everyone,
+/// from the most recent.
+///
+/// This requires no authorization, so websites could syndicate it.
+#[utoipa::path(
+    get,
+    path = "/api/post/feed",
+    tag = "post",
+    responses(
+        (status = 200, description = "Atom feed", content_type = "application/atom+xml"),
+    ),
+)]
+pub async fn feed<Io: IoHandle>(
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+) -> Result<impl IntoResponse, Error> {
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    let mut posts = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let Ok(post) = lazy.get().await else {
+            continue;
+        };
+        if matches!(post.state().status(), Status::Approved | Status::Active)
+            && *post.scope() == Scope::Everyone
+        {
+            if let Some(accepted) = post.last_decision().map(post::State::time) {
+                posts.push((accepted, lazy));
+            }
+        }
+    }
+    posts.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+    posts.truncate(config.feed.limit);
+
+    let url = config.feed.url.trim_end_matches('/');
+    let mut entries = Vec::with_capacity(posts.len());
+    for (accepted, lazy) in posts.iter() {
+        let post = lazy.get().await?;
+        entries.push(post::feed::Entry {
+            id: post.id(),
+            title: post.title(),
+            summary: post.description(),
+            updated: *accepted,
+            images: post
+                .images()
+                .iter()
+                .map(|image| format!("{url}/api/post/public-image/{}/{image}", post.id()))
+                .collect(),
+        });
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        post::feed::atom(&config.feed.title, &format!("{url}/api/post/feed"), entries),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jan(day: u8) -> Date {
+        Date::from_calendar_date(2024, time::Month::January, day).unwrap()
+    }
+
+    #[test]
+    fn parse_range_bounds() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some((0, 99))));
+        assert_eq!(parse_range("bytes=900-", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range(" bytes=10-10 ", 1000), Ok(Some((10, 10))));
+    }
+
+    #[test]
+    fn parse_range_clamps() {
+        assert_eq!(parse_range("bytes=0-5000", 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some((0, 999))));
+    }
+
+    #[test]
+    fn parse_range_ignored() {
+        for value in [
+            "items=0-99",
+            "bytes=0-1,2-3",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=0",
+            "bytes=-a",
+        ] {
+            assert_eq!(parse_range(value, 1000), Ok(None), "{value}");
+        }
+    }
+
+    #[test]
+    fn duration_limits() {
+        // Both the start and end date are counted.
+        assert!(check_duration(&(jan(1)..=jan(7)), 7).is_ok());
+        assert!(matches!(
+            check_duration(&(jan(1)..=jan(8)), 7),
+            Err(Error::PostTooLong(7))
+        ));
+        assert!(check_duration(&(jan(1)..=jan(1)), 1).is_ok());
+        assert!(check_duration(&(jan(1)..=jan(31)), 0).is_ok());
+        assert!(matches!(
+            check_duration(&(jan(2)..=jan(1)), 0),
+            Err(Error::InvalidDateRange)
+        ));
+    }
+
+    #[test]
+    fn duration_overrides() {
+        assert_eq!(extend_max_days(7, 30), 30);
+        assert_eq!(extend_max_days(30, 7), 30);
+        assert_eq!(extend_max_days(7, 0), 0);
+        assert_eq!(extend_max_days(0, 30), 0);
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+        assert_eq!(parse_range("bytes=0-", 0), Err(()));
+    }
+}
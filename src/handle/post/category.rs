@@ -0,0 +1,128 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{account::Permission, audit::Action, post::category::Category, Error};
+
+use crate::{Auth, Global, Worlds};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PutCategoryReq {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Creates or updates a category.
+#[utoipa::path(
+    post,
+    path = "/api/post/categories",
+    tag = "post",
+    request_body = PutCategoryReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn put<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PutCategoryReq { name, description }): Json<PutCategoryReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+
+    let action = Action::PutCategory { name: name.clone() };
+    let id = Category::id_of(&name);
+    let select = worlds.category.select(0, id).hint(id);
+    if let Some(mut lazy) = ga!(select, id) {
+        lazy.get_mut().await?.set_description(description);
+    } else {
+        worlds
+            .category
+            .try_insert(Category::new(name, description))
+            .await
+            .map_err(|_| Error::PermissionDenied)?;
+    }
+    crate::handle::record_audit(&worlds, auth.account, action).await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CategoryRes {
+    pub name: String,
+    pub description: String,
+}
+
+/// Gets all categories.
+#[utoipa::path(
+    get,
+    path = "/api/post/categories",
+    tag = "post",
+    responses((status = 200, body = [CategoryRes])),
+    security(("token" = [])),
+)]
+pub async fn get_categories<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<CategoryRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let select = worlds.category.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let category = lazy.get().await?;
+        res.push(CategoryRes {
+            name: category.name().to_owned(),
+            description: category.description().to_owned(),
+        });
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DeleteCategoryReq {
+    pub name: String,
+}
+
+/// Deletes a category.
+///
+/// Posts in this category are kept as uncategorized.
+#[utoipa::path(
+    post,
+    path = "/api/post/categories/delete",
+    tag = "post",
+    request_body = DeleteCategoryReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn delete<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(DeleteCategoryReq { name }): Json<DeleteCategoryReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+
+    let id = Category::id_of(&name);
+    let select = worlds.category.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or_else(|| Error::CategoryNotFound(name.clone()))?;
+    lazy.destroy().await?;
+
+    let select = worlds.post.select(0, ..);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.get().await?.category() == Some(&name) {
+            lazy.get_mut().await?.set_category(None);
+        }
+    }
+    crate::handle::record_audit(&worlds, auth.account, Action::DeleteCategory { name }).await
+}
+
+/// Validates that the category exists.
+pub async fn validate<Io: IoHandle>(worlds: &Worlds<Io>, name: &str) -> Result<(), Error> {
+    let id = Category::id_of(name);
+    let select = worlds.category.select(0, id).hint(id);
+    if ga!(select, id).is_none() {
+        return Err(Error::CategoryNotFound(name.to_owned()));
+    }
+    Ok(())
+}
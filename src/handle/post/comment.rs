@@ -0,0 +1,223 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::{Account, Permission},
+    post::{comment::Comment, Post},
+    Error,
+};
+use time::OffsetDateTime;
+
+use crate::{Auth, Global, Worlds};
+
+/// Whether the account could discuss on the post, as the
+/// publisher, a co-publisher, a reviewer or an admin.
+async fn can_discuss<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    auth: &Auth,
+    account: &Account,
+    post: &Post,
+) -> Result<bool, Error> {
+    if post.is_publisher(auth.account) {
+        return Ok(true);
+    }
+    Ok(!post.is_draft()
+        && (crate::handle::permitted(worlds, auth, account, &[Permission::Approve]).await?
+            || crate::handle::permitted(worlds, auth, account, &[Permission::ManagePosts]).await?))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateCommentReq {
+    pub post: u64,
+    /// The comment to reply to.
+    #[serde(default)]
+    pub parent: Option<u64>,
+    pub content: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CommentIdRes {
+    pub id: u64,
+}
+
+/// Comments on a post.
+#[utoipa::path(
+    post,
+    path = "/api/post/comment/create",
+    tag = "post",
+    request_body = CreateCommentReq,
+    responses((status = 200, body = CommentIdRes)),
+    security(("token" = [])),
+)]
+pub async fn create<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(CreateCommentReq {
+        post,
+        parent,
+        content,
+    }): Json<CreateCommentReq>,
+) -> Result<Json<CommentIdRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    if content.chars().count() > Comment::MAX_LEN {
+        return Err(Error::CommentTooLong);
+    }
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    if !can_discuss(&worlds, &auth, lazy.get().await?, lazy_post.get().await?).await? {
+        return Err(Error::PermissionDenied);
+    }
+    if let Some(parent) = parent {
+        let select = worlds.comment.select(0, parent).hint(parent);
+        let lazy = ga!(select, parent).ok_or(Error::CommentNotFound(parent))?;
+        if lazy.get().await?.post() != post {
+            return Err(Error::CommentNotFound(parent));
+        }
+    }
+
+    let comment = Comment::new(post, parent, auth.account, content);
+    let id = comment.id();
+    worlds.comment.insert(comment).await?;
+    Ok(Json(CommentIdRes { id }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct GetCommentsReq {
+    pub post: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CommentRes {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub author: u64,
+    pub content: String,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub time: OffsetDateTime,
+}
+
+/// Gets all comments of a post in time order.
+#[utoipa::path(
+    post,
+    path = "/api/post/comment/get",
+    tag = "post",
+    request_body = GetCommentsReq,
+    responses((status = 200, body = [CommentRes])),
+    security(("token" = [])),
+)]
+pub async fn get_comments<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(GetCommentsReq { post }): Json<GetCommentsReq>,
+) -> Result<Json<Vec<CommentRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = sp!(worlds, post);
+    let lazy_post = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    if !can_discuss(&worlds, &auth, lazy.get().await?, lazy_post.get().await?).await? {
+        return Err(Error::PermissionDenied);
+    }
+
+    let select = worlds.comment.select(1, post);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let comment = lazy.get().await?;
+        if comment.post() == post {
+            res.push(CommentRes {
+                id: comment.id(),
+                parent: comment.parent(),
+                author: comment.author(),
+                content: comment.content().to_owned(),
+                time: comment.time(),
+            });
+        }
+    }
+    res.sort_unstable_by_key(|c| c.time);
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DeleteCommentReq {
+    pub post: u64,
+    pub comment: u64,
+}
+
+/// Deletes a comment and all replies to it.
+///
+/// Comments of other accounts could be deleted with
+/// [`Permission::ManagePosts`].
+#[utoipa::path(
+    post,
+    path = "/api/post/comment/delete",
+    tag = "post",
+    request_body = DeleteCommentReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn delete<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(DeleteCommentReq { post, comment }): Json<DeleteCommentReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    let select = worlds.comment.select(0, comment).hint(comment);
+    let lazy_comment = ga!(select, comment).ok_or(Error::CommentNotFound(comment))?;
+    let c = lazy_comment.get().await?;
+    if c.post() != post {
+        return Err(Error::CommentNotFound(comment));
+    }
+    if c.author() != auth.account
+        && !crate::handle::permitted(
+            &worlds,
+            &auth,
+            lazy.get().await?,
+            &[Permission::ManagePosts],
+        )
+        .await?
+    {
+        return Err(Error::PermissionDenied);
+    }
+    lazy_comment.destroy().await?;
+    delete_replies(&worlds, post, vec![comment]).await
+}
+
+/// Deletes replies to the deleted comments of a post recursively.
+pub async fn delete_replies<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    post: u64,
+    mut deleted: Vec<u64>,
+) -> Result<(), Error> {
+    while !deleted.is_empty() {
+        let select = worlds.comment.select(1, post);
+        let mut iter = select.iter();
+        let mut replies = vec![];
+        while let Some(Ok(lazy)) = iter.next().await {
+            let c = lazy.get().await?;
+            if c.post() == post && c.parent().map_or(false, |p| deleted.contains(&p)) {
+                replies.push(c.id());
+                lazy.destroy().await?;
+            }
+        }
+        deleted = replies;
+    }
+    Ok(())
+}
+
+/// Deletes all comments of a post.
+pub async fn delete_all<Io: IoHandle>(worlds: &Worlds<Io>, post: u64) -> Result<(), Error> {
+    let select = worlds.comment.select(1, post);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy.get().await?.post() == post {
+            lazy.destroy().await?;
+        }
+    }
+    Ok(())
+}
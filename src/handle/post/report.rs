@@ -0,0 +1,134 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::Permission,
+    audit::Action,
+    post::report::{Reason, Report},
+    Error,
+};
+use time::OffsetDateTime;
+
+use crate::{Auth, Global};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReportPostReq {
+    pub post: u64,
+    pub reason: Reason,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Flags a published post for moderation.
+#[utoipa::path(
+    post,
+    path = "/api/post/report",
+    tag = "post",
+    request_body = ReportPostReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn report<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(ReportPostReq {
+        post,
+        reason,
+        message,
+    }): Json<ReportPostReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::GetPubPosts);
+    if message.chars().count() > Report::MAX_MESSAGE_LEN {
+        return Err(Error::MessageTooLong);
+    }
+
+    let select = sp!(worlds, post);
+    let lazy = ga!(select, post).ok_or(Error::PostNotFound(post))?;
+    if !lazy.get().await?.is_active() {
+        return Err(Error::PostNotFound(post));
+    }
+    worlds
+        .report
+        .insert(Report::new(post, auth.account, reason, message))
+        .await?;
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReportRes {
+    pub id: u64,
+    pub post: u64,
+    pub reporter: u64,
+    pub reason: Reason,
+    pub message: String,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub time: OffsetDateTime,
+}
+
+/// Gets all reports waiting for moderation in time order.
+#[utoipa::path(
+    get,
+    path = "/api/post/reports",
+    tag = "post",
+    responses((status = 200, body = [ReportRes])),
+    security(("token" = [])),
+)]
+pub async fn reports<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<ReportRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Approve);
+
+    let select = worlds.report.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let report = lazy.get().await?;
+        res.push(ReportRes {
+            id: report.id(),
+            post: report.post(),
+            reporter: report.reporter(),
+            reason: report.reason(),
+            message: report.message().to_owned(),
+            time: report.time(),
+        });
+    }
+    res.sort_unstable_by_key(|r| r.time);
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ResolveReportReq {
+    pub report: u64,
+}
+
+/// Resolves a report by removing it from the queue.
+#[utoipa::path(
+    post,
+    path = "/api/post/report/resolve",
+    tag = "post",
+    request_body = ResolveReportReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn resolve<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(ResolveReportReq { report }): Json<ResolveReportReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Approve);
+
+    let select = worlds.report.select(0, report).hint(report);
+    let lazy = ga!(select, report).ok_or(Error::ReportNotFound(report))?;
+    let post = lazy.destroy().await?.post();
+    crate::handle::record_audit(
+        &worlds,
+        auth.account,
+        Action::ResolveReport { report, post },
+    )
+    .await
+}
@@ -0,0 +1,157 @@
+use axum::{extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::Permission,
+    audit::Action,
+    post::template::{Field, Template},
+    Error,
+};
+
+use crate::{Auth, Global, Worlds};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PutTemplateReq {
+    pub name: String,
+    /// Pattern of titles, containing `{title}` for the given title.
+    pub title_pattern: String,
+    #[serde(default)]
+    pub required: Vec<Field>,
+    /// Default category of posts.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Default on-screen days of posts.
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+/// Creates or updates a template.
+#[utoipa::path(
+    post,
+    path = "/api/post/templates",
+    tag = "post",
+    request_body = PutTemplateReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn put<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(PutTemplateReq {
+        name,
+        title_pattern,
+        required,
+        category,
+        days,
+    }): Json<PutTemplateReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+    if let Some(ref category) = category {
+        super::category::validate(&worlds, category).await?;
+    }
+    if days == Some(0) {
+        return Err(Error::InvalidDateRange);
+    }
+
+    let action = Action::PutTemplate { name: name.clone() };
+    let id = Template::id_of(&name);
+    let select = worlds.template.select(0, id).hint(id);
+    if let Some(mut lazy) = ga!(select, id) {
+        let template = lazy.get_mut().await?;
+        template.set_title_pattern(title_pattern);
+        template.set_required(required);
+        template.set_category(category);
+        template.set_days(days);
+    } else {
+        let mut template = Template::new(name, title_pattern);
+        template.set_required(required);
+        template.set_category(category);
+        template.set_days(days);
+        worlds
+            .template
+            .try_insert(template)
+            .await
+            .map_err(|_| Error::PermissionDenied)?;
+    }
+    crate::handle::record_audit(&worlds, auth.account, action).await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TemplateRes {
+    pub name: String,
+    pub title_pattern: String,
+    pub required: Vec<Field>,
+    pub category: Option<String>,
+    pub days: Option<u32>,
+}
+
+/// Gets all templates.
+#[utoipa::path(
+    get,
+    path = "/api/post/templates",
+    tag = "post",
+    responses((status = 200, body = [TemplateRes])),
+    security(("token" = [])),
+)]
+pub async fn get_templates<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<TemplateRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let select = worlds.template.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let template = lazy.get().await?;
+        res.push(TemplateRes {
+            name: template.name().to_owned(),
+            title_pattern: template.title_pattern().to_owned(),
+            required: template.required().to_vec(),
+            category: template.category().cloned(),
+            days: template.days(),
+        });
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DeleteTemplateReq {
+    pub name: String,
+}
+
+/// Deletes a template.
+///
+/// Posts created from this template are kept.
+#[utoipa::path(
+    post,
+    path = "/api/post/templates/delete",
+    tag = "post",
+    request_body = DeleteTemplateReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn delete<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(DeleteTemplateReq { name }): Json<DeleteTemplateReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManagePosts);
+
+    let id = Template::id_of(&name);
+    let select = worlds.template.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or_else(|| Error::TemplateNotFound(name.clone()))?;
+    lazy.destroy().await?;
+    crate::handle::record_audit(&worlds, auth.account, Action::DeleteTemplate { name }).await
+}
+
+/// Gets a template by name.
+pub async fn get<Io: IoHandle>(worlds: &Worlds<Io>, name: &str) -> Result<Template, Error> {
+    let id = Template::id_of(name);
+    let select = worlds.template.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or_else(|| Error::TemplateNotFound(name.to_owned()))?;
+    lazy.get().await.map(Clone::clone).map_err(From::from)
+}
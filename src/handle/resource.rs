@@ -0,0 +1,866 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
+    Json,
+};
+use dmds::IoHandle;
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::{Account, Permission},
+    config::{self, Config},
+    resource::{
+        self,
+        scan::{ScanStatus, Scanner},
+        storage::Storage,
+        Resource, UploadSessions, Variant,
+    },
+    Error,
+};
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use crate::{Auth, Global, Worlds};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct NewSessionReq {
+    pub variant: Variant,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ResourceIdRes {
+    pub id: u64,
+    /// Warnings of the uploaded resource, e.g. mismatching
+    /// the resolutions of screens.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Creates an upload session, the body should be uploaded
+/// through [`upload`] with the returned id in a short time,
+/// or in chunks through [`upload_chunk`].
+#[utoipa::path(
+    post,
+    path = "/api/resource/new-session",
+    tag = "resource",
+    request_body = NewSessionReq,
+    responses((status = 200, body = ResourceIdRes)),
+    security(("token" = [])),
+)]
+pub async fn new_session<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        upload_sessions,
+        ..
+    }): State<Global<Io>>,
+    Json(NewSessionReq { variant }): Json<NewSessionReq>,
+) -> Result<Json<ResourceIdRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let res = Resource::new(variant, auth.account);
+    let id = res.id();
+    upload_sessions.lock().await.insert(res);
+    Ok(Json(ResourceIdRes {
+        id,
+        warnings: vec![],
+    }))
+}
+
+/// Uploads the body of a resource, returns the
+/// content-addressed id of the resource.
+#[utoipa::path(
+    post,
+    path = "/api/resource/upload/{id}",
+    tag = "resource",
+    params(("id" = u64, Path)),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = 200, body = ResourceIdRes)),
+    security(("token" = [])),
+)]
+pub async fn upload<Io: IoHandle>(
+    auth: Auth,
+    State(global): State<Global<Io>>,
+    Path(id): Path<u64>,
+    body: Bytes,
+) -> Result<Json<ResourceIdRes>, Error> {
+    let Global {
+        worlds,
+        config,
+        upload_sessions,
+        ..
+    } = &global;
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+    let limits = &config.resource;
+    if body.len() > limits.max_size.max(limits.max_video_size) {
+        return Err(Error::ResourceTooLarge);
+    }
+
+    let res = upload_sessions
+        .lock()
+        .await
+        .accept(id, &body, auth.account)?;
+    store(&global, res, body).await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BatchFileRes {
+    /// File name of the part.
+    pub name: Option<String>,
+    /// Content-addressed id if the file is uploaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Uploads several images in a multipart request without sessions,
+/// returns results of each file in order.
+///
+/// Failures of some files don't fail the others.
+#[utoipa::path(
+    post,
+    path = "/api/resource/batch",
+    tag = "resource",
+    request_body(content_type = "multipart/form-data", description = "Files to upload"),
+    responses((status = 200, body = [BatchFileRes])),
+    security(("token" = [])),
+)]
+pub async fn upload_batch<Io: IoHandle>(
+    auth: Auth,
+    State(global): State<Global<Io>>,
+    mut multipart: Multipart,
+) -> Result<Json<Vec<BatchFileRes>>, Error> {
+    const MAX_FILES: usize = 20;
+
+    let worlds = &global.worlds;
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let mut files = vec![];
+    while let Some(field) = multipart.next_field().await? {
+        if files.len() >= MAX_FILES {
+            return Err(Error::TooManyFiles(MAX_FILES));
+        }
+        let name = field.file_name().map(ToOwned::to_owned);
+        let result = match field.bytes().await {
+            Ok(data) if data.len() > global.config.resource.max_size => {
+                Err(Error::ResourceTooLarge)
+            }
+            Ok(data) => {
+                let res = Resource::with_data(Variant::Image, auth.account, &data);
+                store(&global, res, data).await
+            }
+            Err(err) => Err(err.into()),
+        };
+        files.push(match result {
+            Ok(Json(ResourceIdRes { id, warnings })) => BatchFileRes {
+                name,
+                id: Some(id),
+                warnings,
+                error: None,
+            },
+            Err(err) => BatchFileRes {
+                name,
+                id: None,
+                warnings: vec![],
+                error: Some(err.to_string()),
+            },
+        });
+    }
+    Ok(Json(files))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct UploadProgressRes {
+    /// Offset where the next chunk should start from.
+    pub offset: usize,
+}
+
+/// Gets the progress of a chunked upload, for resuming
+/// the upload after disconnecting.
+#[utoipa::path(
+    get,
+    path = "/api/resource/progress/{id}",
+    tag = "resource",
+    params(("id" = u64, Path)),
+    responses((status = 200, body = UploadProgressRes)),
+    security(("token" = [])),
+)]
+pub async fn progress<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        upload_sessions,
+        ..
+    }): State<Global<Io>>,
+    Path(id): Path<u64>,
+) -> Result<Json<UploadProgressRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+    let (_, offset) = upload_sessions.lock().await.progress(id, auth.account)?;
+    Ok(Json(UploadProgressRes { offset }))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChunkQuery {
+    pub offset: usize,
+}
+
+/// Uploads a chunk of a resource at given offset, which should be
+/// the offset returned by the previous chunk or [`progress`].
+///
+/// Chunks are kept in the partial directory until the upload
+/// is finalized through [`finalize`].
+#[utoipa::path(
+    post,
+    path = "/api/resource/chunk/{id}",
+    tag = "resource",
+    params(("id" = u64, Path), ChunkQuery),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = 200, body = UploadProgressRes)),
+    security(("token" = [])),
+)]
+pub async fn upload_chunk<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds,
+        config,
+        upload_sessions,
+        ..
+    }): State<Global<Io>>,
+    Path(id): Path<u64>,
+    Query(ChunkQuery { offset }): Query<ChunkQuery>,
+    body: Bytes,
+) -> Result<Json<UploadProgressRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+
+    // Chunks of a session are written one by one.
+    let mut sessions = upload_sessions.lock().await;
+    let (variant, received) = sessions.progress(id, auth.account)?;
+    if offset != received {
+        return Err(Error::ResourceChunkOffsetMismatch(received));
+    }
+    if received + body.len() > max_size(&config.resource, variant) {
+        return Err(Error::ResourceTooLarge);
+    }
+    check_quota(
+        &config.resource,
+        lazy.get().await?,
+        (received + body.len()) as u64,
+    )?;
+    tokio::fs::create_dir_all(&config.resource.partial_path).await?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(partial_path(&config.resource, id))
+        .await?;
+    // Drops data written by an interrupted request.
+    file.set_len(offset as u64).await?;
+    file.seek(std::io::SeekFrom::Start(offset as u64)).await?;
+    file.write_all(&body).await?;
+    file.sync_data().await?;
+    let offset = sessions.advance(id, offset, body.len(), auth.account)?;
+    Ok(Json(UploadProgressRes { offset }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct FinalizeReq {
+    /// SHA-256 hash of the whole resource in hex.
+    pub sha256: String,
+}
+
+/// Finalizes a chunked upload after verifying the declared hash,
+/// returns the content-addressed id of the resource.
+#[utoipa::path(
+    post,
+    path = "/api/resource/finalize/{id}",
+    tag = "resource",
+    params(("id" = u64, Path)),
+    request_body = FinalizeReq,
+    responses((status = 200, body = ResourceIdRes)),
+    security(("token" = [])),
+)]
+pub async fn finalize<Io: IoHandle>(
+    auth: Auth,
+    State(global): State<Global<Io>>,
+    Path(id): Path<u64>,
+    Json(FinalizeReq { sha256 }): Json<FinalizeReq>,
+) -> Result<Json<ResourceIdRes>, Error> {
+    let Global {
+        worlds,
+        config,
+        upload_sessions,
+        ..
+    } = &global;
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+
+    let mut sessions = upload_sessions.lock().await;
+    sessions.progress(id, auth.account)?;
+    let data = tokio::fs::read(partial_path(&config.resource, id))
+        .await
+        .unwrap_or_default();
+    let res = sessions.finalize(id, &data, &sha256, auth.account)?;
+    drop(sessions);
+    remove_partial(&config.resource, id).await?;
+    store(&global, res, data.into()).await
+}
+
+/// Drops expired upload sessions and their partial data,
+/// including partial data left by sessions lost in restarts.
+///
+/// This should be run periodically in background.
+pub async fn sweep_sessions(
+    config: &Config,
+    upload_sessions: &Mutex<UploadSessions>,
+) -> Result<(), Error> {
+    // Chunks are written with the lock held, so no
+    // partial data is created while sweeping.
+    let mut sessions = upload_sessions.lock().await;
+    for id in sessions.sweep() {
+        remove_partial(&config.resource, id).await?;
+    }
+    let mut dir = match tokio::fs::read_dir(&config.resource.partial_path).await {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    while let Some(entry) = dir.next_entry().await? {
+        let stale = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+            .map_or(true, |id| !sessions.contains(id));
+        if stale {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the partial data of a chunked upload if exists.
+async fn remove_partial(config: &config::Resource, id: u64) -> Result<(), Error> {
+    match tokio::fs::remove_file(partial_path(config, id)).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Path of the partial data of a chunked upload.
+#[inline]
+fn partial_path(config: &config::Resource, id: u64) -> std::path::PathBuf {
+    config.partial_path.join(id.to_string())
+}
+
+/// Max size of a resource in given variant.
+#[inline]
+fn max_size(config: &config::Resource, variant: Variant) -> usize {
+    if variant == Variant::Video {
+        config.max_video_size
+    } else {
+        config.max_size
+    }
+}
+
+/// Validates and stores an accepted resource.
+///
+/// If a scanner is configured, the resource could not be used
+/// until it is scanned as clean.
+async fn store<Io: IoHandle>(
+    global: &Global<Io>,
+    mut res: Resource,
+    body: Bytes,
+) -> Result<Json<ResourceIdRes>, Error> {
+    let Global {
+        worlds,
+        config,
+        storage,
+        scanner,
+        ..
+    } = global;
+    let limits = &config.resource;
+    if body.len() > max_size(limits, res.variant()) {
+        return Err(Error::ResourceTooLarge);
+    }
+    res.sniff(&body)?;
+    match res.variant() {
+        Variant::Image => resource::image::validate(&body, &limits.image)?,
+        Variant::Pdf => resource::pdf::validate(&body)?,
+        Variant::Video => resource::video::validate(
+            &body,
+            time::Duration::seconds(limits.max_video_duration as i64),
+        )?,
+    }
+    let id = res.id();
+    // Ids are content hashes, so the same content is stored once
+    // and shared by references of the existing resource.
+    let select = worlds.resource.select(0, id).hint(id);
+    if let Some(mut lazy) = ga!(select, id) {
+        lazy.get_mut().await?.add_uploader(res.user());
+        return Ok(Json(ResourceIdRes {
+            id,
+            warnings: vec![],
+        }));
+    }
+    let body = if res.variant() == Variant::Image {
+        let data = body.clone();
+        let bake_orientation = limits.image.bake_orientation;
+        match tokio::task::spawn_blocking(move || {
+            resource::image::metadata::strip(&data, bake_orientation)
+        })
+        .await
+        .map_err(|_| Error::Unknown)??
+        {
+            Some(stripped) => {
+                res.replaced(&stripped);
+                Bytes::from(stripped)
+            }
+            None => body,
+        }
+    } else {
+        body
+    };
+    let warnings = if res.variant() == Variant::Image {
+        res.set_dimensions(resource::image::dimensions(&body)?);
+        resource::image::check_presets(&body, &limits.image)?
+    } else {
+        vec![]
+    };
+    // PNG images are compressed before being stored, so stored files
+    // never change, and thumbnails are generated from the original.
+    let stored = if res.content_type() == "image/png" {
+        let (data, config) = (body.clone(), config.clone());
+        match tokio::task::spawn_blocking(move || {
+            resource::image::compress(&data, &config.resource.compression)
+        })
+        .await
+        .map_err(|_| Error::Unknown)?
+        {
+            Ok(Some(compressed)) => {
+                res.replaced(&compressed);
+                Bytes::from(compressed)
+            }
+            Ok(None) => body.clone(),
+            Err(err) => {
+                tracing::warn!("failed to compress resource {id}: {err}");
+                body.clone()
+            }
+        }
+    } else {
+        body.clone()
+    };
+    let user = res.user();
+    let select = sa!(worlds.account, user);
+    let mut lazy = ga!(select, user).ok_or(Error::PermissionDenied)?;
+    check_quota(limits, lazy.get().await?, res.size())?;
+    storage.put(&res.key(), &stored).await?;
+    lazy.get_mut().await?.charge_storage(res.size());
+    if scanner.is_some() {
+        res.set_scan(ScanStatus::Pending);
+    }
+    let variant = res.variant();
+    let (key, size) = (res.key(), res.size());
+    if worlds.resource.try_insert(res).await.is_err() {
+        lazy.get_mut().await?.refund_storage(size);
+        // The same content may be uploaded concurrently,
+        // and the stored file is shared with it.
+        let select = worlds.resource.select(0, id).hint(id);
+        if let Some(mut lazy) = ga!(select, id) {
+            lazy.get_mut().await?.add_uploader(user);
+            return Ok(Json(ResourceIdRes { id, warnings }));
+        }
+        storage.delete(&key).await?;
+        return Err(Error::PermissionDenied);
+    }
+    match scanner {
+        Some(scanner) => scan(global.clone(), scanner.clone(), id, variant, body),
+        None if variant == Variant::Image => generate_thumbnails(storage.clone(), id, body),
+        None => {}
+    }
+    Ok(Json(ResourceIdRes { id, warnings }))
+}
+
+/// Scans a stored resource in background, then generates thumbnails
+/// of it if it is a clean image, or quarantines it and notifies the
+/// uploader if infected.
+///
+/// The resource is deleted if the scanning fails,
+/// so that it could be uploaded again.
+fn scan<Io: IoHandle>(
+    global: Global<Io>,
+    scanner: Arc<dyn Scanner>,
+    id: u64,
+    variant: Variant,
+    body: Bytes,
+) {
+    tokio::spawn(async move {
+        let result: Result<(), Error> = async {
+            let status = match scanner.scan(&body).await {
+                Ok(status) => status,
+                Err(err) => {
+                    tracing::warn!("failed to scan resource {id}: {err}");
+                    collect(&global.worlds, &*global.storage, |res| res.id() == id).await?;
+                    return Ok(());
+                }
+            };
+            let select = global.worlds.resource.select(0, id).hint(id);
+            let Some(mut lazy) = ga!(select, id) else {
+                return Ok(());
+            };
+            let res = lazy.get_mut().await?;
+            res.set_scan(status.clone());
+            let ScanStatus::Infected { signature } = status else {
+                if variant == Variant::Image {
+                    generate_thumbnails(global.storage.clone(), id, body);
+                }
+                return Ok(());
+            };
+            // Quarantined files are kept until being collected.
+            global.storage.put(&res.quarantine_key(), &body).await?;
+            global.storage.delete(&res.key()).await?;
+            let user = res.user();
+            tracing::warn!("resource {id} uploaded by {user} is infected: {signature}");
+            notify_infected(&global, user, id, &signature).await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!("failed to handle scanning of resource {id}: {err}");
+        }
+    });
+}
+
+/// Emails the uploader of an infected resource.
+async fn notify_infected<Io: IoHandle>(
+    global: &Global<Io>,
+    user: u64,
+    id: u64,
+    signature: &str,
+) -> Result<(), Error> {
+    let select = sa!(global.worlds.account, user);
+    let Some(lazy) = ga!(select, user) else {
+        return Ok(());
+    };
+    let Ok(to) = lazy.get().await?.email().parse::<lettre::Address>() else {
+        return Ok(());
+    };
+    let body = format!(
+        "The file {id} you uploaded was detected as {signature}, \
+        and it has been quarantined so it could not be attached to posts.\n\n\
+        Please check your device for malware."
+    );
+    sms3_backend::mail::send(
+        &global.config.smtp,
+        to,
+        "Your SubIT upload was quarantined",
+        body,
+        &global.smtp_transport,
+    )
+    .await
+}
+
+/// Checks whether the resources are uploaded by accounts
+/// accepted by `owner`, unless the account has the given
+/// admin permission.
+///
+/// Accounts uploaded the same content of a deduplicated
+/// resource are all uploaders.
+pub async fn check_owners<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    auth: &Auth,
+    account: &Account,
+    ids: &[u64],
+    owner: impl Fn(u64) -> bool,
+    admin: Permission,
+) -> Result<(), Error> {
+    let mut admin = Some(admin);
+    for id in ids {
+        let select = worlds.resource.select(0, *id).hint(*id);
+        let lazy = ga!(select, *id).ok_or(Error::ResourceNotFound(*id))?;
+        let res = lazy.get().await?;
+        if owner(res.user()) || res.uploaders().any(&owner) {
+            continue;
+        }
+        // Only resolve the permission once.
+        match admin.take() {
+            Some(p) if super::permitted(worlds, auth, account, &[p]).await? => {}
+            Some(_) => return Err(Error::PermissionDenied),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Adds references to all the resources, validating the variants
+/// if given, and removes the added references if any fails.
+pub async fn acquire_all<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    resources: &[(u64, Option<Variant>)],
+) -> Result<(), Error> {
+    for (i, (id, variant)) in resources.iter().enumerate() {
+        if let Err(err) = set_used(worlds, *id, true, *variant).await {
+            for (id, _) in &resources[..i] {
+                set_used(worlds, *id, false, None).await?;
+            }
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Removes references to all the resources.
+pub async fn release_all<Io: IoHandle>(worlds: &Worlds<Io>, ids: &[u64]) -> Result<(), Error> {
+    for id in ids {
+        set_used(worlds, *id, false, None).await?;
+    }
+    Ok(())
+}
+
+/// Adds or removes a reference to a resource, and validates
+/// its variant if given.
+///
+/// The resource is unused when there are no references.
+pub async fn set_used<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    id: u64,
+    used: bool,
+    variant: Option<Variant>,
+) -> Result<(), Error> {
+    let select = worlds.resource.select(0, id).hint(id);
+    let mut lazy = ga!(select, id).ok_or(Error::ResourceNotFound(id))?;
+    let res = lazy.get_mut().await?;
+    if variant.map_or(false, |v| v != res.variant()) {
+        return Err(Error::ResourceVariantMismatch);
+    }
+    let was_used = res.is_used();
+    if used {
+        res.check_scan()?;
+        res.acquire();
+    } else {
+        res.release();
+    }
+    if res.is_used() == was_used {
+        return Ok(());
+    }
+
+    // The used state is a dimension, so the resource
+    // should be moved to another chunk.
+    let res = lazy.destroy().await?;
+    worlds.resource.insert(res).await?;
+    Ok(())
+}
+
+/// Generates thumbnails, and the WebP and AVIF copies if enabled,
+/// of an image resource in background.
+///
+/// The original image is served for sizes without thumbnails.
+fn generate_thumbnails(storage: Arc<dyn Storage>, id: u64, data: Bytes) {
+    tokio::spawn(async move {
+        #[cfg(feature = "webp")]
+        {
+            let data = data.clone();
+            match tokio::task::spawn_blocking(move || resource::image::webp(&data)).await {
+                Ok(Ok(webp)) => {
+                    if let Err(err) = storage.put(&resource::image::webp_key(id), &webp).await {
+                        tracing::warn!("failed to write webp copy of resource {id}: {err}");
+                    }
+                }
+                Ok(Err(err)) => tracing::warn!("failed to transcode resource {id}: {err}"),
+                Err(_) => {}
+            }
+        }
+        #[cfg(feature = "avif")]
+        {
+            let data = data.clone();
+            match tokio::task::spawn_blocking(move || resource::image::avif(&data)).await {
+                Ok(Ok(avif)) => {
+                    if let Err(err) = storage.put(&resource::image::avif_key(id), &avif).await {
+                        tracing::warn!("failed to write avif copy of resource {id}: {err}");
+                    }
+                }
+                Ok(Err(err)) => tracing::warn!("failed to transcode resource {id}: {err}"),
+                Err(_) => {}
+            }
+        }
+        let thumbnails =
+            match tokio::task::spawn_blocking(move || resource::image::thumbnails(&data)).await {
+                Ok(Ok(thumbnails)) => thumbnails,
+                Ok(Err(err)) => {
+                    tracing::warn!("failed to generate thumbnails of resource {id}: {err}");
+                    return;
+                }
+                Err(_) => return,
+            };
+        for (size, data) in thumbnails {
+            if let Err(err) = storage.put(&size.key(id), &data).await {
+                tracing::warn!("failed to write thumbnail of resource {id}: {err}");
+            }
+        }
+    });
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CollectRes {
+    /// Count of deleted resources.
+    pub resources: usize,
+    /// Reclaimed bytes of the deleted resources, excluding
+    /// the derived files.
+    pub bytes: u64,
+}
+
+/// Deletes resources unreferenced for longer than the grace period,
+/// with their derived files, e.g. thumbnails.
+///
+/// This should be run periodically in background.
+pub async fn collect_orphans<Io: IoHandle>(
+    worlds: &Worlds<Io>,
+    config: &Config,
+    storage: &dyn Storage,
+) -> Result<CollectRes, Error> {
+    let grace = time::Duration::hours(config.resource.orphan_grace_hours as i64);
+    let now = time::OffsetDateTime::now_utc();
+    collect(worlds, storage, |res| now - res.created_at() >= grace).await
+}
+
+/// Deletes unreferenced resources matching the filter, and
+/// refunds the storage usage of the uploaders.
+async fn collect<Io, F>(
+    worlds: &Worlds<Io>,
+    storage: &dyn Storage,
+    f: F,
+) -> Result<CollectRes, Error>
+where
+    Io: IoHandle,
+    F: Fn(&Resource) -> bool,
+{
+    let mut collected = CollectRes {
+        resources: 0,
+        bytes: 0,
+    };
+    // Unused resources are in chunks of dimension 1 = 0.
+    let select = worlds.resource.select(1, 0);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = dmds::StreamExt::next(&mut iter).await {
+        let Ok(res) = lazy.get().await else {
+            continue;
+        };
+        if res.is_used() || !f(res) {
+            continue;
+        }
+        for key in res.keys() {
+            storage.delete(&key).await?;
+        }
+        let (user, size) = (res.user(), res.size());
+        collected.resources += 1;
+        collected.bytes += size;
+        lazy.destroy().await?;
+
+        let select = sa!(worlds.account, user);
+        if let Some(mut lazy) = ga!(select, user) {
+            lazy.get_mut().await?.refund_storage(size);
+        }
+    }
+    Ok(collected)
+}
+
+/// Checks if the account could store more bytes within the quota.
+fn check_quota(config: &config::Resource, account: &Account, bytes: u64) -> Result<(), Error> {
+    let usage = account.storage_usage();
+    if config.quota > 0 && usage.saturating_add(bytes) > config.quota {
+        return Err(Error::StorageQuotaExceeded {
+            usage,
+            quota: config.quota,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct UsageResourceRes {
+    pub id: u64,
+    pub variant: Variant,
+    pub size: u64,
+    pub used: bool,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct UsageRes {
+    /// Bytes of uploaded resources.
+    pub usage: u64,
+    /// Max bytes of uploaded resources, unlimited if this is `0`.
+    pub quota: u64,
+    pub resources: Vec<UsageResourceRes>,
+}
+
+/// Gets the storage usage of the account, with the uploaded resources.
+#[utoipa::path(
+    get,
+    path = "/api/resource/usage",
+    tag = "resource",
+    responses((status = 200, body = UsageRes)),
+    security(("token" = [])),
+)]
+pub async fn usage<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, config, .. }): State<Global<Io>>,
+) -> Result<Json<UsageRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    let lazy = va!(auth, select);
+    let usage = lazy.get().await?.storage_usage();
+
+    let select = worlds.resource.select(0, ..);
+    let mut iter = select.iter();
+    let mut resources = vec![];
+    while let Some(Ok(lazy)) = dmds::StreamExt::next(&mut iter).await {
+        let Ok(res) = lazy.get().await else {
+            continue;
+        };
+        if res.user() == auth.account {
+            resources.push(UsageResourceRes {
+                id: res.id(),
+                variant: res.variant(),
+                size: res.size(),
+                used: res.is_used(),
+                created_at: res.created_at().unix_timestamp(),
+            });
+        }
+    }
+    resources.sort_unstable_by_key(|r| std::cmp::Reverse(r.created_at));
+    Ok(Json(UsageRes {
+        usage,
+        quota: config.resource.quota,
+        resources,
+    }))
+}
+
+/// Deletes unreferenced resources uploaded by the account immediately,
+/// freeing the storage usage.
+#[utoipa::path(
+    post,
+    path = "/api/resource/free",
+    tag = "resource",
+    responses((status = 200, body = CollectRes)),
+    security(("token" = [])),
+)]
+pub async fn free<Io: IoHandle>(
+    auth: Auth,
+    State(Global {
+        worlds, storage, ..
+    }): State<Global<Io>>,
+) -> Result<Json<CollectRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select);
+    collect(&worlds, &*storage, |res| res.user() == auth.account)
+        .await
+        .map(Json)
+}
@@ -0,0 +1,301 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::Permission,
+    audit::Action,
+    post::{stats::Stats, Scope, Status},
+    screen::Screen,
+    Error,
+};
+use time::OffsetDateTime;
+
+use crate::{Auth, Global};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RegisterScreenReq {
+    pub name: String,
+    #[serde(default)]
+    pub location: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RegisterScreenRes {
+    pub id: u64,
+    /// Token of the device, only returned once.
+    pub token: String,
+}
+
+/// Registers a display device.
+#[utoipa::path(
+    post,
+    path = "/api/screen/register",
+    tag = "screen",
+    request_body = RegisterScreenReq,
+    responses((status = 200, body = RegisterScreenRes)),
+    security(("token" = [])),
+)]
+pub async fn register<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RegisterScreenReq { name, location }): Json<RegisterScreenReq>,
+) -> Result<Json<RegisterScreenRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageScreens);
+
+    let (screen, token) = Screen::new(name, location);
+    let res = RegisterScreenRes {
+        id: screen.id(),
+        token,
+    };
+    worlds
+        .screen
+        .try_insert(screen)
+        .await
+        .map_err(|_| Error::PermissionDenied)?;
+    super::record_audit(
+        &worlds,
+        auth.account,
+        Action::RegisterScreen { screen: res.id },
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ScreenRes {
+    pub id: u64,
+    pub name: String,
+    pub location: String,
+    pub posts: Vec<u64>,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub last_seen: Option<OffsetDateTime>,
+}
+
+/// Gets all registered screens.
+#[utoipa::path(
+    get,
+    path = "/api/screen/list",
+    tag = "screen",
+    responses((status = 200, body = [ScreenRes])),
+    security(("token" = [])),
+)]
+pub async fn screens<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<ScreenRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageScreens);
+
+    let select = worlds.screen.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let screen = lazy.get().await?;
+        res.push(ScreenRes {
+            id: screen.id(),
+            name: screen.name().to_owned(),
+            location: screen.location().to_owned(),
+            posts: screen.posts().to_vec(),
+            created_at: screen.created_at(),
+            last_seen: screen.last_seen(),
+        });
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AssignPostsReq {
+    pub screen: u64,
+    /// Ids of approved posts to rotate on the screen.
+    pub posts: Vec<u64>,
+}
+
+/// Assigns posts rotating on a screen.
+#[utoipa::path(
+    post,
+    path = "/api/screen/assign",
+    tag = "screen",
+    request_body = AssignPostsReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn assign<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(AssignPostsReq { screen, posts }): Json<AssignPostsReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageScreens);
+
+    for id in posts.iter() {
+        let select = worlds.post.select(0, *id).hint(*id);
+        let lazy = ga!(select, *id).ok_or(Error::PostNotFound(*id))?;
+        if !matches!(
+            lazy.get().await?.state().status(),
+            Status::Approved | Status::Active
+        ) {
+            return Err(Error::PostNotFound(*id));
+        }
+    }
+    let select = worlds.screen.select(0, screen).hint(screen);
+    let mut lazy = ga!(select, screen).ok_or(Error::ScreenNotFound(screen))?;
+    lazy.get_mut().await?.set_posts(posts);
+    Ok(())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RemoveScreenReq {
+    pub screen: u64,
+}
+
+/// Removes a screen, invalidating its token.
+#[utoipa::path(
+    post,
+    path = "/api/screen/remove",
+    tag = "screen",
+    request_body = RemoveScreenReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn remove<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RemoveScreenReq { screen }): Json<RemoveScreenReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::ManageScreens);
+
+    let select = worlds.screen.select(0, screen).hint(screen);
+    let lazy = ga!(select, screen).ok_or(Error::ScreenNotFound(screen))?;
+    lazy.destroy().await?;
+    super::record_audit(&worlds, auth.account, Action::RemoveScreen { screen }).await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PlaylistItemRes {
+    pub id: u64,
+    pub title: String,
+    pub description: String,
+    pub images: Vec<u64>,
+    pub videos: Vec<u64>,
+}
+
+/// Gets active posts rotating on the screen of the token,
+/// from the most important.
+///
+/// Posts scoped to a restricted audience are never shown on screens.
+///
+/// This is requested by display devices.
+#[utoipa::path(
+    get,
+    path = "/api/screen/playlist/{token}",
+    tag = "screen",
+    params(("token" = String, Path)),
+    responses((status = 200, body = [PlaylistItemRes])),
+)]
+pub async fn playlist<Io: IoHandle>(
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Path(token): Path<String>,
+) -> Result<Json<Vec<PlaylistItemRes>>, Error> {
+    let id = Screen::id_of(&token);
+    let select = worlds.screen.select(0, id).hint(id);
+    let mut lazy = ga!(select, id).ok_or(Error::PermissionDenied)?;
+    let screen = lazy.get_mut().await?;
+    if !screen.verify_token(&token) {
+        return Err(Error::PermissionDenied);
+    }
+    screen.touch();
+
+    let now = OffsetDateTime::now_utc();
+    let mut posts = Vec::with_capacity(screen.posts().len());
+    for id in screen.posts() {
+        let select = worlds.post.select(0, *id).hint(*id);
+        if let Some(lazy) = ga!(select, *id) {
+            let post = lazy.get().await?;
+            if post.is_active() && *post.scope() == Scope::Everyone {
+                posts.push((
+                    (post.is_pinned(now), post.priority()),
+                    PlaylistItemRes {
+                        id: post.id(),
+                        title: post.title().to_owned(),
+                        description: post.description().to_owned(),
+                        images: post.images().to_vec(),
+                        videos: post.videos().to_vec(),
+                    },
+                ));
+            }
+        }
+    }
+    posts.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(Json(posts.into_iter().map(|(_, p)| p).collect()))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReportReq {
+    /// Counts of posts shown since the last report.
+    pub reports: Vec<PostReport>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PostReport {
+    pub post: u64,
+    #[serde(default)]
+    pub impressions: u64,
+    #[serde(default)]
+    pub plays: u64,
+}
+
+/// Reports display counts of posts rotating on the screen of the token.
+///
+/// This is requested by display devices.
+#[utoipa::path(
+    post,
+    path = "/api/screen/report/{token}",
+    tag = "screen",
+    params(("token" = String, Path)),
+    request_body = ReportReq,
+    responses((status = 200, description = "Success")),
+)]
+pub async fn report<Io: IoHandle>(
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Path(token): Path<String>,
+    Json(ReportReq { reports }): Json<ReportReq>,
+) -> Result<(), Error> {
+    let id = Screen::id_of(&token);
+    let select = worlds.screen.select(0, id).hint(id);
+    let lazy = ga!(select, id).ok_or(Error::PermissionDenied)?;
+    let screen = lazy.get().await?;
+    if !screen.verify_token(&token) {
+        return Err(Error::PermissionDenied);
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    for PostReport {
+        post,
+        impressions,
+        plays,
+    } in reports
+    {
+        if !screen.posts().contains(&post) {
+            continue;
+        }
+        let id = Stats::id_of(post, today);
+        let select = worlds.stats.select(0, id).hint(id);
+        if let Some(mut lazy) = ga!(select, id) {
+            lazy.get_mut().await?.add(impressions, plays);
+        } else {
+            let mut stats = Stats::new(post, today);
+            stats.add(impressions, plays);
+            worlds.stats.insert(stats).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{body::Bytes, extract::State, Json};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use sms3_backend::{
+    account::Permission,
+    audit::Action,
+    webhook::{Delivery, Event, EventKind, Webhook},
+    Error,
+};
+use time::OffsetDateTime;
+
+use crate::{Auth, Global, Worlds};
+
+/// Header of the signature of an event.
+const SIGNATURE_HEADER: &str = "X-SMS3-Signature";
+/// Max attempts delivering an event.
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RegisterWebhookReq {
+    #[schema(value_type = String)]
+    pub url: reqwest::Url,
+    /// Subscribed events, all events if empty.
+    #[serde(default)]
+    pub events: HashSet<EventKind>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RegisterWebhookRes {
+    pub id: u64,
+    /// Secret of signatures, only returned once.
+    pub secret: String,
+}
+
+/// Registers a webhook.
+#[utoipa::path(
+    post,
+    path = "/api/webhook",
+    tag = "webhook",
+    request_body = RegisterWebhookReq,
+    responses((status = 200, body = RegisterWebhookRes)),
+    security(("token" = [])),
+)]
+pub async fn register<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RegisterWebhookReq { url, events }): Json<RegisterWebhookReq>,
+) -> Result<Json<RegisterWebhookRes>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let webhook = Webhook::new(url.to_string(), events);
+    let res = RegisterWebhookRes {
+        id: webhook.id(),
+        secret: webhook.secret().to_owned(),
+    };
+    worlds.webhook.insert(webhook).await?;
+    super::record_audit(
+        &worlds,
+        auth.account,
+        Action::RegisterWebhook {
+            webhook: res.id,
+            url: url.to_string(),
+        },
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WebhookRes {
+    pub id: u64,
+    pub url: String,
+    pub events: Vec<EventKind>,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub created_at: OffsetDateTime,
+    /// Recent deliveries, the most recent first.
+    pub deliveries: Vec<Delivery>,
+}
+
+/// Gets all webhooks with their delivery logs.
+#[utoipa::path(
+    get,
+    path = "/api/webhook",
+    tag = "webhook",
+    responses((status = 200, body = [WebhookRes])),
+    security(("token" = [])),
+)]
+pub async fn webhooks<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+) -> Result<Json<Vec<WebhookRes>>, Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let select = worlds.webhook.select(0, ..);
+    let mut iter = select.iter();
+    let mut res = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let webhook = lazy.get().await?;
+        res.push(WebhookRes {
+            id: webhook.id(),
+            url: webhook.url().to_owned(),
+            events: webhook.events().iter().copied().collect(),
+            created_at: webhook.created_at(),
+            deliveries: webhook.deliveries().iter().cloned().collect(),
+        });
+    }
+    Ok(Json(res))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RemoveWebhookReq {
+    pub webhook: u64,
+}
+
+/// Removes a webhook.
+#[utoipa::path(
+    post,
+    path = "/api/webhook/remove",
+    tag = "webhook",
+    request_body = RemoveWebhookReq,
+    responses((status = 200, description = "Success")),
+    security(("token" = [])),
+)]
+pub async fn remove<Io: IoHandle>(
+    auth: Auth,
+    State(Global { worlds, .. }): State<Global<Io>>,
+    Json(RemoveWebhookReq { webhook }): Json<RemoveWebhookReq>,
+) -> Result<(), Error> {
+    let select = sa!(worlds.account, auth.account);
+    va!(auth, select, worlds => Permission::Op);
+
+    let select = worlds.webhook.select(0, webhook).hint(webhook);
+    let lazy = ga!(select, webhook).ok_or(Error::WebhookNotFound(webhook))?;
+    lazy.destroy().await?;
+    super::record_audit(&worlds, auth.account, Action::RemoveWebhook { webhook }).await
+}
+
+/// Sends the event to subscribed webhooks in background,
+/// retrying failed deliveries with backoff.
+///
+/// Each webhook is delivered in its own task, so a slow
+/// endpoint doesn't delay the others.
+pub fn dispatch<Io: IoHandle>(worlds: Arc<Worlds<Io>>, client: reqwest::Client, event: Event) {
+    tokio::spawn(async move {
+        let result: Result<(), Error> = async {
+            let body = Bytes::from(serde_json::to_vec(&event).map_err(|_| Error::Unknown)?);
+            let select = worlds.webhook.select(0, ..);
+            let mut iter = select.iter();
+            while let Some(Ok(lazy)) = iter.next().await {
+                let webhook = lazy.get().await?;
+                if !webhook.subscribes(event.kind) {
+                    continue;
+                }
+                let id = webhook.id();
+                let (url, signature) = (webhook.url().to_owned(), webhook.sign(&body));
+                let (worlds, client, event, body) =
+                    (worlds.clone(), client.clone(), event.clone(), body.clone());
+                tokio::spawn(async move {
+                    let delivery = deliver(&client, &event, url, signature, body).await;
+                    // The webhook may be removed while delivering.
+                    let select = worlds.webhook.select(0, id).hint(id);
+                    if let Some(mut lazy) = ga!(select, id) {
+                        if let Err(err) = lazy.get_mut().await.map(|w| w.push_delivery(delivery)) {
+                            tracing::warn!("failed to record webhook delivery: {err}");
+                        }
+                    }
+                });
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!("failed to dispatch webhook event: {err}");
+        }
+    });
+}
+
+/// Delivers the event to a webhook, with retries.
+async fn deliver(
+    client: &reqwest::Client,
+    event: &Event,
+    url: String,
+    signature: String,
+    body: Bytes,
+) -> Delivery {
+    let mut delivery = Delivery {
+        kind: event.kind,
+        post: event.post,
+        time: OffsetDateTime::now_utc(),
+        attempts: 0,
+        status: None,
+        error: None,
+    };
+    while delivery.attempts < MAX_ATTEMPTS && !delivery.is_success() {
+        if delivery.attempts > 0 {
+            tokio::time::sleep(std::time::Duration::from_secs(1 << delivery.attempts)).await;
+        }
+        delivery.attempts += 1;
+        match client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+            .body(body.clone())
+            .send()
+            .await
+        {
+            Ok(res) => {
+                delivery.status = Some(res.status().as_u16());
+                delivery.error = None;
+            }
+            Err(err) => {
+                delivery.status = None;
+                delivery.error = Some(err.to_string());
+            }
+        }
+    }
+    delivery
+}
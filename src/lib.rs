@@ -4,11 +4,20 @@ use lettre::transport::smtp;
 use serde::Serialize;
 
 pub mod config;
+pub mod id;
 
 pub mod account;
 pub mod post;
 
 pub mod resource;
+pub mod screen;
+pub mod webhook;
+
+pub mod audit;
+pub mod challenge;
+pub mod limit;
+pub mod mail;
+pub mod trace;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -22,11 +31,67 @@ pub enum Error {
     UnverifiedAccountNotFound,
     #[error("username or password incorrect")]
     UsernameOrPasswordIncorrect,
+    #[error("password does not meet the policy: {}", display_rules(.0))]
+    PasswordPolicy(Vec<account::password::Rule>),
+    #[error("account locked until {0}")]
+    AccountLocked(time::OffsetDateTime),
+    #[error("account suspended{}: {}", .0.until.map_or(String::new(), |t| format!(" until {t}")), .0.reason)]
+    Suspended(account::Suspension),
+    #[error("account pending approval")]
+    PendingApproval,
+    #[error("account pending deletion at {0}")]
+    PendingDeletion(time::OffsetDateTime),
+    #[error("account is not pending deletion")]
+    NotPendingDeletion,
+    #[error("post {0} not found")]
+    PostNotFound(u64),
+    #[error("comment {0} not found")]
+    CommentNotFound(u64),
+    #[error("comment is too long")]
+    CommentTooLong,
+    #[error("report {0} not found")]
+    ReportNotFound(u64),
+    #[error("message is too long")]
+    MessageTooLong,
+    #[error("category {0} not found")]
+    CategoryNotFound(String),
+    #[error("template {0} not found")]
+    TemplateNotFound(String),
+    #[error("field {0} is required by the template")]
+    TemplateFieldRequired(&'static str),
+    #[error("screen {0} not found")]
+    ScreenNotFound(u64),
+    #[error("webhook {0} not found")]
+    WebhookNotFound(u64),
+    #[error("invalid date range")]
+    InvalidDateRange,
+    #[error("post duration exceeds {0} days")]
+    PostTooLong(u32),
+    #[error("invalid recurrence rule")]
+    InvalidRecurrence,
     #[error("target operation account not found")]
     TargetAccountNotFound,
+    #[error("role \"{0}\" not found")]
+    RoleNotFound(String),
+    #[error("department \"{0}\" not found")]
+    DepartmentNotFound(String),
+    #[error("csv error: {0}")]
+    Csv(csv::Error),
 
     #[error("captcha incorrect")]
     CaptchaIncorrect,
+    #[error("captcha expired")]
+    CaptchaExpired,
+    #[error("invite code required")]
+    InviteRequired,
+    #[error("invite code invalid or expired")]
+    InviteInvalid,
+    #[error("two-factor authentication code required")]
+    TotpRequired,
+    #[error("two-factor authentication code incorrect")]
+    TotpIncorrect,
+    #[error("two-factor authentication not enrolled")]
+    TotpNotEnrolled,
     #[error("request too frequent, try after {0}")]
     ReqTooFrequent(time::Duration),
 
@@ -37,8 +102,59 @@ pub enum Error {
     #[error("failed to send email")]
     Smtp(smtp::Error),
 
+    #[error("login session {0} not found")]
+    SessionNotFound(u64),
+    #[error("api key {0} not found")]
+    ApiKeyNotFound(u64),
+
     #[error("resource upload session {0} not found")]
     ResourceUploadSessionNotFound(u64),
+    #[error("resource {0} not found")]
+    ResourceNotFound(u64),
+    #[error("resource is too large")]
+    ResourceTooLarge,
+    #[error("resource variant mismatched")]
+    ResourceVariantMismatch,
+    #[error("chunk offset mismatched, expected {0}")]
+    ResourceChunkOffsetMismatch(usize),
+    #[error("resource hash mismatched")]
+    ResourceHashMismatch,
+    #[error("unsupported content type")]
+    UnsupportedContentType,
+    #[error("resource {0} is being scanned")]
+    ResourceScanPending(u64),
+    #[error("resource {0} is infected")]
+    ResourceInfected(u64),
+    #[error("malware scanning failed: {0}")]
+    Scan(String),
+    #[error("multipart error: {0}")]
+    Multipart(axum::extract::multipart::MultipartError),
+    #[error("too many files, at most {0} are allowed")]
+    TooManyFiles(usize),
+    #[error("signed urls are disabled")]
+    SignedUrlDisabled,
+    #[error("url signature invalid or expired")]
+    InvalidUrlSignature,
+    #[error("storage quota exceeded, {usage} of {quota} bytes used")]
+    StorageQuotaExceeded { usage: u64, quota: u64 },
+    #[error("invalid pdf file")]
+    InvalidPdf,
+    #[error("invalid video file, only mp4 and webm are supported")]
+    InvalidVideo,
+    #[error("video is too long")]
+    VideoTooLong,
+    #[error("image format {0} is not allowed")]
+    ImageFormatNotAllowed(&'static str),
+    #[error("image dimensions {0}x{1} exceed the limit")]
+    ImageTooLarge(u32, u32),
+    #[error("image {0}")]
+    ImagePresetMismatch(String),
+    #[error("image error: {0}")]
+    Image(image::ImageError),
+    #[error("storage error: {0}")]
+    Storage(String),
+    #[error("io error: {0}")]
+    Io(std::io::Error),
 
     #[error("not logged in")]
     NotLoggedIn,
@@ -46,10 +162,24 @@ pub enum Error {
     HeaderNonAscii(axum::http::header::ToStrError),
     #[error("auth header is not in {{account}}:{{token}} syntax")]
     InvalidAuthHeader,
+    #[error("jwt error: {0}")]
+    Jwt(jsonwebtoken::errors::Error),
+
+    #[error("oauth login is disabled")]
+    OAuthDisabled,
+    #[error("oauth state invalid or expired")]
+    OAuthStateInvalid,
+    #[error("oauth provider error: {0}")]
+    OAuth(reqwest::Error),
 
+    #[error("config error: {0}")]
+    Config(String),
     #[error("database errored")]
     Database(dmds::Error),
 
+    #[error("invalid timestamp")]
+    InvalidTimestamp,
+
     #[error("unknown")]
     Unknown,
 }
@@ -59,14 +189,62 @@ impl Error {
         match self {
             Error::VerifySessionNotFound(_)
             | Error::ResourceUploadSessionNotFound(_)
+            | Error::ResourceNotFound(_)
+            | Error::PostNotFound(_)
+            | Error::CommentNotFound(_)
+            | Error::ReportNotFound(_)
+            | Error::CategoryNotFound(_)
+            | Error::TemplateNotFound(_)
+            | Error::ScreenNotFound(_)
+            | Error::WebhookNotFound(_)
+            | Error::SessionNotFound(_)
+            | Error::ApiKeyNotFound(_)
             | Error::TargetAccountNotFound
+            | Error::RoleNotFound(_)
+            | Error::DepartmentNotFound(_)
             | Error::UnverifiedAccountNotFound => StatusCode::NOT_FOUND,
-            Error::ReqTooFrequent(_) => StatusCode::TOO_MANY_REQUESTS,
-            Error::EmailAddress(_) => StatusCode::BAD_REQUEST,
+            Error::ReqTooFrequent(_) | Error::AccountLocked(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::EmailAddress(_) | Error::PasswordPolicy(_) | Error::Csv(_) => {
+                StatusCode::BAD_REQUEST
+            }
             Error::Lettre(_) | Error::Smtp(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Error::NotLoggedIn => StatusCode::UNAUTHORIZED,
+            Error::NotLoggedIn | Error::Jwt(_) | Error::TotpRequired => StatusCode::UNAUTHORIZED,
+            Error::TotpNotEnrolled => StatusCode::NOT_FOUND,
             Error::HeaderNonAscii(_) | Error::InvalidAuthHeader => StatusCode::BAD_REQUEST,
-            Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::OAuthDisabled | Error::SignedUrlDisabled => StatusCode::NOT_FOUND,
+            Error::OAuthStateInvalid => StatusCode::BAD_REQUEST,
+            Error::OAuth(_) => StatusCode::BAD_GATEWAY,
+            Error::Database(_)
+            | Error::Io(_)
+            | Error::Storage(_)
+            | Error::Scan(_)
+            | Error::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::ResourceTooLarge | Error::StorageQuotaExceeded { .. } => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Error::ResourceChunkOffsetMismatch(_) | Error::ResourceScanPending(_) => {
+                StatusCode::CONFLICT
+            }
+            Error::UnsupportedContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::ResourceVariantMismatch
+            | Error::Multipart(_)
+            | Error::TooManyFiles(_)
+            | Error::ResourceHashMismatch
+            | Error::ResourceInfected(_)
+            | Error::Image(_)
+            | Error::ImageFormatNotAllowed(_)
+            | Error::ImageTooLarge(..)
+            | Error::ImagePresetMismatch(_)
+            | Error::InvalidPdf
+            | Error::InvalidVideo
+            | Error::VideoTooLong => StatusCode::BAD_REQUEST,
+            Error::InvalidTimestamp
+            | Error::InvalidDateRange
+            | Error::PostTooLong(_)
+            | Error::TemplateFieldRequired(_)
+            | Error::InvalidRecurrence
+            | Error::CommentTooLong
+            | Error::MessageTooLong => StatusCode::BAD_REQUEST,
             Error::Unknown => StatusCode::IM_A_TEAPOT,
             _ => StatusCode::FORBIDDEN,
         }
@@ -77,19 +255,40 @@ impl IntoResponse for Error {
     #[inline]
     fn into_response(self) -> axum::response::Response {
         #[derive(Serialize)]
-        struct ErrorInfo {
+        struct ErrorInfo<'a> {
             error: String,
+            /// Failed password policy rules.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            rules: Option<&'a [account::password::Rule]>,
+            /// Id of the failed request, see [`trace::request_id`].
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<String>,
         }
         (
             self.to_status_code(),
             axum::Json(ErrorInfo {
                 error: self.to_string(),
+                rules: if let Error::PasswordPolicy(ref rules) = self {
+                    Some(rules.as_slice())
+                } else {
+                    None
+                },
+                request_id: trace::request_id(),
             }),
         )
             .into_response()
     }
 }
 
+#[inline]
+fn display_rules(rules: &[account::password::Rule]) -> String {
+    rules
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Implements `From<T>` for [`Error`].
 macro_rules! impl_from {
     ($($t:ty => $v:ident),* $(,)?) => {
@@ -111,4 +310,10 @@ impl_from! {
     smtp::Error => Smtp,
     axum::http::header::ToStrError => HeaderNonAscii,
     dmds::Error => Database,
+    jsonwebtoken::errors::Error => Jwt,
+    reqwest::Error => OAuth,
+    csv::Error => Csv,
+    image::ImageError => Image,
+    std::io::Error => Io,
+    axum::extract::multipart::MultipartError => Multipart,
 }
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, net::IpAddr, sync::Mutex};
+
+use time::Instant;
+
+use crate::{config, Error};
+
+/// A fixed-window rate limiter keyed by strings.
+#[derive(Debug)]
+pub struct Limiter {
+    max: u32,
+    window: time::Duration,
+    /// Key => Window.
+    inner: Mutex<HashMap<String, Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+impl Limiter {
+    /// Creates a new limiter with given limit configuration.
+    #[inline]
+    pub fn new(config: &config::Limit) -> Self {
+        Self {
+            max: config.max,
+            window: time::Duration::seconds(config.window as i64),
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request of the given key.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if the key exceeds the limit in the current window,
+    /// with the duration until the window resets.
+    pub fn check(&self, key: &str) -> Result<(), Error> {
+        let mut inner = self.inner.lock().expect("rate limiter poisoned");
+        inner.retain(|_, w| w.start.elapsed() < self.window);
+        let window = inner.entry(key.to_owned()).or_insert_with(|| Window {
+            start: Instant::now(),
+            count: 0,
+        });
+        if window.count >= self.max {
+            return Err(Error::ReqTooFrequent(self.window - window.start.elapsed()));
+        }
+        window.count += 1;
+        Ok(())
+    }
+
+    /// Records a request from the given ip and email.
+    ///
+    /// # Errors
+    ///
+    /// - Errors if either the ip or the email exceeds the limit.
+    #[inline]
+    pub fn check_ip_email(&self, ip: IpAddr, email: &str) -> Result<(), Error> {
+        self.check(&format!("ip:{ip}"))?;
+        self.check(&format!("email:{email}"))
+    }
+}
+
+/// Rate limiters of sensitive account operations.
+#[derive(Debug)]
+pub struct Limiters {
+    pub login: Limiter,
+    pub register: Limiter,
+    pub reset_password: Limiter,
+    /// Attempts of verifying an email change, keyed by account.
+    pub change_email: Limiter,
+}
+
+impl Limiters {
+    /// Creates limiters with given configuration.
+    #[inline]
+    pub fn new(config: &config::RateLimit) -> Self {
+        Self {
+            login: Limiter::new(&config.login),
+            register: Limiter::new(&config.register),
+            reset_password: Limiter::new(&config.reset_password),
+            change_email: Limiter::new(&config.change_email),
+        }
+    }
+}
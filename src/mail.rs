@@ -0,0 +1,37 @@
+use lettre::{transport::smtp, AsyncSmtpTransport};
+
+use crate::{config, Error};
+
+/// Name of the email sender.
+const SENDER: &str = "SubIT";
+
+/// Sends a plain text email with given configuration and `transport`.
+pub async fn send<E>(
+    smtp_config: &config::Smtp,
+    to: lettre::Address,
+    subject: &str,
+    body: String,
+    transport: &AsyncSmtpTransport<E>,
+) -> Result<(), Error>
+where
+    E: lettre::Executor,
+    AsyncSmtpTransport<E>: lettre::AsyncTransport<Error = smtp::Error>,
+{
+    let msg = lettre::message::Message::builder()
+        .sender(lettre::message::Mailbox {
+            email: smtp_config.address.to_owned(),
+            name: Some(SENDER.to_owned()),
+        })
+        .to(lettre::message::Mailbox {
+            name: None,
+            email: to,
+        })
+        .subject(subject)
+        .body(body)?;
+    let result = lettre::AsyncTransport::send(transport, msg).await;
+    if let Err(err) = result {
+        tracing::error!("error sending email with smtp: {err}");
+        return Err(err.into());
+    }
+    Ok(())
+}
@@ -1,32 +1,522 @@
 use std::sync::Arc;
 
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
 use dmds::{IoHandle, World};
 use lettre::AsyncSmtpTransport;
 use sms3_backend::{
-    account::{department::Department, Account},
+    account::{
+        api_key::ApiKeys,
+        department::Department,
+        invite::Invite,
+        jwt::{self, Claims},
+        oauth,
+        role::Role,
+        verify::Tokens,
+        Account,
+    },
+    audit,
+    challenge::Challenges,
     config::Config,
+    limit::Limiters,
+    post::{
+        self, category::Category, comment::Comment, report::Report, stats::Stats,
+        template::Template, Post,
+    },
+    resource::{
+        scan::{self, Scanner},
+        storage::{self, Storage},
+        Resource, UploadSessions,
+    },
+    screen::Screen,
+    webhook::Webhook,
     Error,
 };
+use tokio::sync::{Mutex, RwLock};
 
-fn main() {}
+type Io = dmds_tokio_fs::FsHandle;
+
+/// Runs a job periodically in background, logging its errors.
+macro_rules! periodic {
+    ($name:literal, $secs:expr, $($c:ident),* => $job:expr) => {{
+        $(let $c = $c.clone();)*
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs($secs));
+            loop {
+                interval.tick().await;
+                if let Err(err) = $job.await {
+                    tracing::error!("failed to {}: {err}", $name);
+                }
+            }
+        });
+    }};
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().json().init();
+
+    let path = std::env::var(Config::PATH_ENV).unwrap_or_else(|_| Config::DEFAULT_PATH.to_owned());
+    let config = Arc::new(Config::load(path).expect("failed to load config"));
+    sms3_backend::id::set_node(config.server.node);
+
+    let worlds = Arc::new(Worlds::new(&config.server.data_path));
+    let post_index = handle::post::build_index(&worlds)
+        .await
+        .expect("failed to build post index");
+    let global = Global {
+        smtp_transport: Arc::new(
+            config
+                .smtp
+                .to_transport()
+                .expect("failed to build smtp transport"),
+        ),
+        worlds: worlds.clone(),
+        storage: storage::from_config(&config.resource).expect("failed to build storage"),
+        scanner: scan::from_config(&config.resource),
+        http_client: reqwest::Client::new(),
+        oauth_states: Arc::new(Mutex::new(oauth::States::new())),
+        jwt_states: Arc::new(Mutex::new(jwt::States::new(time::Duration::seconds(
+            config.token.jwt_state_ttl as i64,
+        )))),
+        limiters: Arc::new(Limiters::new(&config.rate_limit)),
+        challenges: Arc::new(Mutex::new(Challenges::new())),
+        upload_sessions: Arc::new(Mutex::new(UploadSessions::new(time::Duration::seconds(
+            config.resource.upload_session_ttl as i64,
+        )))),
+        post_index: Arc::new(RwLock::new(post_index)),
+        config: config.clone(),
+    };
+
+    let Global {
+        smtp_transport,
+        storage,
+        http_client,
+        upload_sessions,
+        post_index,
+        ..
+    } = global.clone();
+    periodic!("save worlds", 30, worlds => worlds.save());
+    periodic!("purge deletions", 60 * 60, worlds, post_index, storage =>
+        handle::account::purge_deletions(&worlds, &post_index, &*storage));
+    periodic!("schedule posts", 60, worlds, http_client => handle::post::schedule(&worlds, &http_client));
+    periodic!("remind expiries", 60 * 60, worlds, config, smtp_transport =>
+        handle::post::remind_expiries(&worlds, &config, &*smtp_transport));
+    periodic!("sweep upload sessions", 60, config, upload_sessions =>
+        handle::resource::sweep_sessions(&config, &upload_sessions));
+    periodic!("collect orphan resources", 60 * 60, worlds, config, storage =>
+        handle::resource::collect_orphans(&worlds, &config, &*storage));
+
+    let router = router(global);
+    let listener = tokio::net::TcpListener::bind(config.server.addr)
+        .await
+        .expect("failed to bind address");
+    let service = router.into_make_service_with_connect_info::<std::net::SocketAddr>();
+    if let Some(tls) = &config.server.tls {
+        let rustls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+            .await
+            .expect("failed to load tls certificate");
+        if tls.reload_interval > 0 {
+            periodic!("reload tls certificate", tls.reload_interval, config, rustls => {
+                let tls = config.server.tls.as_ref().unwrap();
+                rustls.reload_from_pem_file(&tls.cert, &tls.key)
+            });
+        }
+        tracing::info!("listening on {} with tls", config.server.addr);
+        axum_server::from_tcp_rustls(listener.into_std().expect("invalid listener"), rustls)
+            .serve(service)
+            .await
+            .expect("server errored")
+    } else {
+        tracing::info!("listening on {}", config.server.addr);
+        axum::serve(listener, service)
+            .await
+            .expect("server errored")
+    }
+}
+
+/// Routes of all handlers.
+fn router(global: Global<Io>) -> Router {
+    use axum::routing::{get, post};
+    use handle::{account, admin, openapi::ApiDoc, post as posts, resource, screen, webhook};
+    use utoipa::OpenApi;
+    use utoipa_swagger_ui::SwaggerUi;
+
+    let body_limit = global
+        .config
+        .resource
+        .max_size
+        .max(global.config.resource.max_video_size);
+    Router::new()
+        .route(
+            "/api/account/send-captcha",
+            post(account::send_captcha::<Io>),
+        )
+        .route("/api/account/challenge", get(account::challenge::<Io>))
+        .route("/api/account/register", post(account::register::<Io>))
+        .route("/api/account/login", post(account::login::<Io>))
+        .route(
+            "/api/account/oauth/authorize",
+            get(account::oauth_authorize::<Io>),
+        )
+        .route(
+            "/api/account/oauth/callback",
+            get(account::oauth_callback::<Io>),
+        )
+        .route(
+            "/api/account/refresh-token",
+            post(account::refresh_token::<Io>),
+        )
+        .route("/api/account/totp/enroll", post(account::enroll_totp::<Io>))
+        .route(
+            "/api/account/totp/confirm",
+            post(account::confirm_totp::<Io>),
+        )
+        .route(
+            "/api/account/totp/disable",
+            post(account::disable_totp::<Io>),
+        )
+        .route(
+            "/api/account/resend-verify",
+            post(account::resend_verify::<Io>),
+        )
+        .route("/api/account/verify", get(account::verify_link::<Io>))
+        .route(
+            "/api/account/send-reset-password-captcha",
+            post(account::send_reset_password_captcha::<Io>),
+        )
+        .route(
+            "/api/account/reset-password",
+            post(account::reset_password::<Io>),
+        )
+        .route("/api/account/get", get(account::self_info::<Io>))
+        .route("/api/account/sign-out", post(account::sign_out::<Io>))
+        .route("/api/account/restore", post(account::restore::<Io>))
+        .route(
+            "/api/account/notify-prefs",
+            get(account::notify_prefs::<Io>).post(account::set_notify_prefs::<Io>),
+        )
+        .route("/api/account/export", get(account::export::<Io>))
+        .route("/api/account/modify", post(account::modify::<Io>))
+        .route("/api/account/logout", post(account::logout::<Io>))
+        .route("/api/account/sessions", get(account::sessions::<Io>))
+        .route(
+            "/api/account/sessions/revoke",
+            post(account::revoke_session::<Io>),
+        )
+        .route(
+            "/api/account/req-change-email",
+            post(account::req_change_email::<Io>),
+        )
+        .route(
+            "/api/account/change-email",
+            post(account::change_email::<Io>),
+        )
+        .route(
+            "/api/account/set-permissions",
+            post(account::set_permissions::<Io>),
+        )
+        .route("/api/account/put-role", post(account::put_role::<Io>))
+        .route("/api/account/set-roles", post(account::set_roles::<Io>))
+        .route("/api/account/set-avatar", post(account::set_avatar::<Io>))
+        .route("/api/account/avatar/:id", get(account::avatar::<Io>))
+        .route(
+            "/api/account/api-keys",
+            get(account::api_keys::<Io>).post(account::create_api_key::<Io>),
+        )
+        .route(
+            "/api/account/revoke-api-key",
+            post(account::revoke_api_key::<Io>),
+        )
+        .route("/api/account/profile/:id", get(account::profile::<Io>))
+        .route("/api/account/set-privacy", post(account::set_privacy::<Io>))
+        .route(
+            "/api/account/manage/force-logout",
+            post(account::manage::force_logout::<Io>),
+        )
+        .route(
+            "/api/account/manage/reset-password",
+            post(account::manage::reset_password::<Io>),
+        )
+        .route(
+            "/api/account/manage/impersonate",
+            post(account::manage::impersonate::<Io>),
+        )
+        .route(
+            "/api/account/manage/import",
+            post(account::manage::import::<Io>),
+        )
+        .route(
+            "/api/account/manage/search",
+            post(account::manage::search::<Io>),
+        )
+        .route(
+            "/api/account/manage/list",
+            post(account::manage::list::<Io>),
+        )
+        .route(
+            "/api/account/manage/suspend",
+            post(account::manage::suspend::<Io>),
+        )
+        .route(
+            "/api/account/manage/unsuspend",
+            post(account::manage::unsuspend::<Io>),
+        )
+        .route(
+            "/api/account/manage/invites",
+            get(account::manage::invites::<Io>).post(account::manage::issue_invites::<Io>),
+        )
+        .route(
+            "/api/account/manage/revoke-invite",
+            post(account::manage::revoke_invite::<Io>),
+        )
+        .route(
+            "/api/account/manage/dormant",
+            post(account::manage::dormant::<Io>),
+        )
+        .route(
+            "/api/account/manage/grant",
+            post(account::manage::grant::<Io>),
+        )
+        .route(
+            "/api/account/manage/revoke-grant",
+            post(account::manage::revoke_grant::<Io>),
+        )
+        .route(
+            "/api/account/manage/permission-history",
+            post(account::manage::permission_history::<Io>),
+        )
+        .route(
+            "/api/account/manage/set-labels",
+            post(account::manage::set_labels::<Io>),
+        )
+        .route(
+            "/api/account/manage/approval-queue",
+            get(account::manage::approval_queue::<Io>),
+        )
+        .route(
+            "/api/account/manage/approve",
+            post(account::manage::approve::<Io>),
+        )
+        .route("/api/admin/audit", post(admin::audit::<Io>))
+        .route(
+            "/api/admin/collect-resources",
+            post(admin::collect_resources::<Io>),
+        )
+        .route("/api/post/create", post(posts::create::<Io>))
+        .route("/api/post/get", post(posts::get_posts::<Io>))
+        .route("/api/post/info", post(posts::get_posts_info::<Io>))
+        .route("/api/post/resource/:post/:id", get(posts::resource::<Io>))
+        .route(
+            "/api/post/public-image/:post/:id",
+            get(posts::public_image::<Io>),
+        )
+        .route("/api/post/image/:id", get(posts::get_image::<Io>))
+        .route("/api/post/image-info", post(posts::image_info::<Io>))
+        .route("/api/post/sign-url", post(posts::sign_url::<Io>))
+        .route("/api/post/export", post(posts::export::<Io>))
+        .route("/api/post/edit", post(posts::edit::<Io>))
+        .route("/api/post/clone", post(posts::clone::<Io>))
+        .route("/api/post/submit", post(posts::submit::<Io>))
+        .route("/api/post/assigned", get(posts::assigned::<Io>))
+        .route("/api/post/approve", post(posts::approve_post::<Io>))
+        .route("/api/post/approve-batch", post(posts::approve_batch::<Io>))
+        .route("/api/post/amend-review", post(posts::amend_review::<Io>))
+        .route("/api/post/delete", post(posts::delete::<Io>))
+        .route("/api/post/reorder", post(posts::reorder::<Io>))
+        .route("/api/post/history", post(posts::history::<Io>))
+        .route("/api/post/stats", post(posts::stats::<Io>))
+        .route("/api/post/stats/summary", post(posts::stats_summary::<Io>))
+        .route("/api/post/bookmark", post(posts::bookmark::<Io>))
+        .route("/api/post/bookmarks", get(posts::bookmarks::<Io>))
+        .route("/api/post/pin", post(posts::pin::<Io>))
+        .route("/api/post/archive", post(posts::archive::<Io>))
+        .route("/api/post/calendar.ics", get(posts::calendar::<Io>))
+        .route("/api/post/feed", get(posts::feed::<Io>))
+        .route(
+            "/api/post/categories",
+            get(posts::category::get_categories::<Io>).post(posts::category::put::<Io>),
+        )
+        .route(
+            "/api/post/categories/delete",
+            post(posts::category::delete::<Io>),
+        )
+        .route(
+            "/api/post/comment/create",
+            post(posts::comment::create::<Io>),
+        )
+        .route(
+            "/api/post/comment/get",
+            post(posts::comment::get_comments::<Io>),
+        )
+        .route(
+            "/api/post/comment/delete",
+            post(posts::comment::delete::<Io>),
+        )
+        .route("/api/post/report", post(posts::report::report::<Io>))
+        .route("/api/post/reports", get(posts::report::reports::<Io>))
+        .route(
+            "/api/post/report/resolve",
+            post(posts::report::resolve::<Io>),
+        )
+        .route(
+            "/api/post/templates",
+            get(posts::template::get_templates::<Io>).post(posts::template::put::<Io>),
+        )
+        .route(
+            "/api/post/templates/delete",
+            post(posts::template::delete::<Io>),
+        )
+        .route(
+            "/api/resource/new-session",
+            post(resource::new_session::<Io>),
+        )
+        .route("/api/resource/upload/:id", post(resource::upload::<Io>))
+        .route("/api/resource/batch", post(resource::upload_batch::<Io>))
+        .route("/api/resource/progress/:id", get(resource::progress::<Io>))
+        .route(
+            "/api/resource/chunk/:id",
+            post(resource::upload_chunk::<Io>),
+        )
+        .route("/api/resource/finalize/:id", post(resource::finalize::<Io>))
+        .route("/api/resource/usage", get(resource::usage::<Io>))
+        .route("/api/resource/free", post(resource::free::<Io>))
+        .route(
+            "/api/resource/signed/:id",
+            get(posts::signed_resource::<Io>).layer(axum::middleware::from_fn_with_state(
+                global.clone(),
+                posts::verify_signature::<Io>,
+            )),
+        )
+        .route("/api/screen/register", post(screen::register::<Io>))
+        .route("/api/screen/list", get(screen::screens::<Io>))
+        .route("/api/screen/assign", post(screen::assign::<Io>))
+        .route("/api/screen/remove", post(screen::remove::<Io>))
+        .route("/api/screen/playlist/:token", get(screen::playlist::<Io>))
+        .route("/api/screen/report/:token", post(screen::report::<Io>))
+        .route(
+            "/api/webhook",
+            get(webhook::webhooks::<Io>).post(webhook::register::<Io>),
+        )
+        .route("/api/webhook/remove", post(webhook::remove::<Io>))
+        .merge(SwaggerUi::new("/swagger-ui").url("/api/openapi.json", ApiDoc::openapi()))
+        .layer(axum::extract::DefaultBodyLimit::max(body_limit))
+        .layer(axum::middleware::from_fn(sms3_backend::trace::middleware))
+        .with_state(global)
+}
 
 #[derive(Debug, Clone)]
 pub struct Global<Io: IoHandle> {
     pub smtp_transport: Arc<AsyncSmtpTransport<lettre::Tokio1Executor>>,
     pub worlds: Arc<Worlds<Io>>,
     pub config: Arc<Config>,
+    pub storage: Arc<dyn Storage>,
+    /// Scanner of uploads, `None` if uploads are not scanned.
+    pub scanner: Option<Arc<dyn Scanner>>,
+
+    pub http_client: reqwest::Client,
+    pub oauth_states: Arc<Mutex<oauth::States>>,
+    pub jwt_states: Arc<Mutex<jwt::States>>,
+    pub limiters: Arc<Limiters>,
+    pub challenges: Arc<Mutex<Challenges>>,
+    pub upload_sessions: Arc<Mutex<UploadSessions>>,
+    pub post_index: Arc<RwLock<post::search::Index>>,
 }
 
 type AccountWorld<Io> = World<Account, 1, Io>;
 type UnverifiedAccountWorld<Io> = World<sms3_backend::account::Unverified, 1, Io>;
 type DepartmentWorld<Io> = World<Department, 1, Io>;
+type RoleWorld<Io> = World<Role, 1, Io>;
+type AuditWorld<Io> = World<audit::Entry, 2, Io>;
+type InviteWorld<Io> = World<Invite, 1, Io>;
+type PostWorld<Io> = World<Post, 1, Io>;
+type CommentWorld<Io> = World<Comment, 2, Io>;
+type CategoryWorld<Io> = World<Category, 1, Io>;
+type TemplateWorld<Io> = World<Template, 1, Io>;
+type ScreenWorld<Io> = World<Screen, 1, Io>;
+type StatsWorld<Io> = World<Stats, 3, Io>;
+type ReportWorld<Io> = World<Report, 2, Io>;
+type WebhookWorld<Io> = World<Webhook, 1, Io>;
+type ResourceWorld<Io> = World<Resource, 2, Io>;
 
 #[derive(Debug)]
 pub struct Worlds<Io: IoHandle> {
     account: AccountWorld<Io>,
     unverified_account: UnverifiedAccountWorld<Io>,
+    invite: InviteWorld<Io>,
 
     department: DepartmentWorld<Io>,
+    role: RoleWorld<Io>,
+    audit: AuditWorld<Io>,
+    resource: ResourceWorld<Io>,
+    post: PostWorld<Io>,
+    comment: CommentWorld<Io>,
+    category: CategoryWorld<Io>,
+    template: TemplateWorld<Io>,
+    screen: ScreenWorld<Io>,
+    stats: StatsWorld<Io>,
+    report: ReportWorld<Io>,
+    webhook: WebhookWorld<Io>,
+}
+
+impl Worlds<Io> {
+    /// Opens the worlds under the data directory.
+    fn new(root: &std::path::Path) -> Self {
+        macro_rules! world {
+            ($dir:literal, $($c:expr => $r:expr),+) => {
+                dmds::world! {
+                    dmds_tokio_fs::FsHandle::new(root.join($dir), false), $($c | $r),+
+                }
+            };
+        }
+
+        const ALL: std::ops::RangeFull = ..;
+        Self {
+            account: world!("accounts", 1 << 48 => ALL),
+            unverified_account: world!("unverified_accounts", 1 << 48 => ALL),
+            invite: world!("invites", 1 << 48 => ALL),
+            department: world!("departments", 1 << 56 => ALL),
+            role: world!("roles", 1 << 56 => ALL),
+            audit: world!("audit", 1 << 48 => ALL, 60 * 60 * 24 * 7 => ALL),
+            resource: world!("resources", 1 << 48 => ALL, 1 => ..=1),
+            post: world!("posts", 1 << 48 => ALL),
+            comment: world!("comments", 1 << 48 => ALL, 1 << 48 => ALL),
+            category: world!("categories", 1 << 56 => ALL),
+            template: world!("templates", 1 << 56 => ALL),
+            screen: world!("screens", 1 << 56 => ALL),
+            stats: world!("stats", 1 << 48 => ALL, 1 << 48 => ALL, 30 => ALL),
+            report: world!("reports", 1 << 48 => ALL, 1 << 48 => ALL),
+            webhook: world!("webhooks", 1 << 56 => ALL),
+        }
+    }
+
+    /// Writes buffered chunks of all the worlds to the file system.
+    async fn save(&self) -> Result<(), Error> {
+        macro_rules! save {
+            ($($w:ident),+) => {
+                $(dmds_tokio_fs::write_buf(&self.$w).await?;)+
+            };
+        }
+
+        save!(
+            account,
+            unverified_account,
+            invite,
+            department,
+            role,
+            audit,
+            resource,
+            post,
+            comment,
+            category,
+            template,
+            screen,
+            stats,
+            report,
+            webhook
+        );
+        Ok(())
+    }
 }
 
 mod handle {
@@ -52,32 +542,141 @@ mod handle {
     }
 
     /// Validates an account.
+    ///
+    /// Permissions are resolved through both the account and its roles,
+    /// so the worlds should be given if any permission is required.
+    ///
+    /// If the request is authorized with a JWT, permissions are validated
+    /// with its claims, and the account state is validated while
+    /// extracting [`Auth`].
+    ///
+    /// If the request is authorized with an API key, the required
+    /// permissions should be also granted to the key.
     macro_rules! va {
-        ($a:expr, $s:expr => $($p:expr),*$(,)?) => {{
-            let lazy = ga!($s, $a.account).ok_or(Error::PermissionDenied)?;
-            let a = lazy.get().await?;
-            if a.is_token_valid(&$a.token) {
-                let _tags = a.tags();
-                if !($(_tags.contains_permission(&sms3_backend::account::Tag::Permission($p)) &&)* true) {
-                    return Err($crate::Error::PermissionDenied);
-                }
+        ($a:expr, $s:expr, $w:expr => $($p:expr),+$(,)?) => {{
+            let lazy = va!($a, $s);
+            let required = [$($p),+];
+            let permitted = if let Some(ref claims) = $a.claims {
+                claims.contains_permissions(&required)
             } else {
-                return Err($crate::Error::LibAccount(libaccount::Error::InvalidToken));
+                $crate::handle::permitted(&$w, &$a, lazy.get().await?, &required).await?
+            };
+            if !permitted {
+                return Err($crate::Error::PermissionDenied);
             }
             lazy
         }};
-        ($a:expr, $s:expr) => {
-            va!($a, $s =>)
+        ($a:expr, $s:expr) => {{
+            let mut lazy = ga!($s, $a.account).ok_or(Error::PermissionDenied)?;
+            if $a.claims.is_none() {
+                let a = lazy.get_mut().await?;
+                let valid = if $a.is_api_key() {
+                    a.api_keys_mut().touch(&$a.token).is_some()
+                } else {
+                    a.touch_token(&$a.token)
+                };
+                if !valid {
+                    return Err($crate::Error::LibAccount(libaccount::Error::InvalidToken));
+                }
+                a.check_suspension()?;
+            }
+            lazy
+        }};
+    }
+
+    /// Appends an entry to the audit log.
+    pub async fn record_audit<Io: dmds::IoHandle>(
+        worlds: &crate::Worlds<Io>,
+        actor: u64,
+        action: sms3_backend::audit::Action,
+    ) -> Result<(), crate::Error> {
+        worlds
+            .audit
+            .insert(sms3_backend::audit::Entry::new(actor, action))
+            .await
+            .map_err(From::from)
+    }
+
+    /// Whether the request has all the required permissions,
+    /// resolved through the JWT claims, the API key, the account
+    /// and its roles.
+    pub async fn permitted<Io: dmds::IoHandle>(
+        worlds: &crate::Worlds<Io>,
+        auth: &crate::Auth,
+        account: &sms3_backend::account::Account,
+        required: &[sms3_backend::account::Permission],
+    ) -> Result<bool, crate::Error> {
+        if let Some(ref claims) = auth.claims {
+            return Ok(claims.contains_permissions(required));
+        }
+        if auth.is_api_key()
+            && !account
+                .api_keys()
+                .permissions(&auth.token)
+                .map_or(false, |granted| {
+                    required
+                        .iter()
+                        .all(|p| granted.iter().any(|g| g.implies(*p)))
+                })
+        {
+            return Ok(false);
         }
+        if required.iter().all(|p| account.has_permission(*p)) {
+            return Ok(true);
+        }
+        let granted = role_permissions(worlds, account).await?;
+        Ok(required
+            .iter()
+            .all(|p| account.has_permission(*p) || granted.iter().any(|g| g.implies(*p))))
+    }
+
+    /// Collects permissions granted by roles of the account.
+    pub async fn role_permissions<Io: dmds::IoHandle>(
+        worlds: &crate::Worlds<Io>,
+        account: &sms3_backend::account::Account,
+    ) -> Result<std::collections::HashSet<sms3_backend::account::Permission>, crate::Error> {
+        let mut permissions = std::collections::HashSet::new();
+        let mut ids = account.roles();
+        let Some(first) = ids.pop() else {
+            return Ok(permissions);
+        };
+        let mut select = worlds.role.select(0, first);
+        for id in ids.iter() {
+            select = select.plus(0, *id);
+        }
+        ids.push(first);
+        let mut iter = select.iter();
+        while let Some(Ok(l)) = dmds::StreamExt::next(&mut iter).await {
+            if ids.contains(&l.id()) {
+                permissions.extend(l.get().await?.permissions().iter().copied());
+            }
+        }
+        Ok(permissions)
     }
 
     pub mod account;
+    pub mod admin;
+    pub mod openapi;
+    pub mod post;
+    pub mod resource;
+    pub mod screen;
+    pub mod webhook;
 }
 
 #[derive(Debug)]
 pub struct Auth {
     account: u64,
     token: String,
+    /// Claims of the token if it's a validated JWT.
+    claims: Option<Claims>,
+}
+
+impl Auth {
+    /// Whether the token is an API key.
+    #[inline]
+    fn is_api_key(&self) -> bool {
+        ApiKeys::is_api_key(&self.token)
+    }
 }
 
 #[async_trait::async_trait]
@@ -86,7 +685,7 @@ impl<Io: IoHandle> axum::extract::FromRequestParts<Global<Io>> for Auth {
 
     async fn from_request_parts(
         parts: &mut axum::http::request::Parts,
-        _state: &Global<Io>,
+        state: &Global<Io>,
     ) -> Result<Self, Self::Rejection> {
         const KEY: &str = "Authorization";
         let raw = parts.headers.remove(KEY).ok_or(Error::NotLoggedIn)?;
@@ -94,9 +693,85 @@ impl<Io: IoHandle> axum::extract::FromRequestParts<Global<Io>> for Auth {
             .to_str()?
             .split_once(':')
             .ok_or(Error::InvalidAuthHeader)?;
+        let account = account.parse().map_err(|_| Error::InvalidAuthHeader)?;
+        let claims = if ApiKeys::is_api_key(token) {
+            None
+        } else if Tokens::is_impersonation(token) {
+            record_impersonation(state, account, token, parts).await?;
+            None
+        } else if let Some(secret) = state.config.token.jwt_secret() {
+            let claims = Claims::decode(token, secret)?;
+            if claims.sub != account {
+                return Err(Error::InvalidAuthHeader);
+            }
+            validate_jwt_state(state, &claims).await?;
+            Some(claims)
+        } else {
+            None
+        };
         Ok(Self {
-            account: account.parse().map_err(|_| Error::InvalidAuthHeader)?,
+            account,
             token: token.to_owned(),
+            claims,
         })
     }
 }
+
+/// Rejects JWTs issued before the token epoch of the account changed,
+/// and those of suspended accounts or accounts pending deletion.
+///
+/// Usable accounts are cached in [`Global::jwt_states`].
+async fn validate_jwt_state<Io: IoHandle>(
+    state: &Global<Io>,
+    claims: &Claims,
+) -> Result<(), Error> {
+    if state.jwt_states.lock().await.is_valid(claims) {
+        return Ok(());
+    }
+    let select = state.worlds.account.select(0, claims.sub).hint(claims.sub);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = dmds::StreamExt::next(&mut iter).await {
+        if lazy.id() == claims.sub {
+            let account = lazy.get().await?;
+            if claims.epoch != account.token_epoch() {
+                return Err(Error::LibAccount(libaccount::Error::InvalidToken));
+            }
+            account.check_suspension()?;
+            account.check_deletion()?;
+            state.jwt_states.lock().await.insert(account);
+            return Ok(());
+        }
+    }
+    Err(Error::PermissionDenied)
+}
+
+/// Records the request in the audit log if the token
+/// is a valid impersonation token.
+async fn record_impersonation<Io: IoHandle>(
+    state: &Global<Io>,
+    account: u64,
+    token: &str,
+    parts: &axum::http::request::Parts,
+) -> Result<(), Error> {
+    let select = state.worlds.account.select(0, account).hint(account);
+    let mut iter = select.iter();
+    let mut impersonator = None;
+    while let Some(Ok(lazy)) = dmds::StreamExt::next(&mut iter).await {
+        if lazy.id() == account {
+            impersonator = lazy.get().await?.tokens().impersonator(token);
+        }
+    }
+    if let Some(impersonator) = impersonator {
+        handle::record_audit(
+            &state.worlds,
+            impersonator,
+            audit::Action::ImpersonatedRequest {
+                target: account,
+                method: parts.method.to_string(),
+                path: parts.uri.path().to_owned(),
+            },
+        )
+        .await?;
+    }
+    Ok(())
+}
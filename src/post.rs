@@ -1,14 +1,69 @@
-use std::ops::RangeInclusive;
+use std::{collections::HashSet, ops::RangeInclusive};
 
 use serde::{Deserialize, Serialize};
-use time::OffsetDateTime;
+use time::{Date, Duration, OffsetDateTime, Weekday};
 
-#[derive(Debug)]
+pub mod category;
+pub mod comment;
+pub mod feed;
+pub mod ical;
+pub mod markdown;
+pub mod report;
+pub mod search;
+pub mod stats;
+pub mod template;
+
+/// Operator of states pushed automatically by the server.
+pub const SYSTEM_OPERATOR: u64 = 0;
+
+/// A poster submitted by an account.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Post {
+    #[serde(skip)]
     id: u64,
     title: String,
+    description: String,
+    description_format: DescriptionFormat,
+    /// Ids of image resources.
+    images: Vec<u64>,
+    /// Ids of PDF resources.
+    attachments: Vec<u64>,
+    /// Ids of video resources.
+    videos: Vec<u64>,
     /// On-screen time range.
     dates: RangeInclusive<time::Date>,
+    /// Name of the [`category::Category`].
+    category: Option<String>,
+    /// Free-form tags given by the publisher.
+    tags: Vec<String>,
+    /// Accounts sharing edit rights with the creator.
+    co_publishers: HashSet<u64>,
+    /// Audience of this post once it's active.
+    scope: Scope,
+    /// Display priority given by admins, higher is more important.
+    priority: i64,
+    recurrence: Option<Recurrence>,
+    /// Materialized upcoming display windows of the recurrence,
+    /// excluding [`Self::dates`].
+    windows: Vec<RangeInclusive<Date>>,
+
+    /// Append-only log of edits in time order.
+    revisions: Vec<Revision>,
+    /// Approvers of the current review, cleared when
+    /// the post becomes pending again.
+    approvals: HashSet<u64>,
+    /// Name of the role of reviewers this post is assigned to.
+    pool: Option<String>,
+    /// Whether the publisher was reminded before expiry.
+    reminded: bool,
+    /// Pin forcing this post to the top of displays and lists.
+    pin: Option<Pin>,
 
     /// Post states in time order.\
     /// There should be at least one state in a post.
@@ -16,6 +71,333 @@ pub struct Post {
 }
 
 impl Post {
+    /// Creates a new pending or draft post with a generated id.
+    pub fn new(
+        title: String,
+        description: String,
+        images: Vec<u64>,
+        dates: RangeInclusive<time::Date>,
+        creator: u64,
+        draft: bool,
+    ) -> Self {
+        let status = if draft {
+            Status::Draft
+        } else {
+            Status::Pending
+        };
+        Self {
+            id: crate::id::next(),
+            title,
+            description,
+            description_format: DescriptionFormat::Plain,
+            images,
+            attachments: vec![],
+            videos: vec![],
+            dates,
+            category: None,
+            tags: vec![],
+            co_publishers: HashSet::new(),
+            scope: Scope::Everyone,
+            priority: 0,
+            recurrence: None,
+            windows: vec![],
+            revisions: vec![],
+            approvals: HashSet::new(),
+            pool: None,
+            reminded: false,
+            pin: None,
+            states: vec![State::new(status, creator, String::new())],
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    #[inline]
+    pub fn set_title(&mut self, title: String) {
+        self.title = title
+    }
+
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    #[inline]
+    pub fn set_description(&mut self, description: String) {
+        self.description = description
+    }
+
+    #[inline]
+    pub fn description_format(&self) -> DescriptionFormat {
+        self.description_format
+    }
+
+    #[inline]
+    pub fn set_description_format(&mut self, format: DescriptionFormat) {
+        self.description_format = format
+    }
+
+    /// Renders the description into sanitized HTML
+    /// if it's in markdown.
+    pub fn description_html(&self) -> Option<String> {
+        match self.description_format {
+            DescriptionFormat::Plain => None,
+            DescriptionFormat::Markdown => Some(markdown::render(&self.description)),
+        }
+    }
+
+    /// Ids of image resources.
+    #[inline]
+    pub fn images(&self) -> &[u64] {
+        &self.images
+    }
+
+    /// Sets images, returns the previous ones.
+    #[inline]
+    pub fn set_images(&mut self, images: Vec<u64>) -> Vec<u64> {
+        std::mem::replace(&mut self.images, images)
+    }
+
+    /// Ids of PDF resources.
+    #[inline]
+    pub fn attachments(&self) -> &[u64] {
+        &self.attachments
+    }
+
+    /// Sets attachments, returns the previous ones.
+    #[inline]
+    pub fn set_attachments(&mut self, attachments: Vec<u64>) -> Vec<u64> {
+        std::mem::replace(&mut self.attachments, attachments)
+    }
+
+    /// Ids of video resources.
+    #[inline]
+    pub fn videos(&self) -> &[u64] {
+        &self.videos
+    }
+
+    /// Sets videos, returns the previous ones.
+    #[inline]
+    pub fn set_videos(&mut self, videos: Vec<u64>) -> Vec<u64> {
+        std::mem::replace(&mut self.videos, videos)
+    }
+
+    /// On-screen time range.
+    #[inline]
+    pub fn dates(&self) -> &RangeInclusive<time::Date> {
+        &self.dates
+    }
+
+    /// Sets the on-screen time range, which resets the expiry
+    /// reminder if the range ends at another date.
+    #[inline]
+    pub fn set_dates(&mut self, dates: RangeInclusive<time::Date>) {
+        if dates.end() != self.dates.end() {
+            self.reminded = false;
+        }
+        self.dates = dates
+    }
+
+    /// Whether the publisher was reminded before expiry.
+    #[inline]
+    pub fn is_reminded(&self) -> bool {
+        self.reminded
+    }
+
+    #[inline]
+    pub fn set_reminded(&mut self) {
+        self.reminded = true
+    }
+
+    #[inline]
+    pub fn pin(&self) -> Option<&Pin> {
+        self.pin.as_ref()
+    }
+
+    #[inline]
+    pub fn set_pin(&mut self, pin: Option<Pin>) {
+        self.pin = pin
+    }
+
+    /// Whether this post is pinned and the pin is not expired at the time.
+    #[inline]
+    pub fn is_pinned(&self, now: OffsetDateTime) -> bool {
+        self.pin
+            .as_ref()
+            .map_or(false, |p| p.until.map_or(true, |u| now < u))
+    }
+
+    /// Name of the [`category::Category`] of this post.
+    #[inline]
+    pub fn category(&self) -> Option<&String> {
+        self.category.as_ref()
+    }
+
+    #[inline]
+    pub fn set_category(&mut self, category: Option<String>) {
+        self.category = category
+    }
+
+    #[inline]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    #[inline]
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags
+    }
+
+    /// Accounts sharing edit rights with the creator.
+    #[inline]
+    pub fn co_publishers(&self) -> &HashSet<u64> {
+        &self.co_publishers
+    }
+
+    #[inline]
+    pub fn set_co_publishers(&mut self, co_publishers: HashSet<u64>) {
+        self.co_publishers = co_publishers
+    }
+
+    /// Audience of this post once it's active.
+    #[inline]
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    #[inline]
+    pub fn set_scope(&mut self, scope: Scope) {
+        self.scope = scope
+    }
+
+    /// Whether the account is the creator or a co-publisher.
+    #[inline]
+    pub fn is_publisher(&self, account: u64) -> bool {
+        self.creator() == account || self.co_publishers.contains(&account)
+    }
+
+    /// Display priority given by admins, higher is more important.
+    #[inline]
+    pub fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    #[inline]
+    pub fn set_priority(&mut self, priority: i64) {
+        self.priority = priority
+    }
+
+    #[inline]
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    /// Sets the recurrence rule, clearing materialized windows
+    /// and the expiry reminder.
+    #[inline]
+    pub fn set_recurrence(&mut self, recurrence: Option<Recurrence>) {
+        self.recurrence = recurrence;
+        self.windows.clear();
+        self.reminded = false;
+    }
+
+    /// Materialized upcoming display windows of the recurrence.
+    #[inline]
+    pub fn windows(&self) -> &[RangeInclusive<Date>] {
+        &self.windows
+    }
+
+    /// Materializes display windows of the recurrence which
+    /// haven't ended at the date and start within [`Recurrence::HORIZON`].
+    pub fn materialize(&mut self, date: Date) {
+        self.windows = self
+            .recurrence
+            .as_ref()
+            .map(|r| r.windows(&self.dates, date))
+            .unwrap_or_default();
+    }
+
+    /// Whether this post should be on screen at the date,
+    /// in its time range or any of its materialized windows.
+    pub fn on_screen(&self, date: Date) -> bool {
+        self.dates.contains(&date) || self.windows.iter().any(|w| w.contains(&date))
+    }
+
+    /// Whether this post should be on screen at any date of the range,
+    /// in its time range or any of its materialized windows.
+    pub fn overlaps(&self, range: &RangeInclusive<Date>) -> bool {
+        let overlaps = |dates: &RangeInclusive<Date>| {
+            dates.start() <= range.end() && range.start() <= dates.end()
+        };
+        overlaps(&self.dates) || self.windows.iter().any(overlaps)
+    }
+
+    /// The last date this post could be on screen.
+    pub fn last_date(&self) -> Date {
+        let end = *self.dates.end();
+        self.recurrence
+            .as_ref()
+            .map_or(end, |r| end.max(r.until + (end - *self.dates.start())))
+    }
+
+    /// Whether this post is a draft, which is only
+    /// visible to the publisher.
+    #[inline]
+    pub fn is_draft(&self) -> bool {
+        self.state().status() == Status::Draft
+    }
+
+    /// Whether this post is published on screen.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.state().status() == Status::Active
+    }
+
+    /// Transitions this post by its on-screen time range at the date,
+    /// returns the new status if transitioned.
+    ///
+    /// Approved posts become [`Status::Active`] when the range or
+    /// a window of the recurrence starts, active posts become
+    /// [`Status::Approved`] again between windows, and approved or active
+    /// posts become [`Status::Archived`] when the last window ends.
+    pub fn transition(&mut self, date: time::Date) -> Option<Status> {
+        self.materialize(date);
+        let status = match self.state().status() {
+            Status::Approved | Status::Active if self.last_date() < date => Status::Archived,
+            Status::Approved if self.on_screen(date) => Status::Active,
+            Status::Active if !self.on_screen(date) => Status::Approved,
+            _ => return None,
+        };
+        self.push_state(status, SYSTEM_OPERATOR, String::new());
+        Some(status)
+    }
+
+    /// Edits of this post in time order.
+    #[inline]
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    /// Appends a revision if there are any changes.
+    #[inline]
+    pub fn push_revision(&mut self, editor: u64, changes: Vec<Change>) {
+        if !changes.is_empty() {
+            self.revisions.push(Revision {
+                editor,
+                time: OffsetDateTime::now_utc(),
+                changes,
+            })
+        }
+    }
+
     /// Gets the overall states of this post.
     #[inline]
     pub fn states(&self) -> &[State] {
@@ -30,6 +412,77 @@ impl Post {
             .expect("there should be at least one state in a post")
     }
 
+    /// Pushes a new state of this post.
+    #[inline]
+    pub fn push_state(&mut self, status: Status, operator: u64, message: String) {
+        if status == Status::Pending {
+            self.approvals.clear();
+        }
+        self.states.push(State::new(status, operator, message))
+    }
+
+    /// Name of the role of reviewers this post is assigned to.
+    #[inline]
+    pub fn pool(&self) -> Option<&str> {
+        self.pool.as_deref()
+    }
+
+    #[inline]
+    pub fn set_pool(&mut self, pool: Option<String>) {
+        self.pool = pool
+    }
+
+    /// Approvers of the current review.
+    #[inline]
+    pub fn approvals(&self) -> &HashSet<u64> {
+        &self.approvals
+    }
+
+    /// The last approving or rejecting state of this post.
+    pub fn last_decision(&self) -> Option<&State> {
+        self.states
+            .iter()
+            .rev()
+            .find(|s| matches!(s.status, Status::Approved | Status::Rejected))
+    }
+
+    /// Records an approval of a pending post, and approves the post
+    /// if there are at least `quorum` distinct approvers.
+    ///
+    /// Returns whether the post was approved.
+    pub fn approve(&mut self, approver: u64, quorum: usize, message: String) -> bool {
+        self.approvals.insert(approver);
+        let approved = self.approvals.len() >= quorum;
+        if approved {
+            self.push_state(Status::Approved, approver, message);
+        }
+        approved
+    }
+
+    /// Whether the account is referred by this post as a publisher,
+    /// an approver or an operator of its history.
+    pub fn involves(&self, account: u64) -> bool {
+        self.co_publishers.contains(&account)
+            || self.approvals.contains(&account)
+            || self.states.iter().any(|s| s.operator == account)
+            || self.revisions.iter().any(|r| r.editor == account)
+    }
+
+    /// Replaces references to an account whose id changed.
+    pub fn replace_account(&mut self, from: u64, to: u64) {
+        for set in [&mut self.co_publishers, &mut self.approvals] {
+            if set.remove(&from) {
+                set.insert(to);
+            }
+        }
+        for s in self.states.iter_mut().filter(|s| s.operator == from) {
+            s.operator = to;
+        }
+        for r in self.revisions.iter_mut().filter(|r| r.editor == from) {
+            r.editor = to;
+        }
+    }
+
     #[inline]
     pub fn creator(&self) -> u64 {
         self.states
@@ -37,13 +490,115 @@ impl Post {
             .expect("there should be at least one state in a post")
             .operator
     }
+
+    /// Creation time of this post.
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.states
+            .first()
+            .expect("there should be at least one state in a post")
+            .time
+    }
+}
+
+impl dmds::Data for Post {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 2;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                /// Layout with only the content, time range and states.
+                #[derive(Deserialize)]
+                struct PostV1 {
+                    title: String,
+                    description: String,
+                    images: Vec<u64>,
+                    dates: RangeInclusive<time::Date>,
+                    states: Vec<State>,
+                }
+
+                let v1: PostV1 = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                Ok(Self {
+                    id: dims[0],
+                    title: v1.title,
+                    description: v1.description,
+                    description_format: DescriptionFormat::Plain,
+                    images: v1.images,
+                    attachments: vec![],
+                    videos: vec![],
+                    dates: v1.dates,
+                    category: None,
+                    tags: vec![],
+                    co_publishers: HashSet::new(),
+                    scope: Scope::Everyone,
+                    priority: 0,
+                    recurrence: None,
+                    windows: vec![],
+                    revisions: vec![],
+                    approvals: HashSet::new(),
+                    pool: None,
+                    reminded: false,
+                    pin: None,
+                    states: v1.states,
+                })
+            }
+            2 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
+
+/// Audience of an active [`Post`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Everyone, including public screens.
+    #[default]
+    Everyone,
+    /// Accounts with the house label.
+    House(String),
+    /// Accounts in the department.
+    Department(String),
+    /// Accounts with [`crate::account::Permission::GetStaffPosts`].
+    Staff,
+}
+
+/// A pin of a [`Post`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Pin {
+    pub operator: u64,
+    /// Expiry of this pin, pinned forever if not present.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub until: Option<OffsetDateTime>,
 }
 
 /// State of a [`Post`].
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
 pub struct State {
     status: Status,
     #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
     time: OffsetDateTime,
     operator: u64,
 
@@ -52,6 +607,17 @@ pub struct State {
 }
 
 impl State {
+    /// Creates a new state at the current time.
+    #[inline]
+    pub fn new(status: Status, operator: u64, message: String) -> Self {
+        Self {
+            status,
+            time: OffsetDateTime::now_utc(),
+            operator,
+            message,
+        }
+    }
+
     /// [`Status`] of this state.
     #[inline]
     pub fn status(&self) -> Status {
@@ -77,9 +643,218 @@ impl State {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, utoipa::ToSchema)]
 pub enum Status {
     Pending,
+    /// Approved but not on screen yet.
     Approved,
     Rejected,
+    /// Published on screen.
+    Active,
+    /// The on-screen time range has passed.
+    Archived,
+    /// Not submitted for reviewing yet.
+    Draft,
+    /// Returned to the publisher by a reviewer for changes,
+    /// with the requested changes as the message.
+    ChangesRequested,
+}
+
+/// A weekly recurrence rule of a [`Post`].
+///
+/// Each window lasts as long as the time range of the post,
+/// and starts at given weekdays of every `interval` weeks
+/// since the start of the post.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct Recurrence {
+    #[schema(value_type = Vec<String>)]
+    pub weekdays: Vec<Weekday>,
+    /// Interval in weeks.
+    pub interval: u8,
+    /// The last date a window could start.
+    pub until: Date,
+}
+
+impl Recurrence {
+    /// Days ahead to materialize windows.
+    pub const HORIZON: Duration = Duration::days(28);
+
+    /// Whether this rule is valid for the time range.
+    pub fn is_valid(&self, dates: &RangeInclusive<Date>) -> bool {
+        !self.weekdays.is_empty() && self.interval > 0 && self.until >= *dates.start()
+    }
+
+    /// Windows which haven't ended at the date and start within
+    /// [`Self::HORIZON`], excluding the time range itself.
+    fn windows(&self, dates: &RangeInclusive<Date>, date: Date) -> Vec<RangeInclusive<Date>> {
+        let start = *dates.start();
+        let len = *dates.end() - start;
+        let week_of = |d: Date| d - Duration::days(d.weekday().number_days_from_monday() as i64);
+        let first_week = week_of(start);
+
+        let mut windows = vec![];
+        let mut day = (start + Duration::DAY).max(date - len);
+        let last = self.until.min(date + Self::HORIZON);
+        while day <= last {
+            let week = (week_of(day) - first_week).whole_weeks();
+            if self.weekdays.contains(&day.weekday()) && week % self.interval as i64 == 0 {
+                windows.push(day..=day + len);
+            }
+            day = day + Duration::DAY;
+        }
+        windows
+    }
+}
+
+/// A revision of a [`Post`] made by an edit.
+#[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
+pub struct Revision {
+    editor: u64,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    time: OffsetDateTime,
+    changes: Vec<Change>,
+}
+
+impl Revision {
+    #[inline]
+    pub fn editor(&self) -> u64 {
+        self.editor
+    }
+
+    #[inline]
+    pub fn time(&self) -> OffsetDateTime {
+        self.time
+    }
+
+    #[inline]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+/// A changed field in a [`Revision`].
+#[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Title {
+        from: String,
+        to: String,
+    },
+    Description {
+        from: String,
+        to: String,
+    },
+    Images {
+        from: Vec<u64>,
+        to: Vec<u64>,
+    },
+    Attachments {
+        from: Vec<u64>,
+        to: Vec<u64>,
+    },
+    Dates {
+        #[schema(value_type = Object)]
+        from: RangeInclusive<Date>,
+        #[schema(value_type = Object)]
+        to: RangeInclusive<Date>,
+    },
+    Videos {
+        from: Vec<u64>,
+        to: Vec<u64>,
+    },
+    Tags {
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+    Category {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Scope {
+        from: Scope,
+        to: Scope,
+    },
+    CoPublishers {
+        from: Vec<u64>,
+        to: Vec<u64>,
+    },
+    Recurrence {
+        from: Option<Recurrence>,
+        to: Option<Recurrence>,
+    },
+}
+
+/// Format of the description of a [`Post`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptionFormat {
+    #[default]
+    Plain,
+    Markdown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Day of January 2024, where 2024-01-01 is a Monday.
+    fn jan(day: u8) -> Date {
+        Date::from_calendar_date(2024, time::Month::January, day).unwrap()
+    }
+
+    fn recurrence(weekdays: Vec<Weekday>, interval: u8, until: Date) -> Recurrence {
+        Recurrence {
+            weekdays,
+            interval,
+            until,
+        }
+    }
+
+    #[test]
+    fn windows_weekly() {
+        let r = recurrence(vec![Weekday::Monday], 1, jan(29));
+        assert_eq!(
+            r.windows(&(jan(1)..=jan(2)), jan(1)),
+            [8, 15, 22, 29].map(|d| jan(d)..=jan(d + 1))
+        );
+    }
+
+    #[test]
+    fn windows_interval() {
+        let r = recurrence(vec![Weekday::Monday, Weekday::Wednesday], 2, jan(31));
+        // Jan 31 is beyond the horizon.
+        assert_eq!(
+            r.windows(&(jan(1)..=jan(1)), jan(1)),
+            [3, 15, 17, 29].map(|d| jan(d)..=jan(d))
+        );
+    }
+
+    #[test]
+    fn windows_skip_ended() {
+        let r = recurrence(vec![Weekday::Monday], 1, jan(29));
+        // The window starting at Jan 15 hasn't ended yet.
+        assert_eq!(
+            r.windows(&(jan(1)..=jan(2)), jan(16)),
+            [15, 22, 29].map(|d| jan(d)..=jan(d + 1))
+        );
+    }
+
+    #[test]
+    fn windows_horizon() {
+        let until = Date::from_calendar_date(2024, time::Month::December, 31).unwrap();
+        let r = recurrence(vec![Weekday::Monday], 1, until);
+        let windows = r.windows(&(jan(1)..=jan(1)), jan(1));
+        assert_eq!(windows.last(), Some(&(jan(29)..=jan(29))));
+        assert_eq!(windows.len(), 4);
+    }
+
+    #[test]
+    fn recurrence_validity() {
+        let dates = jan(8)..=jan(9);
+        assert!(recurrence(vec![Weekday::Monday], 1, jan(8)).is_valid(&dates));
+        assert!(!recurrence(vec![], 1, jan(31)).is_valid(&dates));
+        assert!(!recurrence(vec![Weekday::Monday], 0, jan(31)).is_valid(&dates));
+        assert!(!recurrence(vec![Weekday::Monday], 1, jan(7)).is_valid(&dates));
+    }
 }
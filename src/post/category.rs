@@ -0,0 +1,88 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+/// An admin-managed category of posts, referenced
+/// by name from [`super::Post`].
+///
+/// # dmds Integration
+///
+/// The id is derived from the name, see [`Self::id_of`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Category {
+    #[serde(skip)]
+    id: u64,
+    name: String,
+    description: String,
+}
+
+impl Category {
+    /// Creates a new category with given name and description.
+    #[inline]
+    pub fn new(name: String, description: String) -> Self {
+        Self {
+            id: Self::id_of(&name),
+            name,
+            description,
+        }
+    }
+
+    /// Gets the id of a category with given name.
+    #[inline]
+    pub fn id_of(name: &str) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    #[inline]
+    pub fn set_description(&mut self, description: String) {
+        self.description = description
+    }
+}
+
+impl dmds::Data for Category {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// A comment on a [`super::Post`], optionally replying
+/// to another comment of the same post.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// 1 -> post
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Comment {
+    #[serde(skip)]
+    id: u64,
+    #[serde(skip)]
+    post: u64,
+    /// The comment this comment replies to.
+    parent: Option<u64>,
+    author: u64,
+    content: String,
+    #[serde(with = "time::serde::timestamp")]
+    time: OffsetDateTime,
+}
+
+impl Comment {
+    /// Max length of a comment in chars.
+    pub const MAX_LEN: usize = 2000;
+
+    /// Creates a new comment at the current time with a generated id.
+    pub fn new(post: u64, parent: Option<u64>, author: u64, content: String) -> Self {
+        Self {
+            id: crate::id::next(),
+            post,
+            parent,
+            author,
+            content,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn post(&self) -> u64 {
+        self.post
+    }
+
+    /// The comment this comment replies to.
+    #[inline]
+    pub fn parent(&self) -> Option<u64> {
+        self.parent
+    }
+
+    #[inline]
+    pub fn author(&self) -> u64 {
+        self.author
+    }
+
+    /// Sets the author, if the id of the author account changed.
+    #[inline]
+    pub fn set_author(&mut self, author: u64) {
+        self.author = author
+    }
+
+    #[inline]
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    #[inline]
+    pub fn time(&self) -> OffsetDateTime {
+        self.time
+    }
+}
+
+impl dmds::Data for Comment {
+    const DIMS: usize = 2;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            1 => self.post,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                this.post = dims[1];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -0,0 +1,68 @@
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// An entry of an Atom feed.
+#[derive(Debug)]
+pub struct Entry<'a> {
+    pub id: u64,
+    pub title: &'a str,
+    pub summary: &'a str,
+    /// Time the post was accepted.
+    pub updated: OffsetDateTime,
+    /// Absolute links of images.
+    pub images: Vec<String>,
+}
+
+/// Serializes entries into an Atom (RFC 4287) feed.
+///
+/// `url` is the public url of the feed.
+pub fn atom<'a>(title: &str, url: &str, entries: impl IntoIterator<Item = Entry<'a>>) -> String {
+    let entries: Vec<_> = entries.into_iter().collect();
+    let updated = entries
+        .iter()
+        .map(|e| e.updated)
+        .max()
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+    let mut buf = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    buf.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    buf.push_str(&format!("<title>{}</title>", escape(title)));
+    buf.push_str(&format!("<id>{}</id>", escape(url)));
+    buf.push_str(&format!(r#"<link rel="self" href="{}"/>"#, escape(url)));
+    buf.push_str(&format!("<updated>{}</updated>", rfc3339(updated)));
+    for entry in entries {
+        buf.push_str("<entry>");
+        buf.push_str(&format!("<id>urn:sms3:post:{}</id>", entry.id));
+        buf.push_str(&format!("<title>{}</title>", escape(entry.title)));
+        buf.push_str(&format!("<updated>{}</updated>", rfc3339(entry.updated)));
+        buf.push_str(&format!("<summary>{}</summary>", escape(entry.summary)));
+        for image in entry.images {
+            buf.push_str(&format!(
+                r#"<link rel="enclosure" href="{}"/>"#,
+                escape(&image)
+            ));
+        }
+        buf.push_str("</entry>");
+    }
+    buf.push_str("</feed>");
+    buf
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[inline]
+fn rfc3339(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
@@ -0,0 +1,108 @@
+use std::fmt::Write;
+
+use time::{Date, OffsetDateTime};
+
+/// Max octets of a content line before folding.
+const LINE_LEN: usize = 75;
+
+/// An all-day event of a calendar.
+#[derive(Debug)]
+pub struct Event<'a> {
+    /// Globally unique id of the event.
+    pub uid: String,
+    pub summary: &'a str,
+    pub description: &'a str,
+    /// First day of the event.
+    pub start: Date,
+    /// Last day of the event, inclusive.
+    pub end: Date,
+    /// Last modification time of the event.
+    pub stamp: OffsetDateTime,
+}
+
+/// Serializes events into an iCalendar object.
+pub fn calendar<'a>(name: &str, events: impl IntoIterator<Item = Event<'a>>) -> String {
+    let mut buf = String::new();
+    line(&mut buf, "BEGIN:VCALENDAR");
+    line(&mut buf, "VERSION:2.0");
+    line(&mut buf, "PRODID:-//SubIT//sms3-backend//EN");
+    line(&mut buf, "CALSCALE:GREGORIAN");
+    line(&mut buf, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        line(&mut buf, "BEGIN:VEVENT");
+        line(&mut buf, &format!("UID:{}", event.uid));
+        line(&mut buf, &format!("DTSTAMP:{}", timestamp(event.stamp)));
+        line(
+            &mut buf,
+            &format!("DTSTART;VALUE=DATE:{}", date(event.start)),
+        );
+        // DTEND of all-day events is exclusive.
+        let end = event.end.next_day().unwrap_or(event.end);
+        line(&mut buf, &format!("DTEND;VALUE=DATE:{}", date(end)));
+        line(&mut buf, &format!("SUMMARY:{}", escape(event.summary)));
+        if !event.description.is_empty() {
+            line(
+                &mut buf,
+                &format!("DESCRIPTION:{}", escape(event.description)),
+            );
+        }
+        line(&mut buf, "END:VEVENT");
+    }
+    line(&mut buf, "END:VCALENDAR");
+    buf
+}
+
+/// Writes a content line, folded into lines of at most [`LINE_LEN`] octets.
+fn line(buf: &mut String, content: &str) {
+    let mut len = 0;
+    for c in content.chars() {
+        if len + c.len_utf8() > LINE_LEN {
+            buf.push_str("\r\n ");
+            // The leading space counts.
+            len = 1;
+        }
+        buf.push(c);
+        len += c.len_utf8();
+    }
+    buf.push_str("\r\n");
+}
+
+/// Escapes a text value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[inline]
+fn date(date: Date) -> String {
+    format!(
+        "{:04}{:02}{:02}",
+        date.year(),
+        date.month() as u8,
+        date.day()
+    )
+}
+
+fn timestamp(time: OffsetDateTime) -> String {
+    let time = time.to_offset(time::UtcOffset::UTC);
+    let mut buf = date(time.date());
+    let _ = write!(
+        buf,
+        "T{:02}{:02}{:02}Z",
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+    buf
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// A report of a [`super::Post`] flagged by a viewer,
+/// waiting for moderation.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// 1 -> post
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    #[serde(skip)]
+    id: u64,
+    #[serde(skip)]
+    post: u64,
+    reporter: u64,
+    reason: Reason,
+    /// Details given by the reporter.
+    message: String,
+    #[serde(with = "time::serde::timestamp")]
+    time: OffsetDateTime,
+}
+
+impl Report {
+    /// Max length of a message in chars.
+    pub const MAX_MESSAGE_LEN: usize = 500;
+
+    /// Creates a new report at the current time with a generated id.
+    pub fn new(post: u64, reporter: u64, reason: Reason, message: String) -> Self {
+        Self {
+            id: crate::id::next(),
+            post,
+            reporter,
+            reason,
+            message,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn post(&self) -> u64 {
+        self.post
+    }
+
+    #[inline]
+    pub fn reporter(&self) -> u64 {
+        self.reporter
+    }
+
+    #[inline]
+    pub fn reason(&self) -> Reason {
+        self.reason
+    }
+
+    /// Details given by the reporter.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[inline]
+    pub fn time(&self) -> OffsetDateTime {
+        self.time
+    }
+}
+
+/// Reason of a [`Report`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    Spam,
+    Outdated,
+    Inappropriate,
+    Other,
+}
+
+impl dmds::Data for Report {
+    const DIMS: usize = 2;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            1 => self.post,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                this.post = dims[1];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
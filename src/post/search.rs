@@ -0,0 +1,198 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+/// An inverted index of posts for full-text searching,
+/// maintained incrementally on post creation and edition.
+#[derive(Debug, Default)]
+pub struct Index {
+    /// Term => Post ids => Fields containing the term.
+    terms: HashMap<String, HashMap<u64, u8>>,
+    /// Post id => Terms.
+    docs: HashMap<u64, HashSet<String>>,
+}
+
+impl Index {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Indexes the post with given title and description,
+    /// replacing the previous terms of the post.
+    pub fn insert(&mut self, id: u64, title: &str, description: &str) {
+        self.remove(id);
+        let mut terms = HashSet::new();
+        for (text, field) in [(title, Field::Title), (description, Field::Description)] {
+            for term in tokenize(text) {
+                *self
+                    .terms
+                    .entry(term.clone())
+                    .or_default()
+                    .entry(id)
+                    .or_default() |= field.mask();
+                terms.insert(term);
+            }
+        }
+        self.docs.insert(id, terms);
+    }
+
+    /// Removes the post from this index.
+    pub fn remove(&mut self, id: u64) {
+        let Some(terms) = self.docs.remove(&id) else {
+            return;
+        };
+        for term in terms {
+            if let Some(ids) = self.terms.get_mut(&term) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Searches posts containing all terms, or any term if `any`
+    /// is `true`, of the query in the field, case-insensitively.
+    pub fn search(&self, query: &str, field: Field, any: bool) -> HashSet<u64> {
+        let mask = field.mask();
+        let mut result: Option<HashSet<u64>> = None;
+        for term in tokenize(query) {
+            let ids: HashSet<u64> = self.terms.get(&term).map_or_else(HashSet::new, |ids| {
+                ids.iter()
+                    .filter(|(_, fields)| *fields & mask != 0)
+                    .map(|(id, _)| *id)
+                    .collect()
+            });
+            result = Some(match result {
+                Some(r) if any => r.union(&ids).copied().collect(),
+                Some(r) => r.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+            if !any && result.as_ref().map_or(false, HashSet::is_empty) {
+                break;
+            }
+        }
+        result.unwrap_or_default()
+    }
+}
+
+/// Fields of posts to search in.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = search::Field)]
+pub enum Field {
+    /// Both the title and description.
+    #[default]
+    All,
+    Title,
+    Description,
+}
+
+impl Field {
+    #[inline]
+    fn mask(self) -> u8 {
+        match self {
+            Field::All => 0b11,
+            Field::Title => 0b01,
+            Field::Description => 0b10,
+        }
+    }
+}
+
+/// Whether the character is a CJK character, which is
+/// tokenized into unigrams and bigrams instead of words.
+#[inline]
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+    )
+}
+
+/// Splits the text into lowercase terms.
+///
+/// Alphanumeric words are terms, and continuous CJK
+/// characters are split into unigrams and bigrams.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = vec![];
+    let mut word = String::new();
+    let mut prev_cjk: Option<char> = None;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+            terms.push(c.to_string());
+            if let Some(p) = prev_cjk {
+                terms.push([p, c].iter().collect());
+            }
+            prev_cjk = Some(c);
+        } else {
+            prev_cjk = None;
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    if !word.is_empty() {
+        terms.push(word);
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_words() {
+        assert_eq!(
+            tokenize("Hello, World! SMS3 v2.0"),
+            ["hello", "world", "sms3", "v2", "0"]
+        );
+        assert!(tokenize(" ,.! ").is_empty());
+    }
+
+    #[test]
+    fn tokenize_cjk() {
+        assert_eq!(tokenize("公告栏"), ["公", "告", "公告", "栏", "告栏"]);
+        assert_eq!(tokenize("SMS3公告 新"), ["sms3", "公", "告", "公告", "新"]);
+    }
+
+    #[test]
+    fn search_fields() {
+        let mut index = Index::new();
+        index.insert(1, "Sports Day", "Held at the field");
+        index.insert(2, "Art Exhibition", "Sports hall");
+        assert_eq!(
+            index.search("sports", Field::All, false),
+            HashSet::from([1, 2])
+        );
+        assert_eq!(
+            index.search("sports", Field::Title, false),
+            HashSet::from([1])
+        );
+        assert_eq!(
+            index.search("sports art", Field::All, false),
+            HashSet::from([2])
+        );
+        assert_eq!(
+            index.search("day exhibition", Field::Title, true),
+            HashSet::from([1, 2])
+        );
+
+        index.insert(1, "Cancelled", "");
+        assert_eq!(
+            index.search("sports", Field::All, false),
+            HashSet::from([2])
+        );
+        index.remove(2);
+        assert!(index.search("sports", Field::All, false).is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+/// Daily display counters of a [`super::Post`], stored apart from
+/// posts so reporting doesn't write-lock them.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// 1 -> post
+/// 2 -> date (julian day)
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Stats {
+    #[serde(skip)]
+    id: u64,
+    #[serde(skip)]
+    post: u64,
+    date: Date,
+
+    /// Times the post was shown.
+    impressions: u64,
+    /// Times the post was played through.
+    plays: u64,
+}
+
+impl Stats {
+    /// Creates empty counters of a post at the date.
+    pub fn new(post: u64, date: Date) -> Self {
+        Self {
+            id: Self::id_of(post, date),
+            post,
+            date,
+            impressions: 0,
+            plays: 0,
+        }
+    }
+
+    /// Gets the id of counters of a post at the date.
+    #[inline]
+    pub fn id_of(post: u64, date: Date) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        post.hash(&mut hasher);
+        date.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn post(&self) -> u64 {
+        self.post
+    }
+
+    #[inline]
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    #[inline]
+    pub fn impressions(&self) -> u64 {
+        self.impressions
+    }
+
+    #[inline]
+    pub fn plays(&self) -> u64 {
+        self.plays
+    }
+
+    /// Adds reported counts.
+    #[inline]
+    pub fn add(&mut self, impressions: u64, plays: u64) {
+        self.impressions = self.impressions.saturating_add(impressions);
+        self.plays = self.plays.saturating_add(plays);
+    }
+}
+
+impl dmds::Data for Stats {
+    const DIMS: usize = 3;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            1 => self.post,
+            2 => self.date.to_julian_day() as u64,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                this.post = dims[1];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
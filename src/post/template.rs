@@ -0,0 +1,176 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use super::Post;
+
+/// Placeholder of the title given by the publisher in title patterns.
+pub const TITLE_PLACEHOLDER: &str = "{title}";
+
+/// An admin-managed template of posts, referenced by name
+/// when creating posts.
+///
+/// # dmds Integration
+///
+/// The id is derived from the name, see [`Self::id_of`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    #[serde(skip)]
+    id: u64,
+    name: String,
+    /// Pattern of titles, with [`TITLE_PLACEHOLDER`] replaced
+    /// by the given title.
+    title_pattern: String,
+    /// Fields that should not be empty.
+    required: Vec<Field>,
+    /// Default category of posts.
+    category: Option<String>,
+    /// Default on-screen days of posts, including the start date.
+    days: Option<u32>,
+}
+
+impl Template {
+    /// Creates a new template with given name and title pattern.
+    #[inline]
+    pub fn new(name: String, title_pattern: String) -> Self {
+        Self {
+            id: Self::id_of(&name),
+            name,
+            title_pattern,
+            required: vec![],
+            category: None,
+            days: None,
+        }
+    }
+
+    /// Gets the id of a template with given name.
+    #[inline]
+    pub fn id_of(name: &str) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn title_pattern(&self) -> &str {
+        &self.title_pattern
+    }
+
+    #[inline]
+    pub fn set_title_pattern(&mut self, title_pattern: String) {
+        self.title_pattern = title_pattern
+    }
+
+    #[inline]
+    pub fn required(&self) -> &[Field] {
+        &self.required
+    }
+
+    #[inline]
+    pub fn set_required(&mut self, required: Vec<Field>) {
+        self.required = required
+    }
+
+    #[inline]
+    pub fn category(&self) -> Option<&String> {
+        self.category.as_ref()
+    }
+
+    #[inline]
+    pub fn set_category(&mut self, category: Option<String>) {
+        self.category = category
+    }
+
+    #[inline]
+    pub fn days(&self) -> Option<u32> {
+        self.days
+    }
+
+    #[inline]
+    pub fn set_days(&mut self, days: Option<u32>) {
+        self.days = days
+    }
+
+    /// Formats the given title with the title pattern.
+    #[inline]
+    pub fn title(&self, title: &str) -> String {
+        self.title_pattern.replace(TITLE_PLACEHOLDER, title)
+    }
+
+    /// Gets the first required field that is empty in the post.
+    pub fn missing(&self, post: &Post) -> Option<Field> {
+        self.required.iter().copied().find(|field| match field {
+            Field::Description => post.description().is_empty(),
+            Field::Images => post.images().is_empty(),
+            Field::Attachments => post.attachments().is_empty(),
+            Field::Videos => post.videos().is_empty(),
+            Field::Tags => post.tags().is_empty(),
+        })
+    }
+}
+
+impl dmds::Data for Template {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
+
+/// A field of posts that could be required by templates.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = template::Field)]
+pub enum Field {
+    Description,
+    Images,
+    Attachments,
+    Videos,
+    Tags,
+}
+
+impl Field {
+    /// Name of the field in requests.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Description => "description",
+            Field::Images => "images",
+            Field::Attachments => "attachments",
+            Field::Videos => "videos",
+            Field::Tags => "tags",
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::{Hash, Hasher},
 };
 
 use serde::{Deserialize, Serialize};
+use sha2::Digest;
 use time::{Instant, OffsetDateTime};
 
 use crate::Error;
 
+pub mod image;
+pub mod pdf;
+pub mod scan;
+pub mod signed;
+pub mod storage;
+pub mod video;
+
 /// Reference and metadata of a resource file.
 ///
 /// # dmds Dimensions
@@ -22,6 +30,19 @@ pub struct Resource {
     id: u64,
     variant: Variant,
     user: u64,
+    /// Count of references, e.g. posts sharing the same content.
+    refs: u32,
+    /// Content type detected from the data.
+    content_type: String,
+    /// Size of the data as bytes.
+    size: u64,
+    created_at: OffsetDateTime,
+    scan: scan::ScanStatus,
+    /// Width and height of an image.
+    dimensions: Option<(u32, u32)>,
+    /// Other accounts uploaded the same content, which are
+    /// not charged for the storage.
+    uploaders: HashSet<u64>,
 
     #[serde(skip)]
     used: bool,
@@ -33,8 +54,9 @@ impl Resource {
     /// The id will be generated randomly based on the
     /// time and account.
     pub fn new(variant: Variant, account: u64) -> Self {
+        let now = OffsetDateTime::now_utc();
         let mut hasher = siphasher::sip::SipHasher24::new();
-        OffsetDateTime::now_utc().hash(&mut hasher);
+        now.hash(&mut hasher);
         account.hash(&mut hasher);
         rand::random::<i32>().hash(&mut hasher);
 
@@ -42,14 +64,229 @@ impl Resource {
             id: hasher.finish(),
             variant,
             user: account,
+            refs: 0,
+            content_type: String::new(),
+            size: 0,
+            created_at: now,
+            scan: scan::ScanStatus::Clean,
+            dimensions: None,
+            uploaders: HashSet::new(),
             used: false,
         }
     }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// The account uploaded this resource.
+    #[inline]
+    pub fn user(&self) -> u64 {
+        self.user
+    }
+
+    /// Whether the account uploaded the content of this resource,
+    /// either first or as a duplicate.
+    #[inline]
+    pub fn is_uploader(&self, account: u64) -> bool {
+        self.user == account || self.uploaders.contains(&account)
+    }
+
+    /// Other accounts uploaded the same content.
+    #[inline]
+    pub fn uploaders(&self) -> impl Iterator<Item = u64> + '_ {
+        self.uploaders.iter().copied()
+    }
+
+    /// Records an account uploading the same content again.
+    #[inline]
+    pub fn add_uploader(&mut self, account: u64) {
+        if account != self.user {
+            self.uploaders.insert(account);
+        }
+    }
+
+    /// Replaces references to an uploader account whose id changed.
+    pub fn replace_uploader(&mut self, from: u64, to: u64) {
+        if self.user == from {
+            self.user = to;
+        }
+        if self.uploaders.remove(&from) {
+            self.uploaders.insert(to);
+        }
+    }
+
+    /// Removes an uploader account, promoting another one if it
+    /// uploaded first.
+    ///
+    /// Returns whether any other uploader is left.
+    pub fn remove_uploader(&mut self, account: u64) -> bool {
+        self.uploaders.remove(&account);
+        if self.user == account {
+            let Some(next) = self.uploaders.iter().next().copied() else {
+                return false;
+            };
+            self.uploaders.remove(&next);
+            self.user = next;
+        }
+        true
+    }
+
+    /// Whether this resource is referenced.
+    #[inline]
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Adds a reference to this resource.
+    ///
+    /// See [`Self::check_scan`] before acquiring.
+    #[inline]
+    pub fn acquire(&mut self) {
+        self.refs = self.refs.saturating_add(1);
+        self.used = true;
+    }
+
+    /// Removes a reference from this resource.
+    #[inline]
+    pub fn release(&mut self) {
+        self.refs = self.refs.saturating_sub(1);
+        self.used = self.refs > 0;
+    }
+
+    /// Key of the file of this resource in the storage.
+    #[inline]
+    pub fn key(&self) -> String {
+        key_of(self.id)
+    }
+
+    /// Key of the file of this resource in the storage
+    /// after being quarantined.
+    #[inline]
+    pub fn quarantine_key(&self) -> String {
+        format!("{}.quarantined", self.id)
+    }
+
+    /// Keys of the file and the derived files of this resource,
+    /// e.g. thumbnails, which may not exist.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = vec![self.key(), self.quarantine_key()];
+        if self.variant == Variant::Image {
+            keys.extend(image::Size::ALL.iter().map(|s| s.key(self.id)));
+            keys.extend(
+                image::AVATAR_SIDES
+                    .iter()
+                    .map(|s| image::avatar_key(self.id, *s)),
+            );
+            keys.push(image::webp_key(self.id));
+            keys.push(image::avif_key(self.id));
+        }
+        keys
+    }
+
+    /// Size of the data as bytes.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Creates a new resource of uploaded data, with
+    /// the content-addressed id.
+    ///
+    /// See [`UploadSessions::accept`].
+    pub fn with_data(variant: Variant, account: u64, data: &[u8]) -> Self {
+        let mut this = Self::new(variant, account);
+        this.id = hash(data);
+        this.size = data.len() as u64;
+        this
+    }
+
+    /// Records the processed data stored in place of the original
+    /// data, e.g. compressed or stripped, and returns the saved bytes.
+    ///
+    /// The id is still the hash of the original data, so uploads
+    /// of the original content are deduplicated to this resource.
+    pub fn replaced(&mut self, data: &[u8]) -> u64 {
+        if let Some((_, content_type)) = sniff(data) {
+            self.content_type = content_type.to_owned();
+        }
+        let saved = self.size.saturating_sub(data.len() as u64);
+        self.size = data.len() as u64;
+        saved
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+
+    /// Count of references, e.g. posts sharing the same content.
+    #[inline]
+    pub fn refs(&self) -> u32 {
+        self.refs
+    }
+
+    /// Width and height of an image, `None` if not recorded.
+    #[inline]
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.dimensions
+    }
+
+    #[inline]
+    pub fn set_dimensions(&mut self, dimensions: (u32, u32)) {
+        self.dimensions = Some(dimensions);
+    }
+
+    /// Status of malware scanning.
+    #[inline]
+    pub fn scan(&self) -> &scan::ScanStatus {
+        &self.scan
+    }
+
+    #[inline]
+    pub fn set_scan(&mut self, status: scan::ScanStatus) {
+        self.scan = status;
+    }
+
+    /// Checks if this resource is scanned as clean,
+    /// so that it could be used.
+    pub fn check_scan(&self) -> Result<(), Error> {
+        match self.scan {
+            scan::ScanStatus::Pending => Err(Error::ResourceScanPending(self.id)),
+            scan::ScanStatus::Clean => Ok(()),
+            scan::ScanStatus::Infected { .. } => Err(Error::ResourceInfected(self.id)),
+        }
+    }
+
+    /// Content type detected from the data, empty
+    /// before the data is sniffed.
+    #[inline]
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Detects the content type of the data by magic bytes,
+    /// regardless of the extension or headers from the client,
+    /// and records it if it matches the variant.
+    pub fn sniff(&mut self, data: &[u8]) -> Result<(), Error> {
+        let (variant, content_type) = sniff(data).ok_or(Error::UnsupportedContentType)?;
+        if variant != self.variant {
+            return Err(Error::ResourceVariantMismatch);
+        }
+        self.content_type = content_type.to_owned();
+        Ok(())
+    }
 }
 
 impl dmds::Data for Resource {
     const DIMS: usize = 2;
-    const VERSION: u32 = 1;
+    const VERSION: u32 = 2;
 
     #[inline]
     fn dim(&self, dim: usize) -> u64 {
@@ -62,14 +299,42 @@ impl dmds::Data for Resource {
 
     fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
         match version {
-            0 => {
+            1 => {
+                /// Layout with only the variant and the uploader.
+                #[derive(Deserialize)]
+                struct ResourceV1 {
+                    variant: Variant,
+                    user: u64,
+                }
+
+                let v1: ResourceV1 = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                let used = dims[1] != 0;
+                Ok(Self {
+                    id: dims[0],
+                    variant: v1.variant,
+                    user: v1.user,
+                    // References were not counted, but a used
+                    // resource is referenced at least once.
+                    refs: used as u32,
+                    content_type: String::new(),
+                    size: 0,
+                    // Unknown, so the resource is treated as fresh.
+                    created_at: OffsetDateTime::now_utc(),
+                    scan: scan::ScanStatus::Clean,
+                    dimensions: None,
+                    uploaders: HashSet::new(),
+                    used,
+                })
+            }
+            2 => {
                 let mut this: Self = bincode::deserialize_from(buf.reader())
                     .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
                 this.id = dims[0];
                 this.used = dims[1] != 0;
                 Ok(this)
             }
-            _ => unreachable!(),
+            _ => unreachable!("unsupported data version {version}"),
         }
     }
 
@@ -80,11 +345,37 @@ impl dmds::Data for Resource {
     }
 }
 
+/// Detects the variant and the content type of the data by magic bytes.
+pub fn sniff(data: &[u8]) -> Option<(Variant, &'static str)> {
+    if let Ok(format) = ::image::guess_format(data) {
+        Some((Variant::Image, format.to_mime_type()))
+    } else if pdf::validate(data).is_ok() {
+        Some((Variant::Pdf, "application/pdf"))
+    } else {
+        video::Format::guess(data).map(|f| (Variant::Video, f.mime_type()))
+    }
+}
+
+/// Content-addressed id of the data.
+#[inline]
+fn hash(data: &[u8]) -> u64 {
+    highway::HighwayHash::hash64(highway::PortableHash::new(highway::Key::default()), data)
+}
+
+/// Key of the file of a resource in the storage.
+#[inline]
+pub fn key_of(id: u64) -> String {
+    id.to_string()
+}
+
 /// A resource uploading session.
 #[derive(Debug)]
 struct UploadSession {
     resource: Resource,
+    /// Time of the last activity.
     instant: Instant,
+    /// Length of the received chunks.
+    received: usize,
 }
 
 impl UploadSession {
@@ -94,17 +385,9 @@ impl UploadSession {
         Self {
             resource,
             instant: Instant::now(),
+            received: 0,
         }
     }
-
-    /// Expire duration of a session.
-    const EXPIRE_DUR: time::Duration = time::Duration::seconds(15);
-
-    /// Whether this session is expired.
-    #[inline]
-    fn is_expired(&self) -> bool {
-        self.instant.elapsed() > Self::EXPIRE_DUR
-    }
 }
 
 impl From<Resource> for UploadSession {
@@ -122,54 +405,137 @@ impl From<UploadSession> for Resource {
 }
 
 /// Storage of resource upload sessions.
-#[derive(Debug, Default)]
+///
+/// Sessions without activity within the TTL are treated as
+/// not found, and dropped by [`Self::sweep`].
+#[derive(Debug)]
 pub struct UploadSessions {
     /// Id => Session.
     inner: HashMap<u64, UploadSession>,
+    ttl: time::Duration,
 }
 
 impl UploadSessions {
     #[inline]
-    pub fn new() -> Self {
-        Default::default()
+    pub fn new(ttl: time::Duration) -> Self {
+        Self {
+            inner: HashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Gets an unexpired session of the user.
+    fn get_mut(&mut self, id: u64, user: u64) -> Result<&mut UploadSession, Error> {
+        let ttl = self.ttl;
+        let session = self
+            .inner
+            .get_mut(&id)
+            .filter(|s| s.instant.elapsed() <= ttl)
+            .ok_or(Error::ResourceUploadSessionNotFound(id))?;
+        if session.resource.user != user {
+            return Err(Error::PermissionDenied);
+        }
+        Ok(session)
     }
 
+    /// Drops expired sessions, and returns their ids,
+    /// so that their partial data could be removed.
+    pub fn sweep(&mut self) -> Vec<u64> {
+        let ttl = self.ttl;
+        let mut expired = vec![];
+        self.inner.retain(|id, s| {
+            let alive = s.instant.elapsed() <= ttl;
+            if !alive {
+                expired.push(*id);
+            }
+            alive
+        });
+        expired
+    }
+
+    /// Whether a session with given id exists, including expired
+    /// sessions which are not swept yet.
     #[inline]
-    fn cleanup(&mut self) {
-        self.inner.retain(|_, v| !v.is_expired());
+    pub fn contains(&self, id: u64) -> bool {
+        self.inner.contains_key(&id)
     }
 
     /// Inserts a new session.
+    #[inline]
     pub fn insert(&mut self, res: Resource) {
-        self.cleanup();
         self.inner.insert(res.id, res.into());
     }
 
+    /// Gets the variant and the length of received chunks
+    /// of a session, where the next chunk should start from.
+    pub fn progress(&mut self, id: u64, user: u64) -> Result<(Variant, usize), Error> {
+        let session = self.get_mut(id, user)?;
+        Ok((session.resource.variant, session.received))
+    }
+
+    /// Records a received chunk of a session with its offset,
+    /// and returns the offset of the next chunk.
+    ///
+    /// The chunk should be persisted before, and the offset
+    /// should be validated through [`Self::progress`].
+    pub fn advance(
+        &mut self,
+        id: u64,
+        offset: usize,
+        len: usize,
+        user: u64,
+    ) -> Result<usize, Error> {
+        let session = self.get_mut(id, user)?;
+        if session.received != offset {
+            return Err(Error::ResourceChunkOffsetMismatch(session.received));
+        }
+        session.received += len;
+        session.instant = Instant::now();
+        Ok(session.received)
+    }
+
+    /// Accepts the assembled chunks of a resource with given id,
+    /// verifies them with the declared SHA-256 hash in hex
+    /// and returns the resource.
+    ///
+    /// See [`Self::accept`].
+    pub fn finalize(
+        &mut self,
+        id: u64,
+        data: &[u8],
+        sha256: &str,
+        user: u64,
+    ) -> Result<Resource, Error> {
+        let (_, received) = self.progress(id, user)?;
+        if received != data.len() {
+            return Err(Error::ResourceChunkOffsetMismatch(received));
+        }
+        let hash: String = sha2::Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        if !hash.eq_ignore_ascii_case(sha256.trim()) {
+            return Err(Error::ResourceHashMismatch);
+        }
+        self.accept(id, data, user)
+    }
+
     /// Accepts the body of a resource with given id,
     /// and returns the resource.
     ///
     /// *Id of the resource* will be changed, so you have to
     /// tell the new id to *the frontend*.
     pub fn accept(&mut self, id: u64, data: &[u8], user: u64) -> Result<Resource, Error> {
-        self.cleanup();
-        let res = &self
-            .inner
-            .get(&id)
-            .ok_or(Error::ResourceUploadSessionNotFound(id))?
-            .resource;
-        if res.user != user {
-            return Err(Error::PermissionDenied);
-        }
-
+        self.get_mut(id, user)?;
         let mut res = self.inner.remove(&id).unwrap().resource;
-        res.id =
-            highway::HighwayHash::hash64(highway::PortableHash::new(highway::Key::default()), data);
+        res.size = data.len() as u64;
+        res.id = hash(data);
         Ok(res)
     }
 }
 
 /// Type of a resource.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 pub enum Variant {
     Image,
     Pdf,
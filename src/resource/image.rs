@@ -0,0 +1,245 @@
+use std::io::Cursor;
+
+use ::image::{imageops::FilterType, ImageOutputFormat};
+use serde::Deserialize;
+
+use crate::{
+    config::{Compression, ImageLimits},
+    Error,
+};
+
+pub mod metadata;
+
+/// Size of a pre-generated image thumbnail.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Size {
+    Small,
+    Medium,
+}
+
+impl Size {
+    /// All sizes of thumbnails, from the smallest.
+    pub const ALL: [Self; 2] = [Self::Small, Self::Medium];
+
+    /// Max side length of thumbnails in this size.
+    #[inline]
+    pub fn max_side(self) -> u32 {
+        match self {
+            Size::Small => 256,
+            Size::Medium => 1024,
+        }
+    }
+
+    #[inline]
+    fn name(self) -> &'static str {
+        match self {
+            Size::Small => "small",
+            Size::Medium => "medium",
+        }
+    }
+
+    /// Key of the thumbnail in this size of an image resource
+    /// in the storage.
+    #[inline]
+    pub fn key(self, id: u64) -> String {
+        format!("{id}-{}", self.name())
+    }
+}
+
+/// Decodes an image and generates thumbnails fitting in each size,
+/// encoded as JPEG, or PNG if the image has alpha channel.
+///
+/// Sizes not smaller than the image are skipped.
+pub fn thumbnails(data: &[u8]) -> Result<Vec<(Size, Vec<u8>)>, Error> {
+    let img = ::image::load_from_memory(data)?;
+    let format = if img.color().has_alpha() {
+        ImageOutputFormat::Png
+    } else {
+        ImageOutputFormat::Jpeg(85)
+    };
+    let mut res = vec![];
+    for size in Size::ALL {
+        let side = size.max_side();
+        if img.width() <= side && img.height() <= side {
+            continue;
+        }
+        let mut buf = Cursor::new(Vec::new());
+        img.resize(side, side, FilterType::Triangle)
+            .write_to(&mut buf, format.clone())?;
+        res.push((size, buf.into_inner()));
+    }
+    Ok(res)
+}
+
+/// Decodes an image and crops it into a square thumbnail
+/// with given side length, encoded as PNG.
+pub fn thumbnail(data: &[u8], size: u32) -> Result<Vec<u8>, Error> {
+    let img = ::image::load_from_memory(data)?.resize_to_fill(size, size, FilterType::Triangle);
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, ImageOutputFormat::Png)?;
+    Ok(buf.into_inner())
+}
+
+/// Validates that the data is in a supported and allowed image format,
+/// and its dimensions are in the limits.
+///
+/// Only the header is decoded.
+pub fn validate(data: &[u8], limits: &ImageLimits) -> Result<(), Error> {
+    let format = ::image::guess_format(data)?;
+    if !limits.formats.is_empty()
+        && !format
+            .extensions_str()
+            .iter()
+            .any(|ext| limits.formats.iter().any(|f| f.eq_ignore_ascii_case(ext)))
+    {
+        return Err(Error::ImageFormatNotAllowed(
+            format.extensions_str().first().copied().unwrap_or_default(),
+        ));
+    }
+    let (width, height) =
+        ::image::io::Reader::with_format(Cursor::new(data), format).into_dimensions()?;
+    if (limits.max_width > 0 && width > limits.max_width)
+        || (limits.max_height > 0 && height > limits.max_height)
+    {
+        return Err(Error::ImageTooLarge(width, height));
+    }
+    Ok(())
+}
+
+/// Reads the width and height of an image from its header.
+#[inline]
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), Error> {
+    ::image::io::Reader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_dimensions()
+        .map_err(From::from)
+}
+
+/// Checks the dimensions of an image against the resolution presets,
+/// and returns the mismatches as warnings.
+///
+/// Images not matching the aspect ratio of any preset would be
+/// stretched on screens, and images smaller than all the matched
+/// presets would be blurry. Mismatches are errors if
+/// [`ImageLimits::reject_mismatches`] is set.
+pub fn check_presets(data: &[u8], limits: &ImageLimits) -> Result<Vec<String>, Error> {
+    if limits.presets.is_empty() {
+        return Ok(vec![]);
+    }
+    let (width, height) = dimensions(data)?;
+    let ratio = width as f32 / height as f32;
+    let matched: Vec<_> = limits
+        .presets
+        .iter()
+        .filter(|p| {
+            let expected = p.width as f32 / p.height as f32;
+            (ratio - expected).abs() <= expected * limits.aspect_tolerance
+        })
+        .collect();
+    let warning = if matched.is_empty() {
+        Some(format!(
+            "{width}x{height} does not match the aspect ratio of any screen"
+        ))
+    } else if matched.iter().all(|p| width < p.width || height < p.height) {
+        Some(format!(
+            "{width}x{height} is smaller than the resolution of the screens"
+        ))
+    } else {
+        None
+    };
+    match warning {
+        Some(warning) if limits.reject_mismatches => Err(Error::ImagePresetMismatch(warning)),
+        warning => Ok(warning.into_iter().collect()),
+    }
+}
+
+/// Compresses a PNG image with the configuration, and returns
+/// the smallest result if it is smaller than the original.
+///
+/// Returns `None` if the image is not a PNG.
+pub fn compress(data: &[u8], config: &Compression) -> Result<Option<Vec<u8>>, Error> {
+    if ::image::guess_format(data)? != ::image::ImageFormat::Png {
+        return Ok(None);
+    }
+    let mut best: Option<Vec<u8>> = None;
+    let mut consider = |candidate: Vec<u8>| {
+        if candidate.len() < best.as_ref().map_or(data.len(), Vec::len) {
+            best = Some(candidate);
+        }
+    };
+    if config.lossless {
+        let options = oxipng::Options::from_preset(config.level.min(6));
+        if let Ok(optimized) = oxipng::optimize_from_memory(data, &options) {
+            consider(optimized);
+        }
+    }
+    if let Some(quality) = config.lossy_quality {
+        let img = ::image::load_from_memory(data)?;
+        if !img.color().has_alpha() {
+            let mut buf = Cursor::new(Vec::new());
+            img.write_to(&mut buf, ImageOutputFormat::Jpeg(quality.clamp(1, 100)))?;
+            consider(buf.into_inner());
+        }
+    }
+    Ok(best)
+}
+
+/// Side lengths of generated avatars, from the smallest.
+pub const AVATAR_SIDES: [u32; 5] = [32, 64, 128, 256, 512];
+
+/// Key of the square avatar with given side length of an image
+/// resource in the storage, see [`AVATAR_SIDES`].
+#[inline]
+pub fn avatar_key(id: u64, side: u32) -> String {
+    format!("{id}-avatar-{side}")
+}
+
+/// Key of the WebP copy of an image resource in the storage.
+#[inline]
+pub fn webp_key(id: u64) -> String {
+    format!("{id}.webp")
+}
+
+/// Key of the AVIF copy of an image resource in the storage.
+#[inline]
+pub fn avif_key(id: u64) -> String {
+    format!("{id}.avif")
+}
+
+/// Decodes an image and encodes it as lossy AVIF.
+#[cfg(feature = "avif")]
+pub fn avif(data: &[u8]) -> Result<Vec<u8>, Error> {
+    use ::image::{codecs::avif::AvifEncoder, ImageEncoder};
+
+    const SPEED: u8 = 6;
+    const QUALITY: u8 = 70;
+
+    let img = ::image::load_from_memory(data)?.to_rgba8();
+    let mut buf = Vec::new();
+    AvifEncoder::new_with_speed_quality(&mut buf, SPEED, QUALITY).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ::image::ColorType::Rgba8,
+    )?;
+    Ok(buf)
+}
+
+/// Decodes an image and encodes it as lossy WebP.
+#[cfg(feature = "webp")]
+pub fn webp(data: &[u8]) -> Result<Vec<u8>, Error> {
+    use ::image::codecs::webp::{WebPEncoder, WebPQuality};
+
+    const QUALITY: u8 = 80;
+
+    let img = ::image::load_from_memory(data)?.to_rgba8();
+    let mut buf = Vec::new();
+    WebPEncoder::new_with_quality(&mut buf, WebPQuality::lossy(QUALITY)).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ::image::ColorType::Rgba8,
+    )?;
+    Ok(buf)
+}
@@ -0,0 +1,214 @@
+//! Stripping of EXIF, GPS and XMP metadata from uploaded images.
+
+use std::io::Cursor;
+
+use ::image::{DynamicImage, ImageFormat, ImageOutputFormat};
+
+use crate::Error;
+
+/// Strips metadata from a JPEG, PNG or WebP image, and returns
+/// `None` if there is nothing to strip.
+///
+/// If `bake_orientation` is set, JPEG and PNG images with a
+/// non-default EXIF orientation are rotated into pixels and
+/// re-encoded, so they are displayed in the same orientation.
+///
+/// JPEG and PNG images in malformed structures are re-encoded,
+/// which drops all the metadata.
+pub fn strip(data: &[u8], bake_orientation: bool) -> Result<Option<Vec<u8>>, Error> {
+    let format = ::image::guess_format(data)?;
+    let parsed = match format {
+        ImageFormat::Jpeg => jpeg(data),
+        ImageFormat::Png => png(data),
+        ImageFormat::WebP => webp(data),
+        _ => return Ok(None),
+    };
+    match parsed {
+        Some((_, Some(orientation @ 2..=8))) if bake_orientation && format != ImageFormat::WebP => {
+            reencode(data, format, orientation).map(Some)
+        }
+        Some((stripped, _)) => Ok(stripped),
+        None if format != ImageFormat::WebP => reencode(data, format, 1).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Decodes a JPEG or PNG image and encodes it in the same
+/// format with the orientation applied.
+fn reencode(data: &[u8], format: ImageFormat, orientation: u16) -> Result<Vec<u8>, Error> {
+    let img = ::image::load_from_memory_with_format(data, format)?;
+    let output = if format == ImageFormat::Jpeg {
+        ImageOutputFormat::Jpeg(90)
+    } else {
+        ImageOutputFormat::Png
+    };
+    let mut buf = Cursor::new(Vec::new());
+    orient(img, orientation).write_to(&mut buf, output)?;
+    Ok(buf.into_inner())
+}
+
+/// Applies an EXIF orientation to the image.
+fn orient(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Reads the orientation from EXIF data in TIFF structure,
+/// optionally prefixed by the `Exif\0\0` header.
+fn exif_orientation(data: &[u8]) -> Option<u16> {
+    const ORIENTATION: u16 = 0x0112;
+
+    let tiff = data.strip_prefix(b"Exif\0\0").unwrap_or(data);
+    let le = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |i: usize| {
+        let b: [u8; 2] = tiff.get(i..i + 2)?.try_into().ok()?;
+        Some(if le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32_at = |i: usize| {
+        let b: [u8; 4] = tiff.get(i..i + 4)?.try_into().ok()?;
+        Some(if le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    (0..u16_at(ifd)? as usize)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+/// Strips APP1 (EXIF and XMP), APP13 (IPTC) and comment segments
+/// of a JPEG image, and reads the orientation.
+fn jpeg(data: &[u8]) -> Option<(Option<Vec<u8>>, Option<u16>)> {
+    const SOS: u8 = 0xda;
+    const APP1: u8 = 0xe1;
+    const APP13: u8 = 0xed;
+    const COM: u8 = 0xfe;
+
+    let mut res = data.get(0..2)?.to_vec();
+    let mut orientation = None;
+    let mut stripped = false;
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        match marker {
+            // Fill bytes.
+            0xff => {
+                i += 1;
+                continue;
+            }
+            // Standalone markers without lengths.
+            0x01 | 0xd0..=0xd7 => {
+                res.extend_from_slice(&data[i..i + 2]);
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let len = u16::from_be_bytes(data.get(i + 2..i + 4)?.try_into().ok()?) as usize;
+        let segment = data.get(i..i + 2 + len)?;
+        if marker == SOS {
+            // The entropy-coded data and the rest are kept.
+            res.extend_from_slice(&data[i..]);
+            break;
+        }
+        if matches!(marker, APP1 | APP13 | COM) {
+            if marker == APP1 && orientation.is_none() {
+                orientation = exif_orientation(&segment[4..]);
+            }
+            stripped = true;
+        } else {
+            res.extend_from_slice(segment);
+        }
+        i += 2 + len;
+    }
+    Some((stripped.then_some(res), orientation))
+}
+
+/// Strips EXIF, text and time chunks of a PNG image,
+/// and reads the orientation.
+fn png(data: &[u8]) -> Option<(Option<Vec<u8>>, Option<u16>)> {
+    const STRIPPED: [&[u8]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+    let mut res = data.get(0..8)?.to_vec();
+    let mut orientation = None;
+    let mut stripped = false;
+    let mut i = 8;
+    while i < data.len() {
+        let len = u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?) as usize;
+        let ty = data.get(i + 4..i + 8)?;
+        // Length, type, data and CRC.
+        let chunk = data.get(i..i + 12 + len)?;
+        if STRIPPED.contains(&ty) {
+            if ty == b"eXIf" {
+                orientation = exif_orientation(&chunk[8..8 + len]);
+            }
+            stripped = true;
+        } else {
+            res.extend_from_slice(chunk);
+        }
+        i += 12 + len;
+    }
+    Some((stripped.then_some(res), orientation))
+}
+
+/// Strips EXIF and XMP chunks of a WebP image, and reads
+/// the orientation.
+fn webp(data: &[u8]) -> Option<(Option<Vec<u8>>, Option<u16>)> {
+    /// Flags of EXIF and XMP chunks in the VP8X chunk.
+    const FLAGS: u8 = 0x08 | 0x04;
+
+    let mut res = data.get(0..12)?.to_vec();
+    let mut orientation = None;
+    let mut stripped = false;
+    let mut i = 12;
+    while i < data.len() {
+        let ty = data.get(i..i + 4)?;
+        let len = u32::from_le_bytes(data.get(i + 4..i + 8)?.try_into().ok()?) as usize;
+        // Chunks are padded to even sizes.
+        let end = (i + 8 + len + (len & 1)).min(data.len());
+        let chunk = data.get(i..end)?;
+        match ty {
+            b"EXIF" | b"XMP " => {
+                if ty == b"EXIF" {
+                    orientation = exif_orientation(chunk.get(8..8 + len)?);
+                }
+                stripped = true;
+            }
+            b"VP8X" => {
+                let start = res.len();
+                res.extend_from_slice(chunk);
+                *res.get_mut(start + 8)? &= !FLAGS;
+            }
+            _ => res.extend_from_slice(chunk),
+        }
+        i = end;
+    }
+    if !stripped {
+        return Some((None, orientation));
+    }
+    let size = (res.len() - 8) as u32;
+    res[4..8].copy_from_slice(&size.to_le_bytes());
+    Some((Some(res), orientation))
+}
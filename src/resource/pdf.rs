@@ -0,0 +1,14 @@
+use crate::Error;
+
+/// Magic bytes at the start of PDF files.
+const MAGIC: &[u8] = b"%PDF-";
+
+/// Validates that the data is a PDF file.
+#[inline]
+pub fn validate(data: &[u8]) -> Result<(), Error> {
+    if data.starts_with(MAGIC) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPdf)
+    }
+}
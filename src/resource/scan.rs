@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{config, Error};
+
+/// Status of malware scanning of a resource.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ScanStatus {
+    /// Waiting for the result, the resource could not be used.
+    Pending,
+    /// Clean, or uploaded without a scanner.
+    Clean,
+    /// Infected and quarantined.
+    Infected { signature: String },
+}
+
+/// Hook scanning uploaded resources for malware.
+#[async_trait::async_trait]
+pub trait Scanner: std::fmt::Debug + Send + Sync {
+    /// Scans the data, returns [`ScanStatus::Clean`]
+    /// or [`ScanStatus::Infected`].
+    async fn scan(&self, data: &[u8]) -> Result<ScanStatus, Error>;
+}
+
+/// Creates the scanner selected by the configuration,
+/// `None` if uploads are not scanned.
+#[inline]
+pub fn from_config(config: &config::Resource) -> Option<Arc<dyn Scanner>> {
+    config
+        .clamav
+        .as_ref()
+        .map(|addr| Arc::new(ClamAv::new(addr.clone())) as Arc<dyn Scanner>)
+}
+
+/// A ClamAV daemon scanning through the `INSTREAM` command.
+#[derive(Debug)]
+pub struct ClamAv {
+    /// `host:port` of the TCP socket, or path of the unix socket.
+    addr: String,
+}
+
+impl ClamAv {
+    #[inline]
+    pub fn new(addr: String) -> Self {
+        Self { addr }
+    }
+}
+
+#[async_trait::async_trait]
+impl Scanner for ClamAv {
+    async fn scan(&self, data: &[u8]) -> Result<ScanStatus, Error> {
+        #[cfg(unix)]
+        if self.addr.starts_with('/') {
+            return instream(tokio::net::UnixStream::connect(&self.addr).await?, data).await;
+        }
+        instream(tokio::net::TcpStream::connect(&self.addr).await?, data).await
+    }
+}
+
+/// Streams the data to a ClamAV daemon and parses the reply.
+async fn instream<S>(mut stream: S, data: &[u8]) -> Result<ScanStatus, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        stream
+            .write_all(&(chunk.len() as u32).to_be_bytes())
+            .await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    stream.flush().await?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    let reply = String::from_utf8_lossy(&reply);
+    // Replies are in `stream: OK` or `stream: {signature} FOUND`.
+    let reply = reply.trim_end_matches('\0').trim();
+    match reply.strip_prefix("stream: ") {
+        Some("OK") => Ok(ScanStatus::Clean),
+        Some(found) if found.ends_with(" FOUND") => Ok(ScanStatus::Infected {
+            signature: found.trim_end_matches(" FOUND").to_owned(),
+        }),
+        _ => Err(Error::Scan(reply.to_owned())),
+    }
+}
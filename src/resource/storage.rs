@@ -0,0 +1,185 @@
+use std::{path::PathBuf, pin::Pin, sync::Arc};
+
+use tokio::io::{AsyncRead, AsyncSeek};
+
+use crate::{config, Error};
+
+/// A seekable reader of a file.
+pub trait Reader: AsyncRead + AsyncSeek + Send {}
+
+impl<T: AsyncRead + AsyncSeek + Send> Reader for T {}
+
+/// A file opened for streaming.
+pub struct Object {
+    /// Length of the file as bytes.
+    pub len: u64,
+    pub reader: Pin<Box<dyn Reader>>,
+}
+
+impl std::fmt::Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Object").field("len", &self.len).finish()
+    }
+}
+
+/// Persistence of resource files by keys.
+#[async_trait::async_trait]
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    /// Stores the file, replacing the previous one.
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Error>;
+
+    /// Gets the file, `None` if not found.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Deletes the file if exists.
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+
+    /// Opens the file for streaming, `None` if not found.
+    ///
+    /// The whole file is read into memory by default.
+    async fn open(&self, key: &str) -> Result<Option<Object>, Error> {
+        Ok(self.get(key).await?.map(|data| Object {
+            len: data.len() as u64,
+            reader: Box::pin(std::io::Cursor::new(data)),
+        }))
+    }
+}
+
+/// Creates the storage selected by the configuration.
+pub fn from_config(config: &config::Resource) -> Result<Arc<dyn Storage>, Error> {
+    Ok(match &config.storage {
+        config::Storage::Local => Arc::new(Local::new(config.path.clone())),
+        config::Storage::S3(s3) => Arc::new(S3::new(s3)?),
+    })
+}
+
+/// Files in a local directory.
+#[derive(Debug)]
+pub struct Local {
+    root: PathBuf,
+}
+
+impl Local {
+    #[inline]
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for Local {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        tokio::fs::write(self.root.join(key), data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match tokio::fs::read(self.root.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn open(&self, key: &str) -> Result<Option<Object>, Error> {
+        let file = match tokio::fs::File::open(self.root.join(key)).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(Object {
+            len: file.metadata().await?.len(),
+            reader: Box::pin(file),
+        }))
+    }
+}
+
+/// Objects in an S3-compatible bucket, e.g. MinIO.
+#[derive(Debug)]
+pub struct S3 {
+    bucket: s3::Bucket,
+}
+
+impl S3 {
+    pub fn new(config: &config::S3Storage) -> Result<Self, Error> {
+        let region = match config.endpoint {
+            Some(ref endpoint) => s3::Region::Custom {
+                region: config.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => config
+                .region
+                .parse()
+                .map_err(|err: s3::error::S3Error| Error::Storage(err.to_string()))?,
+        };
+        let credentials = s3::creds::Credentials::new(
+            Some(&config.access_key),
+            Some(&config.secret_key),
+            None,
+            None,
+            None,
+        )
+        .map_err(|err| Error::Storage(err.to_string()))?;
+        let mut bucket = s3::Bucket::new(&config.bucket, region, credentials)
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        if config.path_style {
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self { bucket })
+    }
+}
+
+/// Converts a status code of an S3 response into a result.
+#[inline]
+fn check(code: u16) -> Result<(), Error> {
+    if (200..300).contains(&code) {
+        Ok(())
+    } else {
+        Err(Error::Storage(format!("s3 responded with status {code}")))
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for S3 {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Error> {
+        let res = self
+            .bucket
+            .put_object(key, data)
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        check(res.status_code())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let res = self
+            .bucket
+            .get_object(key)
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        if res.status_code() == 404 {
+            return Ok(None);
+        }
+        check(res.status_code())?;
+        Ok(Some(res.bytes().to_vec()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let res = self
+            .bucket
+            .delete_object(key)
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        if res.status_code() == 404 {
+            return Ok(());
+        }
+        check(res.status_code())
+    }
+}
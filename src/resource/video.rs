@@ -0,0 +1,146 @@
+use time::Duration;
+
+use crate::Error;
+
+/// Container format of a short video clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Mp4,
+    WebM,
+}
+
+impl Format {
+    /// Guesses the format by magic bytes.
+    pub fn guess(data: &[u8]) -> Option<Self> {
+        if data.get(4..8) == Some(b"ftyp") {
+            Some(Self::Mp4)
+        } else if data.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+            Some(Self::WebM)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Mp4 => "video/mp4",
+            Format::WebM => "video/webm",
+        }
+    }
+}
+
+/// Validates that the data is an MP4 or WebM clip
+/// not longer than `max_duration`.
+pub fn validate(data: &[u8], max_duration: Duration) -> Result<(), Error> {
+    let duration = match Format::guess(data).ok_or(Error::InvalidVideo)? {
+        Format::Mp4 => mp4_duration(data),
+        Format::WebM => webm_duration(data),
+    }
+    .ok_or(Error::InvalidVideo)?;
+    if duration > max_duration {
+        return Err(Error::VideoTooLong);
+    }
+    Ok(())
+}
+
+/// Reads a big-endian unsigned integer.
+fn be(data: &[u8]) -> u64 {
+    data.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+/// Iterates over ISO BMFF boxes, yielding types and bodies.
+fn mp4_boxes<'a>(mut data: &'a [u8]) -> impl Iterator<Item = (&'a [u8], &'a [u8])> {
+    std::iter::from_fn(move || {
+        let d: &'a [u8] = data;
+        let (mut size, ty) = (be(d.get(0..4)?) as usize, d.get(4..8)?);
+        let mut header = 8;
+        if size == 1 {
+            size = be(d.get(8..16)?) as usize;
+            header = 16;
+        } else if size == 0 {
+            size = d.len();
+        }
+        let body = d.get(header..size)?;
+        data = &d[size..];
+        Some((ty, body))
+    })
+}
+
+/// Reads the duration from the `mvhd` box of an MP4 file.
+fn mp4_duration(data: &[u8]) -> Option<Duration> {
+    let (_, moov) = mp4_boxes(data).find(|(ty, _)| *ty == b"moov")?;
+    let (_, mvhd) = mp4_boxes(moov).find(|(ty, _)| *ty == b"mvhd")?;
+    let (timescale, duration) = match mvhd.first()? {
+        0 => (be(mvhd.get(12..16)?), be(mvhd.get(16..20)?)),
+        1 => (be(mvhd.get(20..24)?), be(mvhd.get(24..32)?)),
+        _ => return None,
+    };
+    if timescale == 0 {
+        return None;
+    }
+    seconds(duration as f64 / timescale as f64)
+}
+
+/// Reads an EBML variable-length integer, returns the value
+/// and its length.
+fn vint(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    let mask = if len == 8 { 0 } else { 0xff >> len };
+    let value = data
+        .get(1..len)?
+        .iter()
+        .fold((first & mask) as u64, |acc, b| (acc << 8) | *b as u64);
+    Some((value, len))
+}
+
+/// Finds an element with given id among sibling elements,
+/// returns its body.
+fn ebml_find<'a>(mut data: &'a [u8], id: &[u8]) -> Option<&'a [u8]> {
+    while !data.is_empty() {
+        let (_, id_len) = vint(data)?;
+        let (size, size_len) = vint(data.get(id_len..)?)?;
+        let header = id_len + size_len;
+        // Unknown-sized elements extend to the end.
+        let end = if size == (1 << (7 * size_len)) - 1 {
+            data.len()
+        } else {
+            header.checked_add(size as usize)?.min(data.len())
+        };
+        if &data[..id_len] == id {
+            return data.get(header..end);
+        }
+        data = &data[end..];
+    }
+    None
+}
+
+/// Reads the duration from the segment info of a WebM file.
+fn webm_duration(data: &[u8]) -> Option<Duration> {
+    const SEGMENT: &[u8] = &[0x18, 0x53, 0x80, 0x67];
+    const INFO: &[u8] = &[0x15, 0x49, 0xa9, 0x66];
+    const TIMECODE_SCALE: &[u8] = &[0x2a, 0xd7, 0xb1];
+    const DURATION: &[u8] = &[0x44, 0x89];
+
+    let segment = ebml_find(data, SEGMENT)?;
+    let info = ebml_find(segment, INFO)?;
+    let scale = ebml_find(info, TIMECODE_SCALE).map_or(1_000_000, be);
+    let duration = ebml_find(info, DURATION)?;
+    let duration = match duration.len() {
+        4 => f32::from_be_bytes(duration.try_into().ok()?) as f64,
+        8 => f64::from_be_bytes(duration.try_into().ok()?),
+        _ => return None,
+    };
+    seconds(duration * scale as f64 / 1e9)
+}
+
+/// Converts seconds into a duration, rejecting invalid values.
+#[inline]
+fn seconds(secs: f64) -> Option<Duration> {
+    (secs.is_finite() && (0.0..=u32::MAX as f64).contains(&secs))
+        .then(|| Duration::seconds_f64(secs))
+}
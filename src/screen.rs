@@ -0,0 +1,175 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+/// A physical display device showing posts in rotation.
+///
+/// # dmds Integration
+///
+/// The id is derived from the token, see [`Self::id_of`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Screen {
+    #[serde(skip)]
+    id: u64,
+    /// SHA-256 hash of the secret token the device authorizes with.
+    token_hash: [u8; 32],
+    name: String,
+    /// Where the device is placed.
+    location: String,
+
+    /// Ids of posts rotating on this screen.
+    posts: Vec<u64>,
+
+    #[serde(with = "time::serde::timestamp")]
+    created_at: OffsetDateTime,
+    /// Last time the device fetched its playlist.
+    #[serde(with = "time::serde::timestamp::option")]
+    last_seen: Option<OffsetDateTime>,
+}
+
+impl Screen {
+    /// Length of generated tokens.
+    const TOKEN_LEN: usize = 32;
+
+    /// Registers a new screen with a generated token,
+    /// returning the screen and the token.
+    ///
+    /// Only a hash of the token is stored.
+    pub fn new(name: String, location: String) -> (Self, String) {
+        let token = crate::account::password::generate(Self::TOKEN_LEN);
+        let this = Self {
+            id: Self::id_of(&token),
+            token_hash: Self::hash_token(&token),
+            name,
+            location,
+            posts: vec![],
+            created_at: OffsetDateTime::now_utc(),
+            last_seen: None,
+        };
+        (this, token)
+    }
+
+    /// Gets the id of a screen with given token.
+    #[inline]
+    pub fn id_of(token: &str) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new();
+        token.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    fn hash_token(token: &str) -> [u8; 32] {
+        Sha256::digest(token.as_bytes()).into()
+    }
+
+    /// Whether the token is the token of this screen,
+    /// compared in constant time.
+    pub fn verify_token(&self, token: &str) -> bool {
+        Self::hash_token(token)
+            .iter()
+            .zip(self.token_hash)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Ids of posts rotating on this screen.
+    #[inline]
+    pub fn posts(&self) -> &[u64] {
+        &self.posts
+    }
+
+    #[inline]
+    pub fn set_posts(&mut self, posts: Vec<u64>) {
+        self.posts = posts
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+
+    /// Last time the device fetched its playlist.
+    #[inline]
+    pub fn last_seen(&self) -> Option<OffsetDateTime> {
+        self.last_seen
+    }
+
+    #[inline]
+    pub fn touch(&mut self) {
+        self.last_seen = Some(OffsetDateTime::now_utc())
+    }
+}
+
+impl dmds::Data for Screen {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 2;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                /// Layout storing the plaintext token.
+                #[derive(Deserialize)]
+                struct ScreenV1 {
+                    token: String,
+                    name: String,
+                    location: String,
+                    posts: Vec<u64>,
+                    #[serde(with = "time::serde::timestamp")]
+                    created_at: OffsetDateTime,
+                    #[serde(with = "time::serde::timestamp::option")]
+                    last_seen: Option<OffsetDateTime>,
+                }
+
+                let v1: ScreenV1 = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                Ok(Self {
+                    id: dims[0],
+                    token_hash: Self::hash_token(&v1.token),
+                    name: v1.name,
+                    location: v1.location,
+                    posts: v1.posts,
+                    created_at: v1.created_at,
+                    last_seen: v1.last_seen,
+                })
+            }
+            2 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
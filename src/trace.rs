@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+
+/// Header carrying the request id in responses.
+pub const HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Gets the id of the request being handled by this task.
+///
+/// Tasks spawned by handlers don't inherit the id.
+#[inline]
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Generates an id for each request, and logs the method, route,
+/// status and latency of the request in a span with the id.
+///
+/// The route template is logged instead of the path, so tokens
+/// in paths are not leaked into logs.
+///
+/// The id is also returned in the [`HEADER`] header.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let id = format!("{:016x}", crate::id::next());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or("<unmatched>", MatchedPath::as_str),
+    );
+    let start = Instant::now();
+    let mut res = REQUEST_ID
+        .scope(id.clone(), next.run(req))
+        .instrument(span.clone())
+        .await;
+    let status = res.status();
+    let latency = start.elapsed().as_micros() as u64;
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!(
+                status = status.as_u16(),
+                latency_us = latency,
+                "request failed"
+            )
+        } else {
+            tracing::info!(
+                status = status.as_u16(),
+                latency_us = latency,
+                "request finished"
+            )
+        }
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(HEADER, value);
+    }
+    res
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashSet, VecDeque};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// An outgoing webhook receiving signed post events.
+///
+/// # dmds Dimensions
+///
+/// ```txt
+/// 0 -> id
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(skip)]
+    id: u64,
+    url: String,
+    /// Secret of HMAC-SHA256 signatures.
+    secret: String,
+    /// Subscribed events, all events if empty.
+    events: HashSet<EventKind>,
+    #[serde(with = "time::serde::timestamp")]
+    created_at: OffsetDateTime,
+    /// Recent deliveries, the most recent first.
+    deliveries: VecDeque<Delivery>,
+}
+
+impl Webhook {
+    /// Length of generated secrets.
+    const SECRET_LEN: usize = 32;
+    /// Max count of kept deliveries.
+    const MAX_DELIVERIES: usize = 50;
+
+    /// Creates a new webhook with a generated secret.
+    pub fn new(url: String, events: HashSet<EventKind>) -> Self {
+        Self {
+            id: crate::id::next(),
+            url,
+            secret: crate::account::password::generate(Self::SECRET_LEN),
+            events,
+            created_at: OffsetDateTime::now_utc(),
+            deliveries: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    #[inline]
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    #[inline]
+    pub fn events(&self) -> &HashSet<EventKind> {
+        &self.events
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+
+    /// Whether the webhook subscribes the event.
+    #[inline]
+    pub fn subscribes(&self, kind: EventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    /// Signs the body with the secret, as lowercase hex.
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(body);
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Recent deliveries, the most recent first.
+    #[inline]
+    pub fn deliveries(&self) -> &VecDeque<Delivery> {
+        &self.deliveries
+    }
+
+    /// Records a delivery.
+    pub fn push_delivery(&mut self, delivery: Delivery) {
+        self.deliveries.push_front(delivery);
+        self.deliveries.truncate(Self::MAX_DELIVERIES);
+    }
+}
+
+impl dmds::Data for Webhook {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    #[inline]
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let mut this: Self = bincode::deserialize_from(buf.reader())
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                this.id = dims[0];
+                Ok(this)
+            }
+            _ => unreachable!("unsupported data version {version}"),
+        }
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode::serialize_into(buf.writer(), self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
+
+/// Kind of a post event.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Created,
+    Approved,
+    Rejected,
+    Archived,
+    ChangesRequested,
+}
+
+/// A post event sent to webhooks as JSON.
+#[derive(Debug, Serialize, Clone)]
+pub struct Event {
+    pub kind: EventKind,
+    pub post: u64,
+    #[serde(with = "time::serde::timestamp")]
+    pub time: OffsetDateTime,
+}
+
+impl Event {
+    /// Creates an event at the current time.
+    #[inline]
+    pub fn new(kind: EventKind, post: u64) -> Self {
+        Self {
+            kind,
+            post,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// A delivery of an event to a webhook.
+#[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
+pub struct Delivery {
+    pub kind: EventKind,
+    pub post: u64,
+    #[serde(with = "time::serde::timestamp")]
+    #[schema(value_type = i64)]
+    pub time: OffsetDateTime,
+    pub attempts: u32,
+    /// HTTP status of the last attempt.
+    pub status: Option<u16>,
+    /// Error of the last attempt.
+    pub error: Option<String>,
+}
+
+impl Delivery {
+    /// Whether the event was delivered successfully.
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.status.map_or(false, |s| (200..300).contains(&s))
+    }
+}